@@ -0,0 +1,26 @@
+// Builds the sonar-hook helper and puts it where Tauri expects the
+// `binaries/sonar-hook` sidecar: suffixed with the target triple. Run before
+// `tauri dev` and `tauri build`, which pass the triple they build for.
+import { execFileSync } from "node:child_process";
+import { copyFileSync, mkdirSync } from "node:fs";
+import { join } from "node:path";
+
+const manifest = join("src-tauri", "Cargo.toml");
+const target =
+  process.env.TAURI_ENV_TARGET_TRIPLE ||
+  execFileSync("rustc", ["-vV"], { encoding: "utf8" }).match(/^host: (\S+)$/m)[1];
+const ext = target.includes("windows") ? ".exe" : "";
+
+// Without the app feature, so this doesn't need the sidecar it is building.
+execFileSync(
+  "cargo",
+  ["build", "--release", "--manifest-path", manifest, "--no-default-features", "--bin", "sonar-hook", "--target", target],
+  { stdio: "inherit" },
+);
+
+const dir = join("src-tauri", "binaries");
+mkdirSync(dir, { recursive: true });
+copyFileSync(
+  join("src-tauri", "target", target, "release", `sonar-hook${ext}`),
+  join(dir, `sonar-hook-${target}${ext}`),
+);
@@ -0,0 +1,158 @@
+pub mod routes;
+
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Listener};
+use tauri::async_runtime::JoinHandle;
+use tokio::sync::{broadcast, watch};
+
+use crate::collector::watcher::SessionWatcher;
+use crate::db::Database;
+use crate::pty::manager::PtyManager;
+
+const API_SETTINGS_KEY: &str = "api_server";
+const DEFAULT_PORT: u16 = 7421;
+
+/// App events forwarded to `/api/events` subscribers.
+const STREAMED_EVENTS: &[&str] = &[
+    "session-update",
+    "pty-exit",
+    "anomaly-detected",
+    "loop-detected",
+    "agent-session-started",
+    "tool-started",
+    "tool-finished",
+    "agent-stopped",
+    "agent-needs-attention",
+    "policy-denied",
+];
+
+/// The local HTTP API. Off until enabled; it only ever listens on
+/// 127.0.0.1 and every request needs the token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String,
+    /// Also serve `/metrics` for Prometheus, behind the same token.
+    #[serde(default)]
+    pub metrics: bool,
+}
+
+impl ApiSettings {
+    pub fn load(db: &Database) -> Result<Self, String> {
+        let stored = db
+            .get_setting(API_SETTINGS_KEY)?
+            .and_then(|v| serde_json::from_str::<ApiSettings>(&v).ok());
+        match stored {
+            Some(settings) => Ok(settings),
+            None => {
+                let settings = ApiSettings { enabled: false, port: DEFAULT_PORT, token: new_token(), metrics: false };
+                settings.save(db)?;
+                Ok(settings)
+            }
+        }
+    }
+
+    pub fn save(&self, db: &Database) -> Result<(), String> {
+        if self.token.len() < 32 {
+            return Err("The API token must be at least 32 characters".to_string());
+        }
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        db.set_setting(API_SETTINGS_KEY, &json)
+    }
+}
+
+pub fn new_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+/// An app event as sent over SSE; `data` is the event's JSON payload.
+#[derive(Debug, Clone)]
+pub struct StreamedEvent {
+    pub event: String,
+    pub data: String,
+}
+
+/// Runs the API server in the background and restarts it when its settings
+/// change. App events are collected from the moment it is created, whether
+/// or not the server is running.
+pub struct ApiServer {
+    events: broadcast::Sender<StreamedEvent>,
+    /// Source of `/metrics`, and told about agents spawned through the API.
+    watcher: Arc<SessionWatcher>,
+    running: Mutex<Option<(watch::Sender<bool>, JoinHandle<()>)>>,
+}
+
+impl ApiServer {
+    pub fn new(app: &AppHandle, watcher: Arc<SessionWatcher>) -> Self {
+        let (events, _) = broadcast::channel(256);
+        for name in STREAMED_EVENTS {
+            let sender = events.clone();
+            app.listen_any(*name, move |event| {
+                let _ = sender.send(StreamedEvent {
+                    event: name.to_string(),
+                    data: event.payload().to_string(),
+                });
+            });
+        }
+        Self { events, watcher, running: Mutex::new(None) }
+    }
+
+    /// Stops the running server, then starts it again if `settings` enable it.
+    pub fn apply(
+        &self,
+        settings: &ApiSettings,
+        app: AppHandle,
+        db: Arc<Database>,
+        pty: Arc<PtyManager>,
+    ) -> Result<(), String> {
+        let mut running = self.running.lock().map_err(|e| e.to_string())?;
+        if let Some((stop, task)) = running.take() {
+            // Event streams end on the same signal, so this only waits for
+            // requests in flight and the port is free again afterwards.
+            let _ = stop.send(true);
+            let _ = tauri::async_runtime::block_on(task);
+        }
+        if !settings.enabled {
+            return Ok(());
+        }
+
+        // Bind here so a taken port is reported to the caller.
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, settings.port));
+        let listener = TcpListener::bind(addr).map_err(|e| format!("Can't listen on {}: {}", addr, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        let (stop, stopped) = watch::channel(false);
+        let state = routes::ApiState {
+            db,
+            pty,
+            sink: Arc::new(app),
+            token: Arc::from(settings.token.as_str()),
+            events: self.events.clone(),
+            watcher: self.watcher.clone(),
+            metrics: settings.metrics.then(|| self.watcher.metrics()),
+            shutdown: stopped.clone(),
+        };
+        let task = tauri::async_runtime::spawn(async move {
+            let listener = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    log::error!("API server failed: {}", e);
+                    return;
+                }
+            };
+            let mut stopped = stopped;
+            let shutdown = async move {
+                let _ = stopped.wait_for(|stop| *stop).await;
+            };
+            if let Err(e) = axum::serve(listener, routes::router(state)).with_graceful_shutdown(shutdown).await {
+                log::error!("API server failed: {}", e);
+            }
+        });
+        log::info!("API server listening on {}", addr);
+        *running = Some((stop, task));
+        Ok(())
+    }
+}
@@ -0,0 +1,437 @@
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+
+use super::StreamedEvent;
+use crate::collector::metrics::Metrics;
+use crate::collector::watcher::{EventSink, SessionWatcher};
+use crate::db::calendar::Bucket;
+use crate::db::query::SessionQuery;
+use crate::db::report::ReportGroup;
+use crate::db::Database;
+use crate::pty::manager::{AgentOptions, PtyInfo, PtyManager};
+
+#[derive(Clone)]
+pub struct ApiState {
+    pub db: Arc<Database>,
+    pub pty: Arc<PtyManager>,
+    /// The app window, which shows the terminals spawned through the API.
+    pub sink: Arc<dyn EventSink>,
+    pub watcher: Arc<SessionWatcher>,
+    pub token: Arc<str>,
+    pub events: broadcast::Sender<StreamedEvent>,
+    /// `None` when `/metrics` is turned off.
+    pub metrics: Option<Arc<Metrics>>,
+    pub shutdown: watch::Receiver<bool>,
+}
+
+/// Every route answers JSON; handler errors are `{"error": "..."}`.
+pub fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/api/sessions", get(sessions))
+        .route("/api/sessions/{id}", get(session))
+        .route("/api/sessions/{id}/messages", get(session_messages))
+        .route("/api/sessions/{id}/context", get(session_context))
+        .route("/api/stats/daily", get(daily_costs))
+        .route("/api/stats/buckets", get(cost_buckets))
+        .route("/api/stats/models", get(model_stats))
+        .route("/api/stats/projects", get(project_stats))
+        .route("/api/stats/branches", get(branch_stats))
+        .route("/api/stats/tools", get(tool_stats))
+        .route("/api/stats/cache", get(cache_stats))
+        .route("/api/usage/report", get(usage_report))
+        .route("/api/usage/today", get(cost_today))
+        .route("/api/usage/blocks", get(usage_blocks))
+        .route("/api/usage/current-block", get(current_block))
+        .route("/api/usage/forecast", get(spend_forecast))
+        .route("/api/anomalies", get(anomalies))
+        .route("/api/search", get(search))
+        .route("/api/agents", get(list_agents).post(spawn_agent))
+        .route("/api/agents/{id}", axum::routing::delete(stop_agent))
+        .route("/api/agents/{id}/input", post(write_agent))
+        .route("/api/events", get(events))
+        .route("/metrics", get(metrics))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+pub struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        ApiError(StatusCode::BAD_REQUEST, message)
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// Runs a database call off the async workers.
+async fn blocking<T, F>(state: &ApiState, f: F) -> ApiResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Database) -> Result<T, String> + Send + 'static,
+{
+    let db = state.db.clone();
+    tokio::task::spawn_blocking(move || f(&db))
+        .await
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(Json)
+        .map_err(ApiError::from)
+}
+
+/// Accepts `Authorization: Bearer <token>`, or `?token=` for clients like
+/// `EventSource` that can't set headers.
+async fn authorize(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let from_header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let from_query = Query::<TokenQuery>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(q)| q.token);
+
+    match from_header.or(from_query.as_deref()) {
+        Some(token) if constant_time_eq(token.as_bytes(), state.token.as_bytes()) => next.run(request).await,
+        _ => ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid token".to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Deserialize)]
+struct Range {
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<u32>,
+}
+
+impl Range {
+    fn required(&self) -> Result<(String, String), ApiError> {
+        match (&self.from, &self.to) {
+            (Some(from), Some(to)) => Ok((from.clone(), to.clone())),
+            _ => Err(ApiError(StatusCode::BAD_REQUEST, "from and to are required".to_string())),
+        }
+    }
+}
+
+async fn sessions(State(s): State<ApiState>, Query(query): Query<SessionQuery>) -> impl IntoResponse {
+    blocking(&s, move |db| db.query_sessions(&query)).await
+}
+
+async fn session(State(s): State<ApiState>, Path(id): Path<String>) -> impl IntoResponse {
+    match blocking(&s, move |db| db.get_session(&id)).await {
+        Ok(Json(Some(session))) => Ok(Json(session)),
+        Ok(Json(None)) => Err(ApiError(StatusCode::NOT_FOUND, "No such session".to_string())),
+        Err(e) => Err(e),
+    }
+}
+
+async fn session_messages(State(s): State<ApiState>, Path(id): Path<String>) -> impl IntoResponse {
+    blocking(&s, move |db| db.get_session_messages(&id)).await
+}
+
+async fn session_context(State(s): State<ApiState>, Path(id): Path<String>) -> impl IntoResponse {
+    blocking(&s, move |db| db.get_session_context(&id)).await
+}
+
+#[derive(Deserialize)]
+struct DaysQuery {
+    days: Option<u32>,
+}
+
+async fn daily_costs(State(s): State<ApiState>, Query(q): Query<DaysQuery>) -> impl IntoResponse {
+    blocking(&s, move |db| db.get_daily_costs(q.days.unwrap_or(30))).await
+}
+
+#[derive(Deserialize)]
+struct BucketQuery {
+    bucket: Bucket,
+    periods: Option<u32>,
+}
+
+async fn cost_buckets(State(s): State<ApiState>, Query(q): Query<BucketQuery>) -> impl IntoResponse {
+    blocking(&s, move |db| db.get_cost_buckets(q.bucket, q.periods.unwrap_or(12))).await
+}
+
+async fn model_stats(State(s): State<ApiState>) -> impl IntoResponse {
+    blocking(&s, |db| db.get_model_stats()).await
+}
+
+async fn project_stats(State(s): State<ApiState>, Query(range): Query<Range>) -> impl IntoResponse {
+    let (from, to) = range.required()?;
+    blocking(&s, move |db| db.get_project_stats(&from, &to)).await
+}
+
+async fn branch_stats(State(s): State<ApiState>, Query(range): Query<Range>) -> impl IntoResponse {
+    let (from, to) = range.required()?;
+    blocking(&s, move |db| db.get_branch_stats(&from, &to)).await
+}
+
+async fn tool_stats(State(s): State<ApiState>, Query(range): Query<Range>) -> impl IntoResponse {
+    blocking(&s, move |db| db.get_tool_stats(range.from.as_deref(), range.to.as_deref())).await
+}
+
+async fn cache_stats(State(s): State<ApiState>, Query(range): Query<Range>) -> impl IntoResponse {
+    blocking(&s, move |db| db.get_cache_stats(range.from.as_deref(), range.to.as_deref(), range.limit)).await
+}
+
+#[derive(Deserialize)]
+struct ReportQuery {
+    from: String,
+    to: Option<String>,
+    by: ReportGroup,
+}
+
+async fn usage_report(State(s): State<ApiState>, Query(q): Query<ReportQuery>) -> impl IntoResponse {
+    blocking(&s, move |db| db.get_usage_report(&q.from, q.to.as_deref(), q.by)).await
+}
+
+async fn cost_today(State(s): State<ApiState>) -> impl IntoResponse {
+    blocking(&s, |db| db.get_total_cost_today()).await
+}
+
+async fn usage_blocks(State(s): State<ApiState>, Query(range): Query<Range>) -> impl IntoResponse {
+    blocking(&s, move |db| db.get_usage_blocks(range.from.as_deref())).await
+}
+
+async fn current_block(State(s): State<ApiState>) -> impl IntoResponse {
+    blocking(&s, |db| db.get_current_block()).await
+}
+
+async fn spend_forecast(State(s): State<ApiState>) -> impl IntoResponse {
+    blocking(&s, |db| db.get_spend_forecast()).await
+}
+
+async fn anomalies(State(s): State<ApiState>, Query(range): Query<Range>) -> impl IntoResponse {
+    blocking(&s, move |db| db.get_anomalies(range.limit.unwrap_or(50))).await
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+    limit: Option<u32>,
+}
+
+async fn search(State(s): State<ApiState>, Query(q): Query<SearchQuery>) -> impl IntoResponse {
+    blocking(&s, move |db| db.search_transcripts(&q.q, q.limit)).await
+}
+
+async fn list_agents(State(s): State<ApiState>) -> ApiResult<Vec<PtyInfo>> {
+    Ok(Json(s.pty.list()?))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SpawnKind {
+    Shell,
+    Agent,
+}
+
+#[derive(Deserialize)]
+struct SpawnRequest {
+    /// Generated when missing.
+    agent_id: Option<String>,
+    kind: SpawnKind,
+    working_dir: String,
+    /// Prompt for `agent`; an empty task starts an interactive session.
+    #[serde(default)]
+    task: String,
+    rows: Option<u16>,
+    cols: Option<u16>,
+    /// Permission mode, tool lists and extra directories for `agent`.
+    #[serde(default, flatten)]
+    options: AgentOptions,
+}
+
+#[derive(Serialize)]
+struct Spawned {
+    agent_id: String,
+}
+
+async fn spawn_agent(State(s): State<ApiState>, Json(req): Json<SpawnRequest>) -> ApiResult<Spawned> {
+    let agent_id = req.agent_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    if s.pty.is_alive(&agent_id) {
+        return Err(ApiError(StatusCode::CONFLICT, format!("Agent {} already exists", agent_id)));
+    }
+    match req.kind {
+        SpawnKind::Shell => s.pty.spawn_shell(
+            &agent_id,
+            &req.working_dir,
+            req.rows.unwrap_or(40),
+            req.cols.unwrap_or(120),
+            s.sink.clone(),
+        )?,
+        SpawnKind::Agent => {
+            s.pty.spawn_agent(&agent_id, &req.task, &req.working_dir, &req.options, s.sink.clone())?;
+            s.watcher.register_agent(&agent_id, &req.working_dir);
+            s.watcher.set_permission_mode(&agent_id, req.options.permission_mode);
+        }
+    }
+    Ok(Json(Spawned { agent_id }))
+}
+
+#[derive(Deserialize)]
+struct WriteRequest {
+    data: String,
+}
+
+fn not_found(e: String) -> ApiError {
+    ApiError(StatusCode::NOT_FOUND, e)
+}
+
+async fn write_agent(
+    State(s): State<ApiState>,
+    Path(id): Path<String>,
+    Json(req): Json<WriteRequest>,
+) -> Result<StatusCode, ApiError> {
+    if !s.pty.is_alive(&id) {
+        return Err(not_found(format!("Agent {} not found", id)));
+    }
+    s.pty.write_to_agent(&id, &req.data)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn stop_agent(State(s): State<ApiState>, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    s.pty.kill_agent(&id).map_err(not_found)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Prometheus text exposition of the watcher's counters and the terminals'
+/// state.
+async fn metrics(State(s): State<ApiState>) -> Result<Response, ApiError> {
+    let Some(metrics) = s.metrics.clone() else {
+        return Err(ApiError(StatusCode::NOT_FOUND, "Metrics are turned off".to_string()));
+    };
+    let agents = s.pty.list()?;
+    let body = metrics.render(&agents);
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response())
+}
+
+/// Server-sent events named after the app events they carry, with the same
+/// JSON payloads. Subscribers that fall behind skip what they missed.
+async fn events(State(s): State<ApiState>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = s.events.subscribe();
+    let stream = stream::unfold((receiver, s.shutdown.clone()), |(mut receiver, mut shutdown)| async move {
+        loop {
+            let received = tokio::select! {
+                _ = shutdown.wait_for(|stop| *stop) => return None,
+                received = receiver.recv() => received,
+            };
+            match received {
+                Ok(e) => {
+                    let event = Event::default().event(e.event).data(e.data);
+                    return Some((Ok(event), (receiver, shutdown)));
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use crate::db::test_util::temp_path;
+
+    const TOKEN: &str = "s3cret+token/with=symbols";
+
+    struct NoEvents;
+
+    impl EventSink for NoEvents {
+        fn send(&self, _event: &str, _payload: serde_json::Value) {}
+    }
+
+    fn app(name: &str) -> Router {
+        let db = Arc::new(Database::open(&temp_path(name)).unwrap());
+        let (_, shutdown) = watch::channel(false);
+        router(ApiState {
+            watcher: Arc::new(SessionWatcher::new(db.clone())),
+            db,
+            pty: Arc::new(PtyManager::new()),
+            sink: Arc::new(NoEvents),
+            token: Arc::from(TOKEN),
+            events: broadcast::channel(1).0,
+            metrics: None,
+            shutdown,
+        })
+    }
+
+    async fn get(app: &Router, uri: &str, bearer: Option<&str>) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(token) = bearer {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn requests_need_the_token() {
+        let app = app("api-auth");
+        let unauthorized = serde_json::json!({ "error": "Missing or invalid token" });
+
+        assert_eq!(get(&app, "/api/stats/models", None).await, (StatusCode::UNAUTHORIZED, unauthorized.clone()));
+        assert_eq!(get(&app, "/api/stats/models", Some("s3cret")).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(get(&app, "/api/stats/models?token=s3cret", None).await.0, StatusCode::UNAUTHORIZED);
+
+        assert_eq!(get(&app, "/api/stats/models", Some(TOKEN)).await, (StatusCode::OK, serde_json::json!([])));
+        // EventSource clients put it in the query, percent-encoded.
+        let (status, _) = get(&app, "/api/usage/today?token=s3cret%2Btoken%2Fwith%3Dsymbols", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = get(&app, &format!("/api/usage/today?token={}", TOKEN), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn bad_input_is_reported_as_a_json_error() {
+        let app = app("api-errors");
+
+        let (status, body) = get(&app, "/api/stats/projects?from=2026-03-01", Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, serde_json::json!({ "error": "from and to are required" }));
+
+        let (status, body) = get(&app, "/api/stats/projects?from=soon&to=2026-03-08", Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, serde_json::json!({ "error": "Invalid date: soon" }));
+
+        let (status, body) = get(&app, "/api/sessions/missing", Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, serde_json::json!({ "error": "No such session" }));
+
+        let (status, body) = get(&app, "/metrics", Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "Metrics are turned off");
+    }
+}
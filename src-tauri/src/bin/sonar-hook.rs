@@ -0,0 +1,52 @@
+//! Claude Code hook command. Forwards the hook payload on stdin to the
+//! running app over its Unix socket and prints the app's reply, if any, as
+//! the hook's output. When the app isn't running it does nothing, so a
+//! closed Sonar never gets in Claude Code's way.
+
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// Long enough for the app to decide on a tool call, short enough that a
+/// hung app doesn't stall the agent.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn main() {
+    let mut payload = Vec::new();
+    if std::io::stdin().read_to_end(&mut payload).is_err() {
+        return;
+    }
+    if let Some(reply) = forward(&payload) {
+        let _ = std::io::stdout().write_all(&reply);
+    }
+}
+
+/// Sends the agent id the app set on the terminal, a newline, then the
+/// payload, and reads the reply until the app closes the connection.
+#[cfg(unix)]
+fn forward(payload: &[u8]) -> Option<Vec<u8>> {
+    use std::net::Shutdown;
+    use std::os::unix::net::UnixStream;
+
+    let path = match std::env::var_os("SONAR_HOOK_SOCKET") {
+        Some(path) => std::path::PathBuf::from(path),
+        None => std::path::PathBuf::from(std::env::var_os("HOME")?).join(".open-sonar").join("hooks.sock"),
+    };
+    let mut stream = UnixStream::connect(path).ok()?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(REPLY_TIMEOUT)).ok()?;
+
+    let agent_id = std::env::var("SONAR_AGENT_ID").unwrap_or_default();
+    stream.write_all(agent_id.trim().as_bytes()).ok()?;
+    stream.write_all(b"\n").ok()?;
+    stream.write_all(payload).ok()?;
+    stream.shutdown(Shutdown::Write).ok()?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).ok()?;
+    Some(reply)
+}
+
+#[cfg(not(unix))]
+fn forward(_payload: &[u8]) -> Option<Vec<u8>> {
+    None
+}
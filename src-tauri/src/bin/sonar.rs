@@ -0,0 +1,490 @@
+//! Headless Open Sonar: the app's importer, reports, exports and watcher
+//! without the window, against the same database.
+
+use app_lib::collector::import::import_history;
+use app_lib::collector::otlp::{export_query, OtlpSettings};
+use app_lib::collector::transcript::{export_transcript, TranscriptFormat, TranscriptOptions};
+use app_lib::collector::watcher::{EventSink, SessionWatcher};
+use app_lib::db::calendar;
+use app_lib::db::columnar::DatasetFormat;
+use app_lib::db::export::{ExportFormat, ExportReport};
+use app_lib::db::query::{SessionQuery, SessionSort};
+use app_lib::db::report::ReportGroup;
+use app_lib::db::Database;
+use chrono::{Duration, Local, Utc};
+use clap::{Args, Parser, Subcommand};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[command(name = "sonar", version, about = "Claude Code usage from the command line")]
+struct Cli {
+    /// Database file to use instead of the app's
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Import transcripts from the Claude projects directory that aren't stored yet
+    Import,
+    /// Usage totals over a period
+    Report {
+        /// Start of the period: a duration back from now (30m, 12h, 7d, 2w) or a date
+        #[arg(long, default_value = "7d")]
+        since: String,
+        /// End of the period, exclusive; defaults to now
+        #[arg(long)]
+        until: Option<String>,
+        /// project, model, agent or day
+        #[arg(long, default_value = "project", value_parser = parse_enum::<ReportGroup>)]
+        by: ReportGroup,
+        #[arg(long)]
+        json: bool,
+    },
+    /// List sessions, most recent first
+    Sessions {
+        #[command(flatten)]
+        filter: Filter,
+        /// started_at, total_cost, total_tokens, duration_ms or tool_calls
+        #[arg(long, default_value = "started_at", value_parser = parse_enum::<SessionSort>)]
+        sort: SessionSort,
+        /// Sort ascending
+        #[arg(long)]
+        asc: bool,
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write sessions, messages, a columnar dataset or a transcript to disk, or send sessions over OTLP
+    Export {
+        #[command(subcommand)]
+        target: ExportTarget,
+    },
+    /// Follow transcripts as Claude Code writes them, storing usage and printing events
+    Watch {
+        /// Print one JSON object per event
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExportTarget {
+    /// Matching sessions as csv, json or ndjson
+    Sessions {
+        #[command(flatten)]
+        filter: Filter,
+        #[arg(long, default_value = "csv", value_parser = parse_enum::<ExportFormat>)]
+        format: ExportFormat,
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Messages of matching sessions as csv, json or ndjson
+    Messages {
+        #[command(flatten)]
+        filter: Filter,
+        #[arg(long, default_value = "csv", value_parser = parse_enum::<ExportFormat>)]
+        format: ExportFormat,
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Sessions, messages and tool calls as a month-partitioned parquet or arrow dataset
+    Dataset {
+        #[command(flatten)]
+        filter: Filter,
+        #[arg(long, default_value = "parquet", value_parser = parse_enum::<DatasetFormat>)]
+        format: DatasetFormat,
+        /// Directory to write the dataset under
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// One session's transcript as markdown or html
+    Transcript {
+        session_id: String,
+        #[arg(long, default_value = "markdown", value_parser = parse_enum::<TranscriptFormat>)]
+        format: TranscriptFormat,
+        #[arg(long, short)]
+        output: PathBuf,
+        /// Keep secrets in the output
+        #[arg(long)]
+        no_redact: bool,
+        /// Fold tool input and output longer than this many characters; 0 never folds
+        #[arg(long)]
+        collapse_over: Option<usize>,
+        /// Cut tool output after this many characters; 0 keeps it whole
+        #[arg(long)]
+        max_tool_result: Option<usize>,
+    },
+    /// Matching sessions as OTLP traces and token and cost metrics
+    Otlp {
+        #[command(flatten)]
+        filter: Filter,
+        /// Collector base URL; defaults to the app's OTLP setting
+        #[arg(long)]
+        endpoint: Option<String>,
+    },
+}
+
+#[derive(Args)]
+struct Filter {
+    /// Sessions started after this: a duration back from now (30m, 12h, 7d, 2w) or a date
+    #[arg(long)]
+    since: Option<String>,
+    /// Sessions started before this
+    #[arg(long)]
+    until: Option<String>,
+    /// Encoded project name or working directory
+    #[arg(long)]
+    project: Option<String>,
+    #[arg(long)]
+    model: Option<String>,
+    #[arg(long)]
+    agent: Option<String>,
+    #[arg(long)]
+    status: Option<String>,
+    /// Sessions that called this tool
+    #[arg(long)]
+    tool: Option<String>,
+    /// Text in the session id, project, branch, model or message previews
+    #[arg(long)]
+    text: Option<String>,
+}
+
+impl Filter {
+    fn query(&self, db: &Database) -> Result<SessionQuery, String> {
+        Ok(SessionQuery {
+            from: self.since.as_deref().map(|s| resolve_bound(db, s)).transpose()?,
+            to: self.until.as_deref().map(|s| resolve_bound(db, s)).transpose()?,
+            project: self.project.clone(),
+            model: self.model.clone(),
+            agent_id: self.agent.clone(),
+            status: self.status.clone(),
+            tool_name: self.tool.clone(),
+            text: self.text.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Reads the lowercase names the app's commands take, through their serde names.
+fn parse_enum<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(Value::String(value.to_string())).map_err(|_| format!("unknown value '{}'", value))
+}
+
+/// `30m`, `12h`, `7d` or `2w` back from now, otherwise a date or RFC 3339
+/// timestamp; returned in the stored timestamp format. Durations can't be
+/// negative, and a number without a unit is not one.
+fn resolve_bound(db: &Database, value: &str) -> Result<String, String> {
+    let relative = value
+        .char_indices()
+        .last()
+        .and_then(|(i, unit)| Some((i64::from(value[..i].parse::<u32>().ok()?), unit)))
+        .and_then(|(n, unit)| match unit {
+            'm' => Duration::try_minutes(n),
+            'h' => Duration::try_hours(n),
+            'd' => Duration::try_days(n),
+            'w' => Duration::try_weeks(n),
+            _ => None,
+        });
+    let ts = match relative {
+        Some(ago) => Utc::now()
+            .checked_sub_signed(ago)
+            .ok_or_else(|| format!("Invalid time '{}': too far back", value))?,
+        None => calendar::parse_bound(value, db.get_timezone()?)
+            .ok_or_else(|| format!("Invalid time '{}': use 30m, 12h, 7d, 2w, YYYY-MM-DD or RFC 3339", value))?,
+    };
+    Ok(calendar::format_utc(ts))
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> Result<(), String> {
+    println!("{}", serde_json::to_string_pretty(value).map_err(|e| e.to_string())?);
+    Ok(())
+}
+
+/// Left-aligns the first `text_columns` columns and right-aligns the rest.
+fn print_table(headers: &[&str], rows: &[Vec<String>], text_columns: usize) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                if i < text_columns {
+                    format!("{:<width$}", cell, width = widths[i])
+                } else {
+                    format!("{:>width$}", cell, width = widths[i])
+                }
+            })
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(headers.to_vec());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let tail: String = text.chars().rev().take(max - 1).collect::<Vec<_>>().into_iter().rev().collect();
+    format!("…{}", tail)
+}
+
+fn print_export(report: &ExportReport) {
+    println!("Wrote {} rows ({} bytes) to {}", report.rows, report.bytes, report.path);
+}
+
+/// Prints watcher events as they arrive.
+struct StdoutSink {
+    json: bool,
+}
+
+impl EventSink for StdoutSink {
+    fn send(&self, event: &str, payload: Value) {
+        if self.json {
+            println!("{}", json!({ "event": event, "payload": payload }));
+            return;
+        }
+        let time = Local::now().format("%H:%M:%S");
+        let text = |key: &str| payload.get(key).and_then(Value::as_str).unwrap_or("").to_string();
+        let number = |key: &str| payload.get(key).and_then(Value::as_f64).unwrap_or(0.0);
+        match event {
+            "session-update" => println!(
+                "{}  {}  {}  {} messages  {} tokens  ${:.4}",
+                time, text("agent_id"), text("model"), number("message_count"),
+                number("total_tokens"), number("total_cost")
+            ),
+            "agent-detected" => println!("{}  new session {}: {}", time, text("session_id"), text("task")),
+            "anomaly-detected" => println!("{}  anomaly: {}", time, text("reason")),
+            "usage-block-started" => println!("{}  usage block started at {}", time, text("start")),
+            // Every session update refreshes the block; the session line says enough.
+            "usage-block-update" => {}
+            _ => println!("{}  {} {}", time, event, payload),
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), String> {
+    let db = Arc::new(match &cli.db {
+        Some(path) => Database::open(path)?,
+        None => Database::new()?,
+    });
+
+    match cli.command {
+        Command::Import => {
+            let imported = import_history(&db)?;
+            println!("Imported {} sessions", imported);
+        }
+        Command::Report { since, until, by, json } => {
+            let from = resolve_bound(&db, &since)?;
+            let to = until.as_deref().map(|u| resolve_bound(&db, u)).transpose()?;
+            let rows = db.get_usage_report(&from, to.as_deref(), by)?;
+            if json {
+                return print_json(&rows);
+            }
+            let mut table: Vec<Vec<String>> = rows
+                .iter()
+                .map(|r| vec![
+                    truncate(&r.key, 48),
+                    r.messages.to_string(),
+                    r.total_tokens.to_string(),
+                    format!("{:.2}", r.energy_wh),
+                    format!("${:.2}", r.cost),
+                ])
+                .collect();
+            table.push(vec![
+                "total".to_string(),
+                rows.iter().map(|r| r.messages).sum::<u64>().to_string(),
+                rows.iter().map(|r| r.total_tokens).sum::<u64>().to_string(),
+                format!("{:.2}", rows.iter().map(|r| r.energy_wh).sum::<f64>()),
+                format!("${:.2}", rows.iter().map(|r| r.cost).sum::<f64>()),
+            ]);
+            let group = serde_json::to_value(by).ok().and_then(|v| v.as_str().map(str::to_uppercase)).unwrap_or_default();
+            print_table(&[&group, "MESSAGES", "TOKENS", "WH", "COST"], &table, 1);
+        }
+        Command::Sessions { filter, sort, asc, limit, json } => {
+            let query = SessionQuery {
+                sort,
+                descending: Some(!asc),
+                limit: Some(limit),
+                ..filter.query(&db)?
+            };
+            let sessions = db.query_sessions(&query)?.sessions;
+            if json {
+                return print_json(&sessions);
+            }
+            let table: Vec<Vec<String>> = sessions
+                .iter()
+                .map(|s| vec![
+                    calendar::parse_utc(&s.started_at)
+                        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_else(|| s.started_at.clone()),
+                    s.id.chars().take(8).collect(),
+                    truncate(s.cwd.as_deref().unwrap_or(&s.project), 40),
+                    s.model.clone(),
+                    s.status.clone(),
+                    format!("{}m", s.duration_ms / 60_000),
+                    s.total_tokens.to_string(),
+                    format!("${:.2}", s.total_cost),
+                ])
+                .collect();
+            print_table(&["STARTED", "ID", "PROJECT", "MODEL", "STATUS", "DURATION", "TOKENS", "COST"], &table, 5);
+        }
+        Command::Export { target } => match target {
+            ExportTarget::Sessions { filter, format, output } => {
+                print_export(&db.export_sessions(&filter.query(&db)?, format, &output)?);
+            }
+            ExportTarget::Messages { filter, format, output } => {
+                print_export(&db.export_messages(&filter.query(&db)?, format, &output)?);
+            }
+            ExportTarget::Dataset { filter, format, output } => {
+                for file in db.export_dataset(&filter.query(&db)?, format, &output)? {
+                    println!("Wrote {} {} rows for {} to {}", file.rows, file.table, file.month, file.path);
+                }
+            }
+            ExportTarget::Transcript { session_id, format, output, no_redact, collapse_over, max_tool_result } => {
+                let defaults = TranscriptOptions::default();
+                let options = TranscriptOptions {
+                    redact_secrets: !no_redact,
+                    collapse_over: match collapse_over {
+                        Some(0) => None,
+                        Some(n) => Some(n),
+                        None => defaults.collapse_over,
+                    },
+                    max_tool_result: match max_tool_result {
+                        Some(0) => None,
+                        Some(n) => Some(n),
+                        None => defaults.max_tool_result,
+                    },
+                };
+                let report = export_transcript(&db, &session_id, format, &options, &output)?;
+                println!("Wrote {} turns ({} bytes) to {}", report.rows, report.bytes, report.path);
+            }
+            ExportTarget::Otlp { filter, endpoint } => {
+                let mut settings = OtlpSettings::load(&db)?;
+                if let Some(endpoint) = endpoint {
+                    settings.endpoint = endpoint;
+                }
+                let report = export_query(&db, &settings, &filter.query(&db)?)?;
+                println!(
+                    "Sent {} sessions as {} spans and {} metric points to {}",
+                    report.sessions, report.spans, report.metric_points, settings.endpoint
+                );
+            }
+        },
+        Command::Watch { json } => {
+            let mut watcher = SessionWatcher::new(db.clone());
+            watcher.watch_unregistered("cli");
+            watcher.start(Arc::new(StdoutSink { json }))?;
+            if !json {
+                println!("Watching for Claude Code activity, Ctrl-C to stop");
+            }
+            loop {
+                std::thread::park();
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db(name: &str) -> Database {
+        let dir = std::env::temp_dir().join(format!("sonar-cli-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::open(&dir.join("sessions.db")).unwrap();
+        db.set_timezone("UTC").unwrap();
+        db
+    }
+
+    fn ago(db: &Database, value: &str) -> Duration {
+        let bound = calendar::parse_utc(&resolve_bound(db, value).unwrap()).unwrap();
+        Utc::now() - bound
+    }
+
+    #[test]
+    fn durations_count_back_from_now() {
+        let db = temp_db("bounds-relative");
+        let close = |value: &str, expected: Duration| {
+            let got = ago(&db, value);
+            assert!(got >= expected && got - expected < Duration::seconds(5), "{}: {}", value, got);
+        };
+        close("30m", Duration::minutes(30));
+        close("12h", Duration::hours(12));
+        close("7d", Duration::days(7));
+        close("2w", Duration::weeks(2));
+        close("0d", Duration::zero());
+    }
+
+    #[test]
+    fn bare_numbers_and_negative_durations_are_rejected() {
+        let db = temp_db("bounds-invalid");
+        for value in ["30", "-7d", "-30m", "7y", "d", "99999999999w", ""] {
+            assert!(resolve_bound(&db, value).is_err(), "{}", value);
+        }
+        assert!(resolve_bound(&db, "4294967295w").unwrap_err().contains("too far back"));
+    }
+
+    #[test]
+    fn dates_are_local_midnight() {
+        let db = temp_db("bounds-dates");
+        assert_eq!(resolve_bound(&db, "2026-03-01").unwrap(), "2026-03-01T00:00:00.000Z");
+        db.set_timezone("Asia/Kolkata").unwrap();
+        assert_eq!(resolve_bound(&db, "2026-03-01").unwrap(), "2026-02-28T18:30:00.000Z");
+        assert_eq!(resolve_bound(&db, "2026-03-01T10:15:00Z").unwrap(), "2026-03-01T10:15:00.000Z");
+    }
+
+    #[test]
+    fn filters_become_a_session_query() {
+        let db = temp_db("bounds-filter");
+        let filter = |since: &str| Filter {
+            since: Some(since.to_string()),
+            until: Some("2026-03-08".to_string()),
+            project: Some("/work/app".to_string()),
+            model: None,
+            agent: None,
+            status: Some("completed".to_string()),
+            tool: Some("Bash".to_string()),
+            text: None,
+        };
+
+        let query = filter("2026-03-01").query(&db).unwrap();
+        assert_eq!(query.from.as_deref(), Some("2026-03-01T00:00:00.000Z"));
+        assert_eq!(query.to.as_deref(), Some("2026-03-08T00:00:00.000Z"));
+        assert_eq!(query.project.as_deref(), Some("/work/app"));
+        assert_eq!(query.status.as_deref(), Some("completed"));
+        assert_eq!(query.tool_name.as_deref(), Some("Bash"));
+        assert!(query.from < query.to);
+
+        assert!(filter("7d").query(&db).unwrap().from.is_some());
+        assert!(filter("-7d").query(&db).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Spend over one trailing window, averaged per second.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct BurnRate {
+    pub tokens_per_sec: f64,
+    pub cost_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct BurnRates {
+    pub last_minute: BurnRate,
+    pub last_5_minutes: BurnRate,
+    pub last_hour: BurnRate,
+}
+
+struct Sample {
+    at: DateTime<Utc>,
+    tokens: u64,
+    cost: f64,
+}
+
+/// Rolling token and cost rates per agent and across all agents, from the
+/// usage the watcher reads. Samples are placed at the message's own
+/// timestamp, so transcripts read from the start don't count as a burst.
+#[derive(Default)]
+pub struct BurnRateTracker {
+    agents: Mutex<HashMap<String, VecDeque<Sample>>>,
+    global: Mutex<VecDeque<Sample>>,
+}
+
+impl BurnRateTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, agent_id: &str, timestamp: &str, tokens: u64, cost: f64) {
+        let at = match DateTime::parse_from_rfc3339(timestamp) {
+            Ok(at) => at.with_timezone(&Utc),
+            Err(_) => Utc::now(),
+        };
+        let now = Utc::now();
+        if now - at > window_hour() {
+            return;
+        }
+
+        if let Ok(mut agents) = self.agents.lock() {
+            let samples = agents.entry(agent_id.to_string()).or_default();
+            insert(samples, Sample { at, tokens, cost }, now);
+        }
+        if let Ok(mut global) = self.global.lock() {
+            insert(&mut global, Sample { at, tokens, cost }, now);
+        }
+    }
+
+    pub fn agent_rates(&self, agent_id: &str) -> BurnRates {
+        let now = Utc::now();
+        self.agents
+            .lock()
+            .ok()
+            .and_then(|agents| agents.get(agent_id).map(|samples| rates(samples, now)))
+            .unwrap_or_default()
+    }
+
+    pub fn global_rates(&self) -> BurnRates {
+        let now = Utc::now();
+        self.global
+            .lock()
+            .map(|samples| rates(&samples, now))
+            .unwrap_or_default()
+    }
+}
+
+fn window_hour() -> Duration {
+    Duration::hours(1)
+}
+
+/// Keeps samples ordered by time and drops the ones older than an hour.
+fn insert(samples: &mut VecDeque<Sample>, sample: Sample, now: DateTime<Utc>) {
+    let position = samples.iter().rposition(|s| s.at <= sample.at).map_or(0, |i| i + 1);
+    samples.insert(position, sample);
+    while samples.front().is_some_and(|s| now - s.at > window_hour()) {
+        samples.pop_front();
+    }
+}
+
+fn rates(samples: &VecDeque<Sample>, now: DateTime<Utc>) -> BurnRates {
+    BurnRates {
+        last_minute: rate(samples, now, Duration::minutes(1)),
+        last_5_minutes: rate(samples, now, Duration::minutes(5)),
+        last_hour: rate(samples, now, window_hour()),
+    }
+}
+
+fn rate(samples: &VecDeque<Sample>, now: DateTime<Utc>, window: Duration) -> BurnRate {
+    let since = now - window;
+    let (tokens, cost) = samples
+        .iter()
+        .rev()
+        .take_while(|s| s.at >= since)
+        .fold((0u64, 0.0), |(tokens, cost), s| (tokens + s.tokens, cost + s.cost));
+    let seconds = window.num_seconds() as f64;
+    BurnRate {
+        tokens_per_sec: tokens as f64 / seconds,
+        cost_per_sec: cost / seconds,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(at: &str, tokens: u64, cost: f64) -> Sample {
+        Sample { at: DateTime::parse_from_rfc3339(at).unwrap().with_timezone(&Utc), tokens, cost }
+    }
+
+    #[test]
+    fn rates_cover_each_window_and_drop_old_samples() {
+        let now = DateTime::parse_from_rfc3339("2026-03-10T12:00:00Z").unwrap().with_timezone(&Utc);
+        let mut samples = VecDeque::new();
+        // Out of order, as transcripts read from the start can be.
+        for s in [
+            sample("2026-03-10T11:59:30Z", 60, 0.6),
+            sample("2026-03-10T11:57:00Z", 300, 3.0),
+            sample("2026-03-10T11:20:00Z", 3600, 36.0),
+            sample("2026-03-10T10:30:00Z", 9999, 99.0),
+        ] {
+            insert(&mut samples, s, now);
+        }
+        assert_eq!(samples.len(), 3);
+        assert!(samples.iter().zip(samples.iter().skip(1)).all(|(a, b)| a.at <= b.at));
+
+        let rates = rates(&samples, now);
+        assert!((rates.last_minute.tokens_per_sec - 1.0).abs() < 1e-9);
+        assert!((rates.last_minute.cost_per_sec - 0.01).abs() < 1e-9);
+        assert!((rates.last_5_minutes.tokens_per_sec - 360.0 / 300.0).abs() < 1e-9);
+        assert!((rates.last_hour.tokens_per_sec - 3960.0 / 3600.0).abs() < 1e-9);
+        assert!((rates.last_hour.cost_per_sec - 39.6 / 3600.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn samples_older_than_an_hour_are_not_recorded() {
+        let tracker = BurnRateTracker::new();
+        let old = (Utc::now() - Duration::hours(2)).to_rfc3339();
+        tracker.record("a1", &old, 1000, 1.0);
+        assert_eq!(tracker.global_rates().last_hour.tokens_per_sec, 0.0);
+
+        let recent = (Utc::now() - Duration::seconds(10)).to_rfc3339();
+        tracker.record("a1", &recent, 3600, 3.6);
+        assert!((tracker.agent_rates("a1").last_hour.tokens_per_sec - 1.0).abs() < 1e-9);
+        assert_eq!(tracker.agent_rates("a2").last_hour.tokens_per_sec, 0.0);
+    }
+}
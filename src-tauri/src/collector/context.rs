@@ -0,0 +1,59 @@
+use serde::Serialize;
+
+const DEFAULT_CONTEXT_WINDOW: u64 = 200_000;
+const EXTENDED_CONTEXT_WINDOW: u64 = 1_000_000;
+
+/// Context window of `model`. Transcripts don't say whether the 1M-token beta
+/// was on, so a context larger than the default window implies it was.
+pub fn context_window(model: &str, largest_context: u64) -> u64 {
+    if model.contains("[1m]") || largest_context > DEFAULT_CONTEXT_WINDOW {
+        EXTENDED_CONTEXT_WINDOW
+    } else {
+        DEFAULT_CONTEXT_WINDOW
+    }
+}
+
+/// How full the context window was on the latest message.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ContextPressure {
+    pub context_tokens: u64,
+    pub context_limit: u64,
+    /// `context_tokens / context_limit`.
+    pub ratio: f64,
+    /// Times the conversation has been compacted so far.
+    pub compactions: u32,
+}
+
+impl ContextPressure {
+    pub fn new(model: &str, context_tokens: u64, largest_context: u64, compactions: u32) -> Self {
+        let context_limit = context_window(model, largest_context);
+        Self {
+            context_tokens,
+            context_limit,
+            ratio: context_tokens as f64 / context_limit as f64,
+            compactions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{context_window, ContextPressure};
+
+    #[test]
+    fn window_grows_for_the_1m_beta_or_larger_contexts() {
+        assert_eq!(context_window("claude-sonnet-4-5", 200_000), 200_000);
+        assert_eq!(context_window("claude-sonnet-4-5", 200_001), 1_000_000);
+        assert_eq!(context_window("claude-sonnet-4-5[1m]", 10), 1_000_000);
+    }
+
+    #[test]
+    fn pressure_is_the_latest_context_over_the_window() {
+        let pressure = ContextPressure::new("claude-opus-4-1", 50_000, 180_000, 2);
+        assert_eq!((pressure.context_limit, pressure.ratio, pressure.compactions), (200_000, 0.25, 2));
+
+        // Back under the default window after compacting, but the session went past it before.
+        let pressure = ContextPressure::new("claude-sonnet-4-5", 100_000, 400_000, 1);
+        assert_eq!((pressure.context_limit, pressure.ratio), (1_000_000, 0.1));
+    }
+}
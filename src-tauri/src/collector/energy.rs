@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+use super::parser::TokenUsage;
+
+/// Energy per token for models whose id contains `model_pattern`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnergyFactor {
+    pub model_pattern: String,
+    pub wh_per_token: f64,
+}
+
+/// Estimates of inference energy and the carbon intensity of the grid it
+/// runs on. Factors are matched in order, like pricing; models none of them
+/// match use `default_wh_per_token`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnergySettings {
+    pub factors: Vec<EnergyFactor>,
+    pub default_wh_per_token: f64,
+    pub grid_g_co2_per_kwh: f64,
+}
+
+impl Default for EnergySettings {
+    /// Luccioni et al. (2023) based estimates and the US average grid.
+    fn default() -> Self {
+        let factor = |pattern: &str, wh_per_token: f64| EnergyFactor {
+            model_pattern: pattern.to_string(),
+            wh_per_token,
+        };
+        Self {
+            factors: vec![
+                factor("opus", 0.000004),
+                factor("sonnet", 0.000001),
+                factor("haiku", 0.0000005),
+            ],
+            default_wh_per_token: 0.000001,
+            grid_g_co2_per_kwh: 385.0,
+        }
+    }
+}
+
+impl EnergySettings {
+    pub fn wh_per_token(&self, model: &str) -> f64 {
+        self.factors
+            .iter()
+            .find(|f| model.contains(&f.model_pattern))
+            .map(|f| f.wh_per_token)
+            .unwrap_or(self.default_wh_per_token)
+    }
+
+    /// Every token counts the same, cache reads included.
+    pub fn energy_wh(&self, usage: &TokenUsage, model: &str) -> f64 {
+        let tokens = usage.input_tokens
+            + usage.output_tokens
+            + usage.cache_read_input_tokens
+            + usage.cache_creation_input_tokens;
+        tokens as f64 * self.wh_per_token(model)
+    }
+
+    pub fn co2_grams(&self, energy_wh: f64) -> f64 {
+        energy_wh / 1000.0 * self.grid_g_co2_per_kwh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EnergySettings;
+    use crate::collector::parser::TokenUsage;
+
+    #[test]
+    fn every_token_is_priced_by_the_first_matching_factor() {
+        let settings = EnergySettings::default();
+        let usage = TokenUsage {
+            input_tokens: 1_000,
+            output_tokens: 500,
+            cache_read_input_tokens: 8_000,
+            cache_creation_input_tokens: 500,
+        };
+
+        assert_eq!(settings.energy_wh(&usage, "claude-opus-4-1"), 0.04);
+        assert_eq!(settings.energy_wh(&usage, "claude-sonnet-4-5"), 0.01);
+        assert_eq!(settings.energy_wh(&usage, "claude-3-5-haiku"), 0.005);
+        assert_eq!(settings.energy_wh(&usage, "unknown"), 0.01);
+        assert_eq!(settings.co2_grams(2.0), 0.77);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use super::ingest::{IngestedLine, SessionIngest};
+use super::parser::parse_jsonl_line;
+use super::watcher::get_claude_projects_dir;
+use crate::db::{Database, SessionRecord};
+
+/// Imports every top-level transcript under the Claude projects directory
+/// that isn't stored yet, then reindexes sessions stored before search and
+/// tool calls existed. Returns how many sessions were added.
+pub fn import_history(db: &Database) -> Result<u32, String> {
+    let projects_dir = get_claude_projects_dir()
+        .ok_or_else(|| "No ~/.claude/projects directory found".to_string())?;
+    let imported = import_from(db, &projects_dir)?;
+    reindex_from(db, &projects_dir)?;
+    Ok(imported)
+}
+
+/// Reads the transcripts of sessions queued by the `pending reindex`
+/// migration again, filling in their search entries, tool calls and message
+/// keys without counting their usage twice. Returns how many were reindexed.
+pub fn reindex_pending(db: &Database) -> Result<u32, String> {
+    match get_claude_projects_dir() {
+        Some(projects_dir) => reindex_from(db, &projects_dir),
+        None => Ok(0),
+    }
+}
+
+fn import_from(db: &Database, projects_dir: &Path) -> Result<u32, String> {
+    let privacy = db.get_search_privacy()?;
+    let energy = db.get_energy_settings()?;
+    let mut imported = 0u32;
+
+    for (session_id, (project_name, path)) in transcripts(projects_dir)? {
+        if db.session_exists(&session_id).unwrap_or(true) {
+            continue;
+        }
+
+        // The session row goes in first: messages reference it and
+        // pick up its project and agent for the hourly rollups.
+        let ingest = SessionIngest::new(&session_id, "imported", &project_name, "completed", energy.clone());
+        if let Ok((record, lines)) = parse_session_file(&path, ingest) {
+            if let Ok(()) = db.upsert_session(&record) {
+                for line in &lines {
+                    let _ = line.persist(db, &privacy);
+                }
+                imported += 1;
+            }
+        }
+    }
+
+    Ok(imported)
+}
+
+fn reindex_from(db: &Database, projects_dir: &Path) -> Result<u32, String> {
+    let pending = db.get_pending_reindex()?;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let privacy = db.get_search_privacy()?;
+    let energy = db.get_energy_settings()?;
+    let cutoff = db.get_retention_policy()?.cutoff();
+    let files = transcripts(projects_dir)?;
+    let mut reindexed = 0u32;
+
+    for session in pending {
+        // A transcript that is gone can't be read again; stop asking.
+        if let Some((_, path)) = files.get(&session.id) {
+            let ingest = SessionIngest::new(
+                &session.id, &session.agent_id, &session.project, &session.status, energy.clone(),
+            );
+            // Unreadable for now; try again next time.
+            let Ok((_, lines)) = parse_session_file(path, ingest) else { continue };
+            for line in &lines {
+                line.reindex(db, &privacy, cutoff.as_deref())?;
+            }
+            reindexed += 1;
+        }
+        db.finish_reindex(&session.id)?;
+    }
+
+    Ok(reindexed)
+}
+
+/// Top-level transcripts by session id, with the project they belong to.
+/// Subagent transcripts (`agent-*.jsonl`) are left out.
+fn transcripts(projects_dir: &Path) -> Result<HashMap<String, (String, PathBuf)>, String> {
+    let mut files = HashMap::new();
+
+    for project_entry in std::fs::read_dir(projects_dir).map_err(|e| e.to_string())? {
+        let project_entry = project_entry.map_err(|e| e.to_string())?;
+        if !project_entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+            continue;
+        }
+
+        let project_name = project_entry
+            .file_name()
+            .to_string_lossy()
+            .replace('-', "/");
+
+        for file_entry in std::fs::read_dir(project_entry.path()).map_err(|e| e.to_string())? {
+            let file_entry = file_entry.map_err(|e| e.to_string())?;
+            let path = file_entry.path();
+
+            if path.extension().map(|e| e == "jsonl").unwrap_or(false) {
+                let session_id = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("")
+                    .to_string();
+
+                if session_id.is_empty() || session_id.starts_with("agent-") {
+                    continue;
+                }
+                files.insert(session_id, (project_name.clone(), path));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+fn parse_session_file(
+    path: &Path,
+    mut ingest: SessionIngest,
+) -> Result<(SessionRecord, Vec<IngestedLine>), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let reader = BufReader::new(file);
+    let mut lines = Vec::new();
+
+    for line in reader.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        if let Some(parsed) = parse_jsonl_line(line.trim()) {
+            lines.push(ingest.ingest(&parsed));
+        }
+    }
+
+    Ok((ingest.record().clone(), lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::{message, session, temp_path};
+
+    const TRANSCRIPT: &str = r#"{"type":"user","uuid":"u1","timestamp":"2026-02-02T10:00:00.000Z","message":{"role":"user","content":"fix the flaky parser test"}}
+{"type":"assistant","uuid":"a1","timestamp":"2026-02-02T10:00:05.000Z","message":{"id":"msg_1","model":"claude-sonnet-4-5","usage":{"input_tokens":10,"output_tokens":20},"content":[{"type":"text","text":"Running the suite"},{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"cargo test"}}]}}
+{"type":"user","uuid":"u2","timestamp":"2026-02-02T10:00:09.000Z","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","content":"ok"}]}}
+"#;
+
+    fn rollup_messages(path: &Path) -> i64 {
+        rusqlite::Connection::open(path)
+            .unwrap()
+            .query_row("SELECT SUM(messages) FROM usage_hourly", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn reindexes_sessions_stored_before_search_and_tool_calls() {
+        let path = temp_path("reindex");
+        let projects = path.with_file_name("projects");
+        std::fs::create_dir_all(projects.join("-work-app")).unwrap();
+        std::fs::write(projects.join("-work-app").join("s1.jsonl"), TRANSCRIPT).unwrap();
+
+        // A session from before message keys, search and tool calls.
+        let db = Database::open(&path).unwrap();
+        db.upsert_session(&session("s1", "2026-02-02T10:00:00.000Z")).unwrap();
+        db.insert_message(&message("s1", None, "2026-02-02T10:00:05.000Z", 0.5)).unwrap();
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute("INSERT INTO pending_reindex (session_id) VALUES ('s1')", [])
+            .unwrap();
+
+        assert_eq!(reindex_from(&db, &projects).unwrap(), 1);
+
+        let messages = db.get_session_messages("s1").unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_key.as_deref(), Some("a1"));
+        assert_eq!(rollup_messages(&path), 1);
+        assert_eq!(db.search_transcripts("flaky", None).unwrap()[0].message_key, "u1");
+        let calls = db.get_session_tool_calls("s1").unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].finished_at.as_deref(), Some("2026-02-02T10:00:09.000Z"));
+
+        // Done once: nothing is left pending and a new import adds nothing.
+        assert!(db.get_pending_reindex().unwrap().is_empty());
+        assert_eq!(reindex_from(&db, &projects).unwrap(), 0);
+        assert_eq!(import_from(&db, &projects).unwrap(), 0);
+        assert_eq!(rollup_messages(&path), 1);
+    }
+
+    #[test]
+    fn reindex_leaves_pruned_detail_alone() {
+        let path = temp_path("reindex-pruned");
+        let projects = path.with_file_name("projects");
+        std::fs::create_dir_all(projects.join("-work-app")).unwrap();
+        std::fs::write(projects.join("-work-app").join("s1.jsonl"), TRANSCRIPT).unwrap();
+
+        let db = Database::open(&path).unwrap();
+        db.upsert_session(&session("s1", "2026-02-02T10:00:00.000Z")).unwrap();
+        // Its transcript has been deleted since.
+        db.upsert_session(&session("gone", "2026-02-01T10:00:00.000Z")).unwrap();
+        db.set_retention_policy(&crate::db::retention::RetentionPolicy {
+            retention_days: 1,
+            preview_retention_days: None,
+        }).unwrap();
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute("INSERT INTO pending_reindex (session_id) VALUES ('s1'), ('gone')", [])
+            .unwrap();
+
+        assert_eq!(reindex_from(&db, &projects).unwrap(), 1);
+        assert!(db.get_session_messages("s1").unwrap().is_empty());
+        assert!(db.search_transcripts("flaky", None).unwrap().is_empty());
+        assert!(db.get_session_tool_calls("s1").unwrap().is_empty());
+        assert!(db.get_pending_reindex().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use crate::db::context::CompactionEvent;
+use crate::db::search::{SearchDocument, SearchPrivacy};
+use crate::db::tools::{mcp_server, ToolCallRecord, ToolCallResult};
+use crate::db::{Database, MessageRecord, SessionRecord};
+
+use super::cost::calculate_cost;
+use super::energy::EnergySettings;
+use super::parser::ParsedMessage;
+
+const PREVIEW_CHARS: usize = 200;
+
+/// Folds the lines of one transcript file into a running session row and the
+/// message, search and tool call rows to persist for each line. Shared by
+/// history import and the live watcher so both store sessions the same way.
+pub struct SessionIngest {
+    record: SessionRecord,
+    block_indices: HashMap<String, u32>,
+    energy: EnergySettings,
+}
+
+/// What a single transcript line produced.
+pub struct IngestedLine {
+    /// Set for lines that carry token usage from a known model.
+    pub message: Option<MessageRecord>,
+    pub document: Option<SearchDocument>,
+    pub tool_calls: Vec<ToolCallRecord>,
+    pub tool_results: Vec<ToolCallResult>,
+    pub compaction: Option<CompactionEvent>,
+}
+
+impl IngestedLine {
+    /// Stores the line. The session row must already be written.
+    pub fn persist(&self, db: &Database, privacy: &SearchPrivacy) -> Result<(), String> {
+        if let Some(ref message) = self.message {
+            db.insert_message(message)?;
+        }
+        self.persist_detail(db, privacy)
+    }
+
+    /// Stores the line for a session that is already counted: keyless
+    /// message rows get their key, and the search, tool call and compaction
+    /// rows are filled in. Nothing is added to the session's usage, and lines
+    /// older than `cutoff` are left pruned.
+    pub fn reindex(&self, db: &Database, privacy: &SearchPrivacy, cutoff: Option<&str>) -> Result<(), String> {
+        if let Some(ref message) = self.message {
+            db.claim_message_key(message)?;
+        }
+        if self.is_older_than(cutoff) {
+            return Ok(());
+        }
+        self.persist_detail(db, privacy)
+    }
+
+    /// Whether retention has already pruned what this line would store.
+    pub fn is_older_than(&self, cutoff: Option<&str>) -> bool {
+        let timestamp = self
+            .message
+            .as_ref()
+            .map(|m| m.timestamp.as_str())
+            .or_else(|| self.document.as_ref().map(|d| d.timestamp.as_str()))
+            .or_else(|| self.tool_calls.first().map(|t| t.started_at.as_str()));
+        matches!((cutoff, timestamp), (Some(cutoff), Some(timestamp)) if timestamp < cutoff)
+    }
+
+    fn persist_detail(&self, db: &Database, privacy: &SearchPrivacy) -> Result<(), String> {
+        if let Some(ref document) = self.document {
+            db.index_document(document, privacy)?;
+        }
+        for call in &self.tool_calls {
+            db.insert_tool_call(call)?;
+        }
+        for result in &self.tool_results {
+            db.complete_tool_call(result)?;
+        }
+        if let Some(ref compaction) = self.compaction {
+            db.insert_compaction(compaction)?;
+        }
+        Ok(())
+    }
+}
+
+impl SessionIngest {
+    pub fn new(
+        session_id: &str,
+        agent_id: &str,
+        project: &str,
+        status: &str,
+        energy: EnergySettings,
+    ) -> Self {
+        Self {
+            record: SessionRecord {
+                id: session_id.to_string(),
+                agent_id: agent_id.to_string(),
+                project: project.to_string(),
+                model: "unknown".to_string(),
+                total_cost: 0.0,
+                total_tokens: 0,
+                input_tokens: 0,
+                output_tokens: 0,
+                cache_read_tokens: 0,
+                cache_creation_tokens: 0,
+                duration_ms: 0,
+                status: status.to_string(),
+                tool_calls: 0,
+                started_at: String::new(),
+                ended_at: None,
+                cwd: None,
+                git_branch: None,
+                energy_wh: 0.0,
+                permission_mode: None,
+            },
+            block_indices: HashMap::new(),
+            energy,
+        }
+    }
+
+    pub fn record(&self) -> &SessionRecord {
+        &self.record
+    }
+
+    pub fn set_permission_mode(&mut self, mode: Option<String>) {
+        self.record.permission_mode = mode;
+    }
+
+    pub fn ingest(&mut self, parsed: &ParsedMessage) -> IngestedLine {
+        let r = &mut self.record;
+
+        if !parsed.timestamp.is_empty() {
+            if r.started_at.is_empty() {
+                r.started_at = parsed.timestamp.clone();
+            }
+            r.ended_at = Some(parsed.timestamp.clone());
+            r.duration_ms = duration_ms(&r.started_at, &parsed.timestamp);
+        }
+
+        if let Some(ref model) = parsed.model {
+            r.model = model.clone();
+        }
+        if parsed.cwd.is_some() {
+            r.cwd = parsed.cwd.clone();
+        }
+        if parsed.git_branch.is_some() {
+            r.git_branch = parsed.git_branch.clone();
+        }
+
+        // Claude Code writes one line per content block, each with its own
+        // uuid; older transcripts without one fall back to message id + block index.
+        let message_key = parsed.uuid.clone().or_else(|| {
+            parsed.message_id.as_ref().map(|id| {
+                let index = self.block_indices.entry(id.clone()).or_insert(0);
+                *index += 1;
+                format!("{}:{}", id, *index - 1)
+            })
+        });
+
+        let mut message = None;
+        if let Some(ref usage) = parsed.usage {
+            r.input_tokens += usage.input_tokens;
+            r.output_tokens += usage.output_tokens;
+            r.cache_read_tokens += usage.cache_read_input_tokens;
+            r.cache_creation_tokens += usage.cache_creation_input_tokens;
+            r.total_tokens += usage.input_tokens + usage.output_tokens
+                + usage.cache_read_input_tokens + usage.cache_creation_input_tokens;
+
+            if r.model != "unknown" {
+                let msg_cost = calculate_cost(usage, &r.model);
+                let msg_energy = self.energy.energy_wh(usage, &r.model);
+                r.total_cost += msg_cost;
+                r.energy_wh += msg_energy;
+
+                message = Some(MessageRecord {
+                    id: 0,
+                    session_id: r.id.clone(),
+                    message_key: message_key.clone(),
+                    message_type: parsed.message_type.clone(),
+                    timestamp: parsed.timestamp.clone(),
+                    model: Some(r.model.clone()),
+                    input_tokens: usage.input_tokens,
+                    output_tokens: usage.output_tokens,
+                    cache_read_tokens: usage.cache_read_input_tokens,
+                    cache_creation_tokens: usage.cache_creation_input_tokens,
+                    cost: msg_cost,
+                    content_preview: parsed.content_text.as_ref().map(|t| t.chars().take(PREVIEW_CHARS).collect()),
+                    tool_name: parsed.tool_calls.first().cloned(),
+                    context_tokens: usage.input_tokens
+                        + usage.cache_read_input_tokens
+                        + usage.cache_creation_input_tokens,
+                    energy_wh: msg_energy,
+                });
+            }
+        }
+
+        r.tool_calls += parsed.tool_calls.len() as u32;
+
+        let cost_share = message
+            .as_ref()
+            .map(|m| m.cost / parsed.tool_uses.len().max(1) as f64)
+            .unwrap_or(0.0);
+        let tool_calls = parsed
+            .tool_uses
+            .iter()
+            .filter(|t| !t.id.is_empty())
+            .map(|t| ToolCallRecord {
+                tool_use_id: t.id.clone(),
+                session_id: r.id.clone(),
+                message_key: message_key.clone(),
+                name: t.name.clone(),
+                server: mcp_server(&t.name),
+                input_bytes: t.input.to_string().len() as u64,
+                started_at: parsed.timestamp.clone(),
+                cost: cost_share,
+            })
+            .collect();
+        let tool_results = parsed
+            .tool_results
+            .iter()
+            .filter(|t| !t.tool_use_id.is_empty())
+            .map(|t| ToolCallResult {
+                tool_use_id: t.tool_use_id.clone(),
+                finished_at: parsed.timestamp.clone(),
+                is_error: t.is_error,
+            })
+            .collect();
+
+        let compaction = parsed.compaction.as_ref().and_then(|c| {
+            let event_key = match c.kind.as_str() {
+                "summary" => format!("summary:{}:{}", r.id, c.leaf_uuid.as_deref()?),
+                _ => parsed.uuid.clone()?,
+            };
+            Some(CompactionEvent {
+                session_id: r.id.clone(),
+                event_key,
+                kind: c.kind.clone(),
+                // Summary lines carry no timestamp of their own.
+                timestamp: if parsed.timestamp.is_empty() {
+                    r.ended_at.clone().unwrap_or_default()
+                } else {
+                    parsed.timestamp.clone()
+                },
+                trigger: c.trigger.clone(),
+                pre_tokens: c.pre_tokens,
+                summary: c.summary.clone(),
+            })
+        });
+
+        let document = message_key.map(|key| SearchDocument {
+            session_id: r.id.clone(),
+            message_key: key,
+            message_type: parsed.message_type.clone(),
+            timestamp: parsed.timestamp.clone(),
+            text: parsed.content_text.clone().unwrap_or_default(),
+            tool_input: parsed
+                .tool_uses
+                .iter()
+                .map(|t| format!("{} {}", t.name, t.input))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            tool_result: parsed
+                .tool_results
+                .iter()
+                .map(|t| t.content.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        });
+
+        IngestedLine { message, document, tool_calls, tool_results, compaction }
+    }
+}
+
+fn duration_ms(first_ts: &str, last_ts: &str) -> u64 {
+    let start = chrono::DateTime::parse_from_rfc3339(first_ts).ok();
+    let end = chrono::DateTime::parse_from_rfc3339(last_ts).ok();
+    match (start, end) {
+        (Some(s), Some(e)) => (e - s).num_milliseconds().max(0) as u64,
+        _ => 0,
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use super::burn_rate::{BurnRate, BurnRateTracker};
+use super::parser::TokenUsage;
+use crate::pty::manager::PtyInfo;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct UsageKey {
+    model: String,
+    project: String,
+    agent: String,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct UsageTotals {
+    input_tokens: u64,
+    output_tokens: u64,
+    cache_read_tokens: u64,
+    cache_creation_tokens: u64,
+    cost: f64,
+    messages: u64,
+}
+
+/// Counters for what the watcher has read since the app started, rendered in
+/// the Prometheus text format. A transcript first picked up mid-session is
+/// counted from its start.
+pub struct Metrics {
+    usage: Mutex<BTreeMap<UsageKey, UsageTotals>>,
+    /// By tool name, then agent.
+    tool_calls: Mutex<BTreeMap<(String, String), u64>>,
+    loop_alerts: Mutex<BTreeMap<String, u64>>,
+    burn_rates: Arc<BurnRateTracker>,
+}
+
+impl Metrics {
+    pub fn new(burn_rates: Arc<BurnRateTracker>) -> Self {
+        Self {
+            usage: Mutex::new(BTreeMap::new()),
+            tool_calls: Mutex::new(BTreeMap::new()),
+            loop_alerts: Mutex::new(BTreeMap::new()),
+            burn_rates,
+        }
+    }
+
+    pub fn record_message(&self, agent: &str, project: &str, model: &str, usage: Option<&TokenUsage>, cost: f64) {
+        let key = UsageKey {
+            model: model.to_string(),
+            project: project.to_string(),
+            agent: agent.to_string(),
+        };
+        if let Ok(mut totals) = self.usage.lock() {
+            let totals = totals.entry(key).or_default();
+            totals.messages += 1;
+            totals.cost += cost;
+            if let Some(usage) = usage {
+                totals.input_tokens += usage.input_tokens;
+                totals.output_tokens += usage.output_tokens;
+                totals.cache_read_tokens += usage.cache_read_input_tokens;
+                totals.cache_creation_tokens += usage.cache_creation_input_tokens;
+            }
+        }
+    }
+
+    pub fn record_tool_call(&self, agent: &str, tool: &str) {
+        if let Ok(mut calls) = self.tool_calls.lock() {
+            *calls.entry((tool.to_string(), agent.to_string())).or_default() += 1;
+        }
+    }
+
+    pub fn record_loop_alert(&self, agent: &str) {
+        if let Ok(mut alerts) = self.loop_alerts.lock() {
+            *alerts.entry(agent.to_string()).or_default() += 1;
+        }
+    }
+
+    /// The counters, the current burn rate and how many of `agents` are
+    /// running or waiting for input.
+    pub fn render(&self, agents: &[PtyInfo]) -> String {
+        let mut out = String::new();
+        let usage = self.usage.lock().map(|u| u.clone()).unwrap_or_default();
+
+        header(&mut out, "sonar_tokens_total", "counter", "Tokens read from transcripts, by token type.");
+        for (key, totals) in &usage {
+            for (kind, value) in [
+                ("input", totals.input_tokens),
+                ("output", totals.output_tokens),
+                ("cache_read", totals.cache_read_tokens),
+                ("cache_creation", totals.cache_creation_tokens),
+            ] {
+                sample(&mut out, "sonar_tokens_total", &[("type", kind), ("model", &key.model), ("project", &key.project), ("agent", &key.agent)], value as f64);
+            }
+        }
+
+        header(&mut out, "sonar_cost_usd_total", "counter", "Estimated spend in US dollars.");
+        for (key, totals) in &usage {
+            sample(&mut out, "sonar_cost_usd_total", &usage_labels(key), totals.cost);
+        }
+
+        header(&mut out, "sonar_messages_total", "counter", "Transcript messages, user and assistant.");
+        for (key, totals) in &usage {
+            sample(&mut out, "sonar_messages_total", &usage_labels(key), totals.messages as f64);
+        }
+
+        header(&mut out, "sonar_tool_calls_total", "counter", "Tool calls made by agents.");
+        if let Ok(calls) = self.tool_calls.lock() {
+            for ((tool, agent), count) in calls.iter() {
+                sample(&mut out, "sonar_tool_calls_total", &[("tool", tool), ("agent", agent)], *count as f64);
+            }
+        }
+
+        header(&mut out, "sonar_loop_alerts_total", "counter", "Times an agent was seen repeating itself.");
+        if let Ok(alerts) = self.loop_alerts.lock() {
+            for (agent, count) in alerts.iter() {
+                sample(&mut out, "sonar_loop_alerts_total", &[("agent", agent)], *count as f64);
+            }
+        }
+
+        header(&mut out, "sonar_agents", "gauge", "Open terminals that are producing output, or silent and waiting for input.");
+        for kind in ["shell", "agent"] {
+            let running = agents.iter().filter(|a| a.kind == kind && a.running && !a.waiting).count();
+            let waiting = agents.iter().filter(|a| a.kind == kind && a.running && a.waiting).count();
+            sample(&mut out, "sonar_agents", &[("kind", kind), ("state", "running")], running as f64);
+            sample(&mut out, "sonar_agents", &[("kind", kind), ("state", "waiting")], waiting as f64);
+        }
+
+        let rates = self.burn_rates.global_rates();
+        let windows: [(&str, BurnRate); 3] = [
+            ("1m", rates.last_minute),
+            ("5m", rates.last_5_minutes),
+            ("1h", rates.last_hour),
+        ];
+        header(&mut out, "sonar_burn_rate_tokens_per_second", "gauge", "Tokens per second across all agents over a trailing window.");
+        for (window, rate) in windows {
+            sample(&mut out, "sonar_burn_rate_tokens_per_second", &[("window", window)], rate.tokens_per_sec);
+        }
+        header(&mut out, "sonar_burn_rate_usd_per_second", "gauge", "Spend per second across all agents over a trailing window.");
+        for (window, rate) in windows {
+            sample(&mut out, "sonar_burn_rate_usd_per_second", &[("window", window)], rate.cost_per_sec);
+        }
+
+        out
+    }
+}
+
+fn usage_labels(key: &UsageKey) -> [(&str, &str); 3] {
+    [("model", &key.model), ("project", &key.project), ("agent", &key.agent)]
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+        .collect();
+    let _ = writeln!(out, "{}{{{}}} {}", name, labels.join(","), value);
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -1,3 +1,12 @@
+pub mod burn_rate;
+pub mod context;
 pub mod cost;
+pub mod energy;
+pub mod import;
+pub mod ingest;
+pub mod metrics;
+pub mod otlp;
 pub mod parser;
+pub mod redact;
+pub mod transcript;
 pub mod watcher;
@@ -0,0 +1,621 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::db::query::SessionQuery;
+use crate::db::{calendar, Database, SessionRecord};
+
+const OTLP_SETTINGS_KEY: &str = "otlp_export";
+/// Last `ended_at` covered by the background export.
+const EXPORTED_UNTIL_KEY: &str = "otlp_exported_until";
+/// Sessions quiet for this long are taken to be finished and exported.
+const SETTLE_MINUTES: i64 = 10;
+const SESSIONS_PER_REQUEST: usize = 20;
+const REQUEST_TIMEOUT_SECS: u64 = 30;
+
+const STATUS_ERROR: u8 = 2;
+const SPAN_KIND_INTERNAL: u8 = 1;
+const SPAN_KIND_CLIENT: u8 = 3;
+const TEMPORALITY_DELTA: u8 = 1;
+
+/// Where to send traces and metrics over OTLP/HTTP with JSON encoding.
+/// `endpoint` is the collector's base URL; `/v1/traces` and `/v1/metrics`
+/// are appended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OtlpSettings {
+    pub enabled: bool,
+    pub endpoint: String,
+    /// Sent with every request, e.g. an `authorization` header.
+    pub headers: BTreeMap<String, String>,
+    pub service_name: String,
+}
+
+impl Default for OtlpSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4318".to_string(),
+            headers: BTreeMap::new(),
+            service_name: "open-sonar".to_string(),
+        }
+    }
+}
+
+impl OtlpSettings {
+    pub fn load(db: &Database) -> Result<Self, String> {
+        Ok(db
+            .get_setting(OTLP_SETTINGS_KEY)?
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn save(&self, db: &Database) -> Result<(), String> {
+        if !self.endpoint.starts_with("http://") && !self.endpoint.starts_with("https://") {
+            return Err(format!("Invalid OTLP endpoint: {}", self.endpoint));
+        }
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        db.set_setting(OTLP_SETTINGS_KEY, &json)?;
+        if !self.enabled {
+            // Turning it back on later starts from then, not from now.
+            db.set_setting(EXPORTED_UNTIL_KEY, "")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OtlpReport {
+    pub sessions: u32,
+    pub spans: u32,
+    pub metric_points: u32,
+}
+
+/// Exports the sessions that went quiet since the last run. The first run
+/// only starts the clock, so turning the export on doesn't replay history;
+/// `export_query` is there for backfills.
+pub fn export_settled(db: &Database, settings: &OtlpSettings) -> Result<OtlpReport, String> {
+    export_settled_at(db, settings, Utc::now())
+}
+
+fn export_settled_at(db: &Database, settings: &OtlpSettings, now: DateTime<Utc>) -> Result<OtlpReport, String> {
+    let until = calendar::format_utc(now - Duration::minutes(SETTLE_MINUTES));
+    let Some(since) = db.get_setting(EXPORTED_UNTIL_KEY)?.filter(|s| !s.is_empty()) else {
+        db.set_setting(EXPORTED_UNTIL_KEY, &until)?;
+        return Ok(OtlpReport::default());
+    };
+    if since >= until {
+        return Ok(OtlpReport::default());
+    }
+
+    let sessions = db.get_sessions_ended_between(&since, &until)?;
+    let report = export_sessions(db, settings, &sessions, true)?;
+    db.set_setting(EXPORTED_UNTIL_KEY, &until)?;
+    Ok(report)
+}
+
+/// Sends every session matching `query` in full, for backfills.
+pub fn export_query(db: &Database, settings: &OtlpSettings, query: &SessionQuery) -> Result<OtlpReport, String> {
+    let mut sessions = Vec::new();
+    let mut page_query = SessionQuery { limit: Some(1000), cursor: None, ..query.clone() };
+    loop {
+        let page = db.query_sessions(&page_query)?;
+        sessions.extend(page.sessions);
+        match page.next_cursor {
+            Some(cursor) => page_query.cursor = Some(cursor),
+            None => break,
+        }
+    }
+    export_sessions(db, settings, &sessions, false)
+}
+
+/// Sends a trace per session, with a span per assistant turn and per tool
+/// call, then token and cost sums over the same messages. With `resume`, a
+/// session sent before only adds the turns and tool calls since, extending
+/// the trace it already has, and how far each session got is recorded.
+pub fn export_sessions(
+    db: &Database,
+    settings: &OtlpSettings,
+    sessions: &[SessionRecord],
+    resume: bool,
+) -> Result<OtlpReport, String> {
+    let mut report = OtlpReport::default();
+    let mut usage: BTreeMap<(String, String), Usage> = BTreeMap::new();
+    let mut window_start: Option<u128> = None;
+    let mut sent = Vec::new();
+
+    for batch in sessions.chunks(SESSIONS_PER_REQUEST) {
+        let mut spans = Vec::new();
+        for session in batch {
+            let since = if resume { db.get_otlp_exported_until(&session.id)? } else { None };
+            let trace = session_trace(db, session, since.as_deref(), &mut usage)?;
+            if !trace.is_empty() {
+                if let Some(start) = unix_nanos(since.as_deref().unwrap_or(&session.started_at)) {
+                    window_start = Some(window_start.map_or(start, |w| w.min(start)));
+                }
+            }
+            spans.extend(trace);
+            sent.push(session);
+        }
+        report.sessions += batch.len() as u32;
+        report.spans += spans.len() as u32;
+        if !spans.is_empty() {
+            post(settings, "v1/traces", &traces_body(settings, spans))?;
+        }
+    }
+
+    if !usage.is_empty() {
+        let (body, points) = metrics_body(settings, &usage, window_start.unwrap_or_default(), now_nanos());
+        post(settings, "v1/metrics", &body)?;
+        report.metric_points = points;
+    }
+
+    // Only once everything went through, so a failed run is sent again whole.
+    if resume {
+        for session in sent {
+            let until = session.ended_at.as_deref().unwrap_or(&session.started_at);
+            db.set_otlp_exported_until(&session.id, until)?;
+        }
+    }
+    Ok(report)
+}
+
+#[derive(Default)]
+struct Usage {
+    input_tokens: u64,
+    output_tokens: u64,
+    cache_read_tokens: u64,
+    cache_creation_tokens: u64,
+    cost: f64,
+}
+
+/// The session's root span followed by its turn and tool call spans. Turns
+/// run from the message before them to the reply, which is the time the
+/// model took to answer.
+fn session_trace(
+    db: &Database,
+    session: &SessionRecord,
+    since: Option<&str>,
+    usage: &mut BTreeMap<(String, String), Usage>,
+) -> Result<Vec<Value>, String> {
+    let trace_id = trace_id(&session.id);
+    let root_id = span_id(&session.id, "session");
+    let messages = db.get_session_messages(&session.id)?;
+    let tool_calls = db.get_session_tool_calls(&session.id)?;
+    let is_new = |timestamp: &str| match since {
+        Some(since) => timestamp > since,
+        None => true,
+    };
+
+    let end = session.ended_at.clone().unwrap_or_else(|| session.started_at.clone());
+    let mut spans = vec![span(
+        &trace_id,
+        &root_id,
+        None,
+        &format!("session {}", session.project),
+        SPAN_KIND_INTERNAL,
+        (&session.started_at, &end),
+        vec![
+            string("session.id", &session.id),
+            string("sonar.agent_id", &session.agent_id),
+            string("sonar.project", &session.project),
+            string("vcs.ref.head.name", session.git_branch.as_deref().unwrap_or_default()),
+            string("sonar.permission_mode", session.permission_mode.as_deref().unwrap_or_default()),
+            string("gen_ai.request.model", &session.model),
+            int("gen_ai.usage.input_tokens", session.input_tokens),
+            int("gen_ai.usage.output_tokens", session.output_tokens),
+            int("sonar.usage.cache_read_tokens", session.cache_read_tokens),
+            int("sonar.usage.cache_creation_tokens", session.cache_creation_tokens),
+            double("sonar.cost_usd", session.total_cost),
+            int("sonar.tool_calls", session.tool_calls as u64),
+        ],
+    )];
+
+    let mut turn_ids: HashMap<&str, String> = HashMap::new();
+    let mut previous = session.started_at.as_str();
+    for message in &messages {
+        let started = previous;
+        previous = &message.timestamp;
+        if message.message_type != "assistant" {
+            continue;
+        }
+        let key = message.message_key.clone().unwrap_or_else(|| message.id.to_string());
+        let id = span_id(&session.id, &key);
+        if let Some(ref message_key) = message.message_key {
+            turn_ids.insert(message_key, id.clone());
+        }
+        if !is_new(&message.timestamp) {
+            continue;
+        }
+
+        let model = message.model.clone().unwrap_or_else(|| "unknown".to_string());
+        let totals = usage.entry((model.clone(), session.project.clone())).or_default();
+        totals.input_tokens += message.input_tokens;
+        totals.output_tokens += message.output_tokens;
+        totals.cache_read_tokens += message.cache_read_tokens;
+        totals.cache_creation_tokens += message.cache_creation_tokens;
+        totals.cost += message.cost;
+
+        spans.push(span(
+            &trace_id,
+            &id,
+            Some(&root_id),
+            &format!("chat {}", model),
+            SPAN_KIND_CLIENT,
+            (started, &message.timestamp),
+            vec![
+                string("gen_ai.system", "anthropic"),
+                string("gen_ai.operation.name", "chat"),
+                string("gen_ai.request.model", &model),
+                int("gen_ai.usage.input_tokens", message.input_tokens),
+                int("gen_ai.usage.output_tokens", message.output_tokens),
+                int("sonar.usage.cache_read_tokens", message.cache_read_tokens),
+                int("sonar.usage.cache_creation_tokens", message.cache_creation_tokens),
+                double("sonar.cost_usd", message.cost),
+            ],
+        ));
+    }
+
+    for call in tool_calls.iter().filter(|c| is_new(&c.started_at)) {
+        let parent = call
+            .message_key
+            .as_deref()
+            .and_then(|key| turn_ids.get(key))
+            .unwrap_or(&root_id);
+        let error = call.is_error.unwrap_or(false);
+        let mut attributes = vec![
+            string("gen_ai.operation.name", "execute_tool"),
+            string("gen_ai.tool.name", &call.name),
+            string("gen_ai.tool.call.id", &call.tool_use_id),
+            double("sonar.cost_usd", call.cost),
+            boolean("error", error),
+        ];
+        if let Some(ref server) = call.server {
+            attributes.push(string("sonar.mcp_server", server));
+        }
+        let mut tool_span = span(
+            &trace_id,
+            &span_id(&session.id, &call.tool_use_id),
+            Some(parent),
+            &format!("execute_tool {}", call.name),
+            SPAN_KIND_INTERNAL,
+            (&call.started_at, call.finished_at.as_deref().unwrap_or(&call.started_at)),
+            attributes,
+        );
+        if error {
+            tool_span["status"] = json!({ "code": STATUS_ERROR, "message": "tool returned an error" });
+        }
+        spans.push(tool_span);
+    }
+
+    // A session sent before with nothing new since isn't sent again.
+    if since.is_some() && spans.len() == 1 && !is_new(&session.started_at) {
+        spans.clear();
+    }
+    Ok(spans)
+}
+
+fn span(
+    trace_id: &str,
+    span_id: &str,
+    parent: Option<&str>,
+    name: &str,
+    kind: u8,
+    (start, end): (&str, &str),
+    attributes: Vec<Value>,
+) -> Value {
+    let start = unix_nanos(start).unwrap_or_default();
+    let end = unix_nanos(end).unwrap_or(start).max(start);
+    let mut span = json!({
+        "traceId": trace_id,
+        "spanId": span_id,
+        "name": name,
+        "kind": kind,
+        "startTimeUnixNano": start.to_string(),
+        "endTimeUnixNano": end.to_string(),
+        "attributes": attributes,
+    });
+    if let Some(parent) = parent {
+        span["parentSpanId"] = json!(parent);
+    }
+    span
+}
+
+fn traces_body(settings: &OtlpSettings, spans: Vec<Value>) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": resource(settings),
+            "scopeSpans": [{ "scope": scope(), "spans": spans }],
+        }]
+    })
+}
+
+/// Delta sums of tokens by type and of cost, per model and project, over
+/// `[start, end]`.
+fn metrics_body(
+    settings: &OtlpSettings,
+    usage: &BTreeMap<(String, String), Usage>,
+    start: u128,
+    end: u128,
+) -> (Value, u32) {
+    let point = |attributes: Vec<Value>, value: Value| {
+        let mut point = json!({
+            "attributes": attributes,
+            "startTimeUnixNano": start.to_string(),
+            "timeUnixNano": end.to_string(),
+        });
+        match value {
+            Value::String(_) => point["asInt"] = value,
+            _ => point["asDouble"] = value,
+        }
+        point
+    };
+
+    let mut tokens = Vec::new();
+    let mut cost = Vec::new();
+    for ((model, project), u) in usage {
+        for (kind, value) in [
+            ("input", u.input_tokens),
+            ("output", u.output_tokens),
+            ("cache_read", u.cache_read_tokens),
+            ("cache_creation", u.cache_creation_tokens),
+        ] {
+            let attributes = vec![
+                string("gen_ai.token.type", kind),
+                string("gen_ai.request.model", model),
+                string("sonar.project", project),
+            ];
+            tokens.push(point(attributes, json!(value.to_string())));
+        }
+        let attributes = vec![string("gen_ai.request.model", model), string("sonar.project", project)];
+        cost.push(point(attributes, json!(u.cost)));
+    }
+    let points = (tokens.len() + cost.len()) as u32;
+
+    let sum = |name: &str, unit: &str, description: &str, points: Vec<Value>| {
+        json!({
+            "name": name,
+            "unit": unit,
+            "description": description,
+            "sum": {
+                "dataPoints": points,
+                "aggregationTemporality": TEMPORALITY_DELTA,
+                "isMonotonic": true,
+            },
+        })
+    };
+    let body = json!({
+        "resourceMetrics": [{
+            "resource": resource(settings),
+            "scopeMetrics": [{
+                "scope": scope(),
+                "metrics": [
+                    sum("sonar.tokens", "{token}", "Tokens used by assistant turns", tokens),
+                    sum("sonar.cost", "USD", "Estimated spend of assistant turns", cost),
+                ],
+            }],
+        }]
+    });
+    (body, points)
+}
+
+fn resource(settings: &OtlpSettings) -> Value {
+    json!({ "attributes": [string("service.name", &settings.service_name)] })
+}
+
+fn scope() -> Value {
+    json!({ "name": "open-sonar", "version": env!("CARGO_PKG_VERSION") })
+}
+
+fn post(settings: &OtlpSettings, path: &str, body: &Value) -> Result<(), String> {
+    let url = format!("{}/{}", settings.endpoint.trim_end_matches('/'), path);
+    let mut request = ureq::post(&url)
+        .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .set("Content-Type", "application/json");
+    for (name, value) in &settings.headers {
+        request = request.set(name, value);
+    }
+    match request.send_string(&body.to_string()) {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, response)) => {
+            let detail = response.into_string().unwrap_or_default();
+            Err(format!("OTLP export to {} failed with {}: {}", url, code, detail.trim()))
+        }
+        Err(e) => Err(format!("OTLP export failed: {}", e)),
+    }
+}
+
+fn string(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// OTLP/JSON carries 64-bit integers as strings.
+fn int(key: &str, value: u64) -> Value {
+    json!({ "key": key, "value": { "intValue": value.to_string() } })
+}
+
+fn double(key: &str, value: f64) -> Value {
+    json!({ "key": key, "value": { "doubleValue": value } })
+}
+
+fn boolean(key: &str, value: bool) -> Value {
+    json!({ "key": key, "value": { "boolValue": value } })
+}
+
+/// Claude Code session ids are UUIDs and become the trace id as they are,
+/// so a trace can be found from the session. Anything else is hashed.
+fn trace_id(session_id: &str) -> String {
+    match uuid::Uuid::parse_str(session_id) {
+        Ok(uuid) => uuid.simple().to_string(),
+        Err(_) => format!("{:016x}{:016x}", fnv1a(session_id, 0), fnv1a(session_id, 1)),
+    }
+}
+
+/// Span ids derive from the session and the turn or tool call, so sending a
+/// session twice produces the same spans.
+fn span_id(session_id: &str, key: &str) -> String {
+    format!("{:016x}", fnv1a(&format!("{}/{}", session_id, key), 0))
+}
+
+fn fnv1a(text: &str, seed: u64) -> u64 {
+    text.bytes()
+        .chain(seed.to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+fn unix_nanos(timestamp: &str) -> Option<u128> {
+    let at = DateTime::parse_from_rfc3339(timestamp).ok()?;
+    Some(at.timestamp_nanos_opt()?.max(0) as u128)
+}
+
+fn now_nanos() -> u128 {
+    Utc::now().timestamp_nanos_opt().unwrap_or_default().max(0) as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::{message, session, temp_db};
+    use crate::db::tools::{ToolCallRecord, ToolCallResult};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+
+    /// A collector on a local port that hands over each request's path and
+    /// JSON body.
+    fn collector() -> (String, Receiver<(String, Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let path = request_line.split(' ').nth(1).unwrap().to_string();
+                tx.send((path, serde_json::from_slice(&body).unwrap())).unwrap();
+                stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+            }
+        });
+        (endpoint, rx)
+    }
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        calendar::parse_utc(timestamp).unwrap()
+    }
+
+    fn span_names(body: &Value) -> Vec<String> {
+        body["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|span| span["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    /// Sums the delta points of a metric, whatever their labels.
+    fn metric_total(body: &Value, name: &str, token_type: Option<&str>) -> f64 {
+        let metrics = body["resourceMetrics"][0]["scopeMetrics"][0]["metrics"].as_array().unwrap();
+        let metric = metrics.iter().find(|m| m["name"] == name).unwrap();
+        metric["sum"]["dataPoints"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|point| match token_type {
+                Some(kind) => point["attributes"][0]["value"]["stringValue"] == kind,
+                None => true,
+            })
+            .map(|point| match &point["asInt"] {
+                Value::String(value) => value.parse().unwrap(),
+                _ => point["asDouble"].as_f64().unwrap(),
+            })
+            .sum()
+    }
+
+    fn add_turn(db: &Database, key: &str, timestamp: &str) {
+        let mut record = session("s1", "2026-03-01T09:00:00.000Z");
+        record.ended_at = Some(timestamp.to_string());
+        db.upsert_session(&record).unwrap();
+        db.insert_message(&message("s1", Some(key), timestamp, 0.5)).unwrap();
+    }
+
+    #[test]
+    fn long_sessions_are_sent_whole_and_resumed_ones_add_to_them() {
+        let db = temp_db("otlp-ticks");
+        let (endpoint, requests) = collector();
+        let settings = OtlpSettings { enabled: true, endpoint, ..OtlpSettings::default() };
+
+        // The first tick only starts the clock.
+        assert_eq!(export_settled_at(&db, &settings, at("2026-03-01T09:00:00.000Z")).unwrap().sessions, 0);
+
+        // A session running for an hour and a half, with a tool call early on.
+        add_turn(&db, "a1", "2026-03-01T09:00:05.000Z");
+        db.insert_tool_call(&ToolCallRecord {
+            tool_use_id: "toolu_1".to_string(),
+            session_id: "s1".to_string(),
+            message_key: Some("a1".to_string()),
+            name: "Bash".to_string(),
+            server: None,
+            input_bytes: 20,
+            started_at: "2026-03-01T09:00:05.000Z".to_string(),
+            cost: 0.0,
+        }).unwrap();
+        db.complete_tool_call(&ToolCallResult {
+            tool_use_id: "toolu_1".to_string(),
+            finished_at: "2026-03-01T09:00:09.000Z".to_string(),
+            is_error: false,
+        }).unwrap();
+        add_turn(&db, "a2", "2026-03-01T10:30:00.000Z");
+
+        // Still settling.
+        assert_eq!(export_settled_at(&db, &settings, at("2026-03-01T10:35:00.000Z")).unwrap().sessions, 0);
+
+        let report = export_settled_at(&db, &settings, at("2026-03-01T10:41:00.000Z")).unwrap();
+        assert_eq!((report.sessions, report.spans), (1, 4));
+        let (path, traces) = requests.recv().unwrap();
+        assert_eq!(path, "/v1/traces");
+        assert_eq!(
+            span_names(&traces),
+            ["session /work/app", "chat claude-sonnet-4-5", "chat claude-sonnet-4-5", "execute_tool Bash"]
+        );
+        let spans = traces["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert!(spans[1..].iter().all(|span| span["parentSpanId"] != Value::Null));
+        assert_eq!(spans[3]["parentSpanId"], spans[1]["spanId"]);
+        let (path, metrics) = requests.recv().unwrap();
+        assert_eq!(path, "/v1/metrics");
+        assert_eq!(metric_total(&metrics, "sonar.tokens", Some("input")), 20.0);
+        assert_eq!(metric_total(&metrics, "sonar.tokens", Some("output")), 40.0);
+        assert_eq!(metric_total(&metrics, "sonar.cost", None), 1.0);
+
+        // Nothing new: nothing sent.
+        assert_eq!(export_settled_at(&db, &settings, at("2026-03-01T10:50:00.000Z")).unwrap().spans, 0);
+        assert!(requests.try_recv().is_err());
+
+        // Resumed later: the trace gets the new turn, the metrics only its usage.
+        add_turn(&db, "a3", "2026-03-01T11:00:00.000Z");
+        let report = export_settled_at(&db, &settings, at("2026-03-01T11:15:00.000Z")).unwrap();
+        assert_eq!(report.spans, 2);
+        let (_, traces) = requests.recv().unwrap();
+        assert_eq!(span_names(&traces), ["session /work/app", "chat claude-sonnet-4-5"]);
+        let (_, metrics) = requests.recv().unwrap();
+        assert_eq!(metric_total(&metrics, "sonar.tokens", Some("input")), 10.0);
+        assert_eq!(metric_total(&metrics, "sonar.cost", None), 0.5);
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TokenUsage {
     pub input_tokens: u64,
     pub output_tokens: u64,
@@ -8,26 +8,47 @@ pub struct TokenUsage {
     pub cache_creation_input_tokens: u64,
 }
 
-impl Default for TokenUsage {
-    fn default() -> Self {
-        Self {
-            input_tokens: 0,
-            output_tokens: 0,
-            cache_read_input_tokens: 0,
-            cache_creation_input_tokens: 0,
-        }
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolUse {
+    pub id: String,
+    pub name: String,
+    pub input: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolResult {
+    pub tool_use_id: String,
+    pub content: String,
+    pub is_error: bool,
+}
+
+/// A `compact_boundary` system line or a `summary` line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Compaction {
+    pub kind: String,
+    pub trigger: Option<String>,
+    pub pre_tokens: Option<u64>,
+    pub summary: Option<String>,
+    /// Last message the summary covers.
+    pub leaf_uuid: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParsedMessage {
     pub message_type: String,
     pub session_id: String,
+    pub uuid: Option<String>,
+    pub message_id: Option<String>,
+    pub cwd: Option<String>,
+    pub git_branch: Option<String>,
     pub timestamp: String,
     pub model: Option<String>,
     pub usage: Option<TokenUsage>,
     pub content_text: Option<String>,
     pub tool_calls: Vec<String>,
+    pub tool_uses: Vec<ToolUse>,
+    pub tool_results: Vec<ToolResult>,
+    pub compaction: Option<Compaction>,
 }
 
 pub fn parse_jsonl_line(line: &str) -> Option<ParsedMessage> {
@@ -51,30 +72,54 @@ pub fn parse_jsonl_line(line: &str) -> Option<ParsedMessage> {
         .unwrap_or("")
         .to_string();
 
+    let uuid = value
+        .get("uuid")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    let cwd = value
+        .get("cwd")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+
+    let git_branch = value
+        .get("gitBranch")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+
+    let compaction = parse_compaction(&value, &msg_type);
+
     let message = value.get("message");
 
+    let message_id = message
+        .and_then(|m| m.get("id"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
     let model = message
         .and_then(|m| m.get("model"))
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
-    let usage = message.and_then(|m| m.get("usage")).and_then(|u| {
-        Some(TokenUsage {
-            input_tokens: u.get("input_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
-            output_tokens: u.get("output_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
-            cache_read_input_tokens: u
-                .get("cache_read_input_tokens")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
-            cache_creation_input_tokens: u
-                .get("cache_creation_input_tokens")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
-        })
+    let usage = message.and_then(|m| m.get("usage")).map(|u| TokenUsage {
+        input_tokens: u.get("input_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+        output_tokens: u.get("output_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+        cache_read_input_tokens: u
+            .get("cache_read_input_tokens")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0),
+        cache_creation_input_tokens: u
+            .get("cache_creation_input_tokens")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0),
     });
 
-    let mut content_text = None;
+    let mut content_text: Option<String> = None;
     let mut tool_calls = Vec::new();
+    let mut tool_uses = Vec::new();
+    let mut tool_results = Vec::new();
 
     if let Some(content) = message.and_then(|m| m.get("content")) {
         if let Some(arr) = content.as_array() {
@@ -83,14 +128,40 @@ pub fn parse_jsonl_line(line: &str) -> Option<ParsedMessage> {
                     match item_type {
                         "text" => {
                             if let Some(text) = item.get("text").and_then(|v| v.as_str()) {
-                                content_text = Some(text.to_string());
+                                content_text = Some(match content_text {
+                                    Some(existing) => format!("{}\n{}", existing, text),
+                                    None => text.to_string(),
+                                });
                             }
                         }
                         "tool_use" => {
                             if let Some(name) = item.get("name").and_then(|v| v.as_str()) {
                                 tool_calls.push(name.to_string());
+                                tool_uses.push(ToolUse {
+                                    id: item
+                                        .get("id")
+                                        .and_then(|v| v.as_str())
+                                        .unwrap_or("")
+                                        .to_string(),
+                                    name: name.to_string(),
+                                    input: item.get("input").cloned().unwrap_or(serde_json::Value::Null),
+                                });
                             }
                         }
+                        "tool_result" => {
+                            tool_results.push(ToolResult {
+                                tool_use_id: item
+                                    .get("tool_use_id")
+                                    .and_then(|v| v.as_str())
+                                    .unwrap_or("")
+                                    .to_string(),
+                                content: tool_result_text(item.get("content")),
+                                is_error: item
+                                    .get("is_error")
+                                    .and_then(|v| v.as_bool())
+                                    .unwrap_or(false),
+                            });
+                        }
                         _ => {}
                     }
                 }
@@ -103,10 +174,113 @@ pub fn parse_jsonl_line(line: &str) -> Option<ParsedMessage> {
     Some(ParsedMessage {
         message_type: msg_type,
         session_id,
+        uuid,
+        message_id,
+        cwd,
+        git_branch,
         timestamp,
         model,
         usage,
         content_text,
         tool_calls,
+        tool_uses,
+        tool_results,
+        compaction,
     })
 }
+
+fn parse_compaction(value: &serde_json::Value, msg_type: &str) -> Option<Compaction> {
+    let str_field = |v: Option<&serde_json::Value>| v.and_then(|v| v.as_str()).map(|s| s.to_string());
+
+    if msg_type == "summary" {
+        return Some(Compaction {
+            kind: "summary".to_string(),
+            trigger: None,
+            pre_tokens: None,
+            summary: str_field(value.get("summary")),
+            leaf_uuid: str_field(value.get("leafUuid")),
+        });
+    }
+
+    if msg_type == "system" && value.get("subtype").and_then(|v| v.as_str()) == Some("compact_boundary") {
+        let metadata = value.get("compactMetadata");
+        return Some(Compaction {
+            kind: "compact_boundary".to_string(),
+            trigger: str_field(metadata.and_then(|m| m.get("trigger"))),
+            pre_tokens: metadata.and_then(|m| m.get("preTokens")).and_then(|v| v.as_u64()),
+            summary: None,
+            leaf_uuid: None,
+        });
+    }
+
+    None
+}
+
+/// Tool results carry either a plain string or a list of content blocks.
+fn tool_result_text(content: Option<&serde_json::Value>) -> String {
+    match content {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(serde_json::Value::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_jsonl_line;
+    use crate::db::tools::mcp_server;
+
+    #[test]
+    fn text_blocks_are_joined_and_tool_uses_extracted() {
+        let line = r#"{"type":"assistant","sessionId":"s1","timestamp":"2026-03-01T10:00:00.000Z",
+            "message":{"id":"msg_1","model":"claude-sonnet-4-5","content":[
+                {"type":"text","text":"Reading the file."},
+                {"type":"tool_use","id":"toolu_1","name":"Read","input":{"file_path":"/work/app/main.rs"}},
+                {"type":"text","text":"Then searching."},
+                {"type":"tool_use","id":"toolu_2","name":"mcp__github__search_code","input":{"q":"todo"}}
+            ],"usage":{"input_tokens":12,"output_tokens":34,"cache_read_input_tokens":56}}}"#
+            .replace('\n', "");
+        let parsed = parse_jsonl_line(&line).unwrap();
+
+        assert_eq!(parsed.content_text.as_deref(), Some("Reading the file.\nThen searching."));
+        assert_eq!(parsed.tool_calls, ["Read", "mcp__github__search_code"]);
+        assert_eq!(parsed.tool_uses[0].id, "toolu_1");
+        assert_eq!(parsed.tool_uses[0].input["file_path"], "/work/app/main.rs");
+        assert_eq!(parsed.tool_uses[1].id, "toolu_2");
+        let usage = parsed.usage.unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens, usage.cache_read_input_tokens), (12, 34, 56));
+    }
+
+    #[test]
+    fn tool_results_read_strings_and_block_lists() {
+        let line = r#"{"type":"user","sessionId":"s1","timestamp":"2026-03-01T10:00:01.000Z","message":{"content":[
+            {"type":"tool_result","tool_use_id":"toolu_1","content":"fn main() {}"},
+            {"type":"tool_result","tool_use_id":"toolu_2","is_error":true,
+             "content":[{"type":"text","text":"rate limited"},{"type":"text","text":"retry later"}]}
+        ]}}"#
+            .replace('\n', "");
+        let parsed = parse_jsonl_line(&line).unwrap();
+
+        assert_eq!(parsed.tool_results.len(), 2);
+        assert_eq!(parsed.tool_results[0].content, "fn main() {}");
+        assert!(!parsed.tool_results[0].is_error);
+        assert_eq!(parsed.tool_results[1].tool_use_id, "toolu_2");
+        assert_eq!(parsed.tool_results[1].content, "rate limited\nretry later");
+        assert!(parsed.tool_results[1].is_error);
+        assert!(parsed.content_text.is_none());
+    }
+
+    #[test]
+    fn mcp_tool_names_give_their_server() {
+        assert_eq!(mcp_server("mcp__github__search_code").as_deref(), Some("github"));
+        assert_eq!(mcp_server("mcp__playwright__browser__click").as_deref(), Some("playwright"));
+        assert_eq!(mcp_server("mcp__solo").as_deref(), Some("solo"));
+        assert_eq!(mcp_server("mcp____tool"), None);
+        assert_eq!(mcp_server("Read"), None);
+        assert_eq!(mcp_server("my_mcp__tool"), None);
+    }
+}
@@ -0,0 +1,485 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use super::cost::calculate_cost;
+use super::parser::{parse_jsonl_line, ParsedMessage};
+use super::redact::redact_secrets;
+use super::watcher::get_claude_projects_dir;
+use crate::db::export::ExportReport;
+use crate::db::{calendar, Database, SessionRecord};
+
+const DEFAULT_COLLAPSE_OVER: usize = 2000;
+const DEFAULT_MAX_TOOL_RESULT: usize = 20_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    Markdown,
+    /// A single HTML file with inline styles and no external assets.
+    Html,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranscriptOptions {
+    pub redact_secrets: bool,
+    /// Tool inputs and outputs longer than this many characters are folded
+    /// into a `<details>` block; `None` leaves everything expanded.
+    pub collapse_over: Option<usize>,
+    /// Tool outputs are cut after this many characters, with a note of how
+    /// much was left out; `None` keeps them whole.
+    pub max_tool_result: Option<usize>,
+}
+
+impl Default for TranscriptOptions {
+    fn default() -> Self {
+        Self {
+            redact_secrets: true,
+            collapse_over: Some(DEFAULT_COLLAPSE_OVER),
+            max_tool_result: Some(DEFAULT_MAX_TOOL_RESULT),
+        }
+    }
+}
+
+enum Block {
+    Text(String),
+    ToolUse { name: String, input: String },
+    ToolResult { name: String, output: String, is_error: bool },
+}
+
+enum Entry {
+    Turn { role: String, timestamp: String, model: Option<String>, message_id: Option<String>, cost: f64, blocks: Vec<Block> },
+    Compaction { kind: String, timestamp: String, trigger: Option<String>, pre_tokens: Option<u64>, summary: Option<String> },
+}
+
+/// The session's transcript under the Claude projects directory.
+pub fn find_transcript(session_id: &str) -> Option<PathBuf> {
+    let projects_dir = get_claude_projects_dir()?;
+    let file_name = format!("{}.jsonl", session_id);
+    std::fs::read_dir(projects_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// Renders the session's transcript to `path` as Markdown or HTML. Session
+/// totals come from the database when the session has been imported.
+pub fn export_transcript(
+    db: &Database,
+    session_id: &str,
+    format: TranscriptFormat,
+    options: &TranscriptOptions,
+    path: &Path,
+) -> Result<ExportReport, String> {
+    let source = find_transcript(session_id)
+        .ok_or_else(|| format!("No transcript found for session {}", session_id))?;
+    let session = db.get_session(session_id)?;
+    let (document, turns) = render_transcript(&source, session_id, session.as_ref(), format, options)?;
+
+    std::fs::write(path, &document).map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+    Ok(ExportReport {
+        path: path.to_string_lossy().to_string(),
+        rows: turns,
+        bytes: document.len() as u64,
+    })
+}
+
+/// The rendered document and the number of turns in it.
+pub fn render_transcript(
+    source: &Path,
+    session_id: &str,
+    session: Option<&SessionRecord>,
+    format: TranscriptFormat,
+    options: &TranscriptOptions,
+) -> Result<(String, u64), String> {
+    let file = File::open(source).map_err(|e| e.to_string())?;
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+    let mut cwd: Option<String> = None;
+
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        if let Some(parsed) = parse_jsonl_line(line.trim()) {
+            if cwd.is_none() {
+                cwd = parsed.cwd.clone();
+            }
+            push_entry(&mut entries, &mut tool_names, parsed, options);
+        }
+    }
+
+    let turns = entries.iter().filter(|e| matches!(e, Entry::Turn { .. })).count() as u64;
+    let header = header_rows(session_id, session, cwd);
+    let document = match format {
+        TranscriptFormat::Markdown => markdown(session_id, &header, &entries, options),
+        TranscriptFormat::Html => html(session_id, &header, &entries, options),
+    };
+    Ok((document, turns))
+}
+
+fn push_entry(
+    entries: &mut Vec<Entry>,
+    tool_names: &mut HashMap<String, String>,
+    parsed: ParsedMessage,
+    options: &TranscriptOptions,
+) {
+    let clean = |text: &str| -> String {
+        if options.redact_secrets { redact_secrets(text).into_owned() } else { text.to_string() }
+    };
+
+    if let Some(c) = parsed.compaction {
+        entries.push(Entry::Compaction {
+            kind: c.kind,
+            timestamp: parsed.timestamp,
+            trigger: c.trigger,
+            pre_tokens: c.pre_tokens,
+            summary: c.summary.as_deref().map(clean),
+        });
+        return;
+    }
+    if parsed.message_type != "user" && parsed.message_type != "assistant" {
+        return;
+    }
+    // Every line of a response repeats its usage, so a message id is priced
+    // once, by the line reporting the most.
+    let line_cost = match (&parsed.usage, &parsed.model) {
+        (Some(usage), Some(model)) => calculate_cost(usage, model),
+        _ => 0.0,
+    };
+
+    let mut blocks = Vec::new();
+    if let Some(text) = parsed.content_text.as_deref().filter(|t| !t.trim().is_empty()) {
+        blocks.push(Block::Text(clean(text)));
+    }
+    for tool_use in &parsed.tool_uses {
+        tool_names.insert(tool_use.id.clone(), tool_use.name.clone());
+        let input = serde_json::to_string_pretty(&tool_use.input).unwrap_or_default();
+        blocks.push(Block::ToolUse { name: tool_use.name.clone(), input: clean(&input) });
+    }
+    for result in &parsed.tool_results {
+        blocks.push(Block::ToolResult {
+            name: tool_names.get(&result.tool_use_id).cloned().unwrap_or_else(|| "tool".to_string()),
+            output: truncate(clean(&result.content), options.max_tool_result),
+            is_error: result.is_error,
+        });
+    }
+    if blocks.is_empty() {
+        // Thinking-only lines still cost something.
+        if let Some(Entry::Turn { role, message_id, cost, .. }) = entries.last_mut() {
+            if *role == parsed.message_type {
+                if message_id.is_some() && *message_id == parsed.message_id {
+                    *cost = cost.max(line_cost);
+                } else {
+                    *cost += line_cost;
+                }
+            }
+        }
+        return;
+    }
+
+    // Claude Code writes each content block of a response as its own line,
+    // and tool results come back as user lines; both read better folded into
+    // the turn they belong to.
+    if let Some(Entry::Turn { role, message_id, cost, blocks: previous, .. }) = entries.last_mut() {
+        let same_message = *role == parsed.message_type && message_id.is_some() && *message_id == parsed.message_id;
+        let results_only = parsed.content_text.is_none() && !parsed.tool_results.is_empty();
+        if same_message || results_only {
+            previous.extend(blocks);
+            *cost = if same_message { cost.max(line_cost) } else { *cost + line_cost };
+            return;
+        }
+    }
+
+    entries.push(Entry::Turn {
+        role: parsed.message_type,
+        timestamp: parsed.timestamp,
+        model: parsed.model,
+        message_id: parsed.message_id,
+        cost: line_cost,
+        blocks,
+    });
+}
+
+/// `text` cut to `limit` characters, noting how many were left out.
+fn truncate(text: String, limit: Option<usize>) -> String {
+    let total = text.chars().count();
+    match limit {
+        Some(limit) if total > limit => {
+            let kept: String = text.chars().take(limit).collect();
+            format!("{}\n… ({} more characters)", kept, total - limit)
+        }
+        _ => text,
+    }
+}
+
+fn header_rows(session_id: &str, session: Option<&SessionRecord>, cwd: Option<String>) -> Vec<(&'static str, String)> {
+    let mut rows = vec![("Session", session_id.to_string())];
+    if let Some(dir) = cwd.or_else(|| session.and_then(|s| s.cwd.clone())) {
+        rows.push(("Project", dir));
+    }
+    if let Some(s) = session {
+        if let Some(branch) = &s.git_branch {
+            rows.push(("Branch", branch.clone()));
+        }
+        rows.push(("Model", s.model.clone()));
+        rows.push(("Started", display_time(&s.started_at)));
+        if let Some(ended) = &s.ended_at {
+            rows.push(("Ended", display_time(ended)));
+        }
+        rows.push(("Tokens", s.total_tokens.to_string()));
+        rows.push(("Cost", format!("${:.4}", s.total_cost)));
+        rows.push(("Tool calls", s.tool_calls.to_string()));
+    }
+    rows
+}
+
+fn display_time(ts: &str) -> String {
+    calendar::parse_utc(ts)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| ts.to_string())
+}
+
+/// Summary lines carry no timestamp; they stand in for turns before the file starts.
+fn compaction_title(kind: &str, timestamp: &str) -> String {
+    if kind == "summary" {
+        "Summary of earlier conversation".to_string()
+    } else {
+        format!("Conversation compacted · {}", display_time(timestamp))
+    }
+}
+
+fn role_title(role: &str) -> &'static str {
+    if role == "assistant" { "Assistant" } else { "User" }
+}
+
+fn should_collapse(text: &str, options: &TranscriptOptions) -> bool {
+    options.collapse_over.is_some_and(|limit| text.chars().count() > limit)
+}
+
+/// A backtick fence longer than any run of backticks in `text`.
+fn fence(text: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    "`".repeat((longest + 1).max(3))
+}
+
+fn markdown(session_id: &str, header: &[(&str, String)], entries: &[Entry], options: &TranscriptOptions) -> String {
+    let mut out = format!("# Transcript {}\n\n| | |\n|---|---|\n", session_id);
+    for (label, value) in header {
+        out.push_str(&format!("| {} | {} |\n", label, value.replace('|', "\\|")));
+    }
+
+    let code = |out: &mut String, title: String, lang: &str, text: &str| {
+        let fence = fence(text);
+        let body = format!("{}{}\n{}\n{}\n", fence, lang, text, fence);
+        if should_collapse(text, options) {
+            out.push_str(&format!(
+                "\n<details>\n<summary>{} ({} characters)</summary>\n\n{}\n</details>\n",
+                title, text.chars().count(), body
+            ));
+        } else {
+            out.push_str(&format!("\n**{}**\n\n{}", title, body));
+        }
+    };
+
+    for entry in entries {
+        match entry {
+            Entry::Turn { role, timestamp, model, cost, blocks, .. } => {
+                out.push_str(&format!("\n---\n\n### {} · {}", role_title(role), display_time(timestamp)));
+                if let Some(model) = model {
+                    out.push_str(&format!(" · `{}`", model));
+                }
+                if *cost > 0.0 {
+                    out.push_str(&format!(" · ${:.4}", cost));
+                }
+                out.push('\n');
+                for block in blocks {
+                    match block {
+                        Block::Text(text) => out.push_str(&format!("\n{}\n", text)),
+                        Block::ToolUse { name, input } => code(&mut out, format!("Tool call: {}", name), "json", input),
+                        Block::ToolResult { name, output, is_error } => {
+                            let title = if *is_error { format!("{} error", name) } else { format!("{} output", name) };
+                            code(&mut out, title, "", output)
+                        }
+                    }
+                }
+            }
+            Entry::Compaction { kind, timestamp, trigger, pre_tokens, summary } => {
+                out.push_str(&format!("\n---\n\n> **{}**", compaction_title(kind, timestamp)));
+                if let Some(trigger) = trigger {
+                    out.push_str(&format!(" · {}", trigger));
+                }
+                if let Some(tokens) = pre_tokens {
+                    out.push_str(&format!(" · {} tokens before", tokens));
+                }
+                out.push('\n');
+                if let Some(summary) = summary {
+                    out.push_str(&format!(">\n> {}\n", summary.replace('\n', "\n> ")));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+const HTML_STYLE: &str = "
+body { font: 14px/1.5 -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif; max-width: 960px; margin: 2rem auto; padding: 0 1rem; color: #1f2328; background: #fff; }
+h1 { font-size: 1.4rem; word-break: break-all; }
+table { border-collapse: collapse; margin-bottom: 1.5rem; }
+td { padding: 2px 12px 2px 0; vertical-align: top; }
+td:first-child { color: #656d76; }
+.turn { border-top: 1px solid #d0d7de; padding: 0.75rem 0; }
+.turn h3 { font-size: 0.95rem; margin: 0 0 0.5rem; }
+.turn.user h3 { color: #0969da; }
+.turn.assistant h3 { color: #8250df; }
+.meta { color: #656d76; font-weight: normal; }
+.text { white-space: pre-wrap; word-wrap: break-word; }
+pre { background: #f6f8fa; padding: 0.75rem; overflow-x: auto; border-radius: 6px; margin: 0.25rem 0 0.75rem; }
+details summary { cursor: pointer; color: #656d76; }
+.error summary { color: #cf222e; }
+.compaction { border-left: 3px solid #bf8700; background: #fff8c5; padding: 0.5rem 0.75rem; margin: 0.75rem 0; }
+@media (prefers-color-scheme: dark) {
+  body { color: #e6edf3; background: #0d1117; }
+  pre { background: #161b22; }
+  .turn { border-color: #30363d; }
+  .compaction { background: #272115; }
+}
+";
+
+fn html(session_id: &str, header: &[(&str, String)], entries: &[Entry], options: &TranscriptOptions) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Transcript {}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>Transcript {}</h1>\n<table>\n",
+        escape_html(session_id), HTML_STYLE, escape_html(session_id)
+    );
+    for (label, value) in header {
+        out.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>\n", label, escape_html(value)));
+    }
+    out.push_str("</table>\n");
+
+    let code = |out: &mut String, title: String, text: &str, is_error: bool| {
+        let open = if should_collapse(text, options) { "" } else { " open" };
+        let class = if is_error { " class=\"error\"" } else { "" };
+        out.push_str(&format!(
+            "<details{}{}><summary>{} ({} characters)</summary><pre>{}</pre></details>\n",
+            class, open, escape_html(&title), text.chars().count(), escape_html(text)
+        ));
+    };
+
+    for entry in entries {
+        match entry {
+            Entry::Turn { role, timestamp, model, cost, blocks, .. } => {
+                out.push_str(&format!(
+                    "<section class=\"turn {}\">\n<h3>{} <span class=\"meta\">· {}",
+                    if role == "assistant" { "assistant" } else { "user" },
+                    role_title(role),
+                    escape_html(&display_time(timestamp))
+                ));
+                if let Some(model) = model {
+                    out.push_str(&format!(" · {}", escape_html(model)));
+                }
+                if *cost > 0.0 {
+                    out.push_str(&format!(" · ${:.4}", cost));
+                }
+                out.push_str("</span></h3>\n");
+                for block in blocks {
+                    match block {
+                        Block::Text(text) => out.push_str(&format!("<div class=\"text\">{}</div>\n", escape_html(text))),
+                        Block::ToolUse { name, input } => code(&mut out, format!("Tool call: {}", name), input, false),
+                        Block::ToolResult { name, output, is_error } => {
+                            let title = if *is_error { format!("{} error", name) } else { format!("{} output", name) };
+                            code(&mut out, title, output, *is_error)
+                        }
+                    }
+                }
+                out.push_str("</section>\n");
+            }
+            Entry::Compaction { kind, timestamp, trigger, pre_tokens, summary } => {
+                out.push_str(&format!(
+                    "<div class=\"compaction\"><strong>{}</strong> <span class=\"meta\">",
+                    escape_html(&compaction_title(kind, timestamp))
+                ));
+                if let Some(trigger) = trigger {
+                    out.push_str(&format!(" · {}", escape_html(trigger)));
+                }
+                if let Some(tokens) = pre_tokens {
+                    out.push_str(&format!(" · {} tokens before", tokens));
+                }
+                out.push_str("</span>");
+                if let Some(summary) = summary {
+                    out.push_str(&format!("<div class=\"text\">{}</div>", escape_html(summary)));
+                }
+                out.push_str("</div>\n");
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::temp_path;
+
+    const TRANSCRIPT: &str = r#"{"type":"user","uuid":"u1","timestamp":"2026-02-02T10:00:00.000Z","message":{"role":"user","content":"list the files"}}
+{"type":"assistant","uuid":"a1","timestamp":"2026-02-02T10:00:05.000Z","message":{"id":"msg_1","model":"claude-sonnet-4-5","usage":{"input_tokens":1000000,"output_tokens":0},"content":[{"type":"text","text":"Listing"}]}}
+{"type":"assistant","uuid":"a2","timestamp":"2026-02-02T10:00:06.000Z","message":{"id":"msg_1","model":"claude-sonnet-4-5","usage":{"input_tokens":1000000,"output_tokens":0},"content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"ls"}}]}}
+{"type":"user","uuid":"u2","timestamp":"2026-02-02T10:00:09.000Z","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","content":"abcdefghij"}]}}
+"#;
+
+    fn render(format: TranscriptFormat, options: &TranscriptOptions) -> String {
+        let path = temp_path(&format!("transcript-{:?}", format)).with_file_name("s1.jsonl");
+        std::fs::write(&path, TRANSCRIPT).unwrap();
+        render_transcript(&path, "s1", None, format, options).unwrap().0
+    }
+
+    #[test]
+    fn turns_show_their_cost() {
+        let price = calculate_cost(
+            &parse_jsonl_line(TRANSCRIPT.lines().nth(1).unwrap()).unwrap().usage.unwrap(),
+            "claude-sonnet-4-5",
+        );
+        // Both lines of msg_1 carry the same usage; it is counted once.
+        let expected = format!("${:.4}", price);
+        let markdown = render(TranscriptFormat::Markdown, &TranscriptOptions::default());
+        assert!(markdown.contains(&format!("`claude-sonnet-4-5` · {}\n", expected)), "{}", markdown);
+        // User turns cost nothing and show no cost.
+        assert_eq!(markdown.matches(" · $").count(), 1);
+
+        let html = render(TranscriptFormat::Html, &TranscriptOptions::default());
+        assert!(html.contains(&format!("claude-sonnet-4-5 · {}</span>", expected)), "{}", html);
+    }
+
+    #[test]
+    fn long_tool_results_are_cut() {
+        let options = TranscriptOptions { max_tool_result: Some(4), ..Default::default() };
+        let markdown = render(TranscriptFormat::Markdown, &options);
+        assert!(markdown.contains("abcd\n… (6 more characters)\n"), "{}", markdown);
+        assert!(!markdown.contains("abcde"));
+
+        let options = TranscriptOptions { max_tool_result: None, ..Default::default() };
+        assert!(render(TranscriptFormat::Markdown, &options).contains("abcdefghij"));
+    }
+}
@@ -2,12 +2,26 @@ use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Seek, SeekFrom};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Emitter};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 
+use crate::db::{calendar, Database};
+use crate::detection::anomaly::AnomalyDetector;
+use crate::detection::loop_detector::LoopDetector;
+use crate::pty::manager::PermissionMode;
+
+use super::burn_rate::{BurnRateTracker, BurnRates};
+use super::context::ContextPressure;
 use super::cost::calculate_cost;
-use super::parser::{parse_jsonl_line, TokenUsage};
+use super::ingest::{IngestedLine, SessionIngest};
+use super::metrics::Metrics;
+use super::parser::{parse_jsonl_line, ParsedMessage, TokenUsage};
+
+/// How long a session can go without a transcript line before it counts as
+/// completed.
+const SESSION_IDLE_MINUTES: i64 = 30;
 
 #[derive(Clone, serde::Serialize)]
 pub struct SessionUpdate {
@@ -19,6 +33,10 @@ pub struct SessionUpdate {
     pub message_count: u32,
     pub tool_calls: Vec<String>,
     pub usage: TokenUsage,
+    /// Rolling rates for this agent and for all agents together.
+    pub burn_rate: BurnRates,
+    pub global_burn_rate: BurnRates,
+    pub context_pressure: ContextPressure,
 }
 
 #[derive(Clone, serde::Serialize)]
@@ -37,19 +55,84 @@ struct TrackedFile {
     cumulative_tool_calls: Vec<String>,
     last_model: Option<String>,
     cumulative_usage: TokenUsage,
+    context_tokens: u64,
+    peak_context_tokens: u64,
+    compactions: u32,
+    /// `None` for subagent transcripts, which import skips as well.
+    ingest: Option<SessionIngest>,
+}
+
+/// Receives the watcher's events: the app window, or the headless CLI.
+pub trait EventSink: Send + Sync + 'static {
+    fn send(&self, event: &str, payload: serde_json::Value);
+}
+
+#[cfg(feature = "app")]
+impl EventSink for tauri::AppHandle {
+    fn send(&self, event: &str, payload: serde_json::Value) {
+        use tauri::Emitter;
+        let _ = self.emit(event, payload);
+    }
+}
+
+pub(crate) fn emit<T: Serialize>(sink: &dyn EventSink, event: &str, payload: T) {
+    if let Ok(value) = serde_json::to_value(payload) {
+        sink.send(event, value);
+    }
+}
+
+/// What the watcher keeps about usage across all transcripts.
+struct WatchState {
+    database: Arc<Database>,
+    /// Agent credited with transcripts outside every registered directory;
+    /// `None` ignores them.
+    fallback_agent: Mutex<Option<String>>,
+    /// Start of the usage block last reported, to tell when a new one opens
+    /// and to read the next update from there.
+    current_block: Mutex<Option<String>>,
+    burn_rates: Arc<BurnRateTracker>,
+    anomalies: AnomalyDetector,
+    /// Fed each assistant line, to catch agents repeating the same step.
+    loops: LoopDetector,
+    metrics: Arc<Metrics>,
+    /// Permission mode of each agent the app spawned, recorded on its
+    /// sessions.
+    permission_modes: Mutex<HashMap<String, PermissionMode>>,
+    /// Transcripts are read again from the start after a restart; lines
+    /// older than this were already counted and alerted on.
+    started_at: DateTime<Utc>,
+}
+
+impl WatchState {
+    fn is_replayed(&self, timestamp: &str) -> bool {
+        calendar::parse_utc(timestamp).is_some_and(|ts| ts < self.started_at)
+    }
 }
 
 pub struct SessionWatcher {
     tracked: Arc<Mutex<HashMap<PathBuf, TrackedFile>>>,
     agent_dirs: Arc<Mutex<HashMap<String, String>>>,
+    state: Arc<WatchState>,
     _watcher: Option<RecommendedWatcher>,
 }
 
 impl SessionWatcher {
-    pub fn new() -> Self {
+    pub fn new(database: Arc<Database>) -> Self {
+        let burn_rates = Arc::new(BurnRateTracker::new());
         Self {
             tracked: Arc::new(Mutex::new(HashMap::new())),
             agent_dirs: Arc::new(Mutex::new(HashMap::new())),
+            state: Arc::new(WatchState {
+                database,
+                fallback_agent: Mutex::new(None),
+                current_block: Mutex::new(None),
+                metrics: Arc::new(Metrics::new(burn_rates.clone())),
+                burn_rates,
+                anomalies: AnomalyDetector::new(),
+                loops: LoopDetector::new(),
+                permission_modes: Mutex::new(HashMap::new()),
+                started_at: Utc::now(),
+            }),
             _watcher: None,
         }
     }
@@ -60,12 +143,32 @@ impl SessionWatcher {
         }
     }
 
-    pub fn start(&mut self, app_handle: AppHandle) -> Result<(), String> {
+    /// Records `mode` on the sessions of `agent_id` from now on.
+    pub fn set_permission_mode(&self, agent_id: &str, mode: PermissionMode) {
+        if let Ok(mut modes) = self.state.permission_modes.lock() {
+            modes.insert(agent_id.to_string(), mode);
+        }
+    }
+
+    /// Follows every transcript, crediting those outside registered agent
+    /// directories to `agent_id`.
+    pub fn watch_unregistered(&self, agent_id: &str) {
+        if let Ok(mut fallback) = self.state.fallback_agent.lock() {
+            *fallback = Some(agent_id.to_string());
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.state.metrics.clone()
+    }
+
+    pub fn start(&mut self, sink: Arc<dyn EventSink>) -> Result<(), String> {
         let claude_dir = get_claude_projects_dir()
             .ok_or_else(|| "No ~/.claude/projects directory found".to_string())?;
 
         let tracked = self.tracked.clone();
         let agent_dirs = self.agent_dirs.clone();
+        let state = self.state.clone();
 
         let watcher_result = notify::recommended_watcher(
             move |res: Result<notify::Event, notify::Error>| {
@@ -78,7 +181,8 @@ impl SessionWatcher {
                                         path,
                                         &tracked,
                                         &agent_dirs,
-                                        &app_handle,
+                                        &state,
+                                        sink.as_ref(),
                                     );
                                 }
                             }
@@ -101,7 +205,7 @@ impl SessionWatcher {
 }
 
 fn dir_to_agent_id(
-    jsonl_path: &PathBuf,
+    jsonl_path: &Path,
     agent_dirs: &Arc<Mutex<HashMap<String, String>>>,
 ) -> Option<String> {
     let parent = jsonl_path.parent()?;
@@ -132,9 +236,11 @@ fn process_changes(
     path: &PathBuf,
     tracked: &Arc<Mutex<HashMap<PathBuf, TrackedFile>>>,
     agent_dirs: &Arc<Mutex<HashMap<String, String>>>,
-    app_handle: &AppHandle,
+    state: &WatchState,
+    sink: &dyn EventSink,
 ) -> Result<(), String> {
-    let agent_id = match dir_to_agent_id(path, agent_dirs) {
+    let fallback = || state.fallback_agent.lock().ok().and_then(|agent| agent.clone());
+    let agent_id = match dir_to_agent_id(path, agent_dirs).or_else(fallback) {
         Some(id) => id,
         None => return Ok(()),
     };
@@ -150,20 +256,43 @@ fn process_changes(
         cumulative_tool_calls: Vec::new(),
         last_model: None,
         cumulative_usage: TokenUsage::default(),
+        context_tokens: 0,
+        peak_context_tokens: 0,
+        compactions: 0,
+        ingest: session_ingest(path, &agent_id, &state.database),
     });
 
     entry.agent_id = agent_id.clone();
+    if let Some(ingest) = entry.ingest.as_mut() {
+        let mode = state.permission_modes.lock().ok().and_then(|modes| modes.get(&agent_id).copied());
+        ingest.set_permission_mode(mode.map(|m| m.as_str().to_string()));
+    }
+    let project = entry
+        .ingest
+        .as_ref()
+        .map(|ingest| ingest.record().project.clone())
+        .or_else(|| project_of(path))
+        .unwrap_or_default();
 
     let file = File::open(path).map_err(|e| e.to_string())?;
     let mut reader = BufReader::new(file);
     reader.seek(SeekFrom::Start(entry.offset)).map_err(|e| e.to_string())?;
 
     let mut new_messages = 0u32;
+    let mut ingested = Vec::new();
     let mut line = String::new();
 
     while reader.read_line(&mut line).map_err(|e| e.to_string())? > 0 {
         if let Some(parsed) = parse_jsonl_line(line.trim()) {
+            if parsed.compaction.is_some() {
+                entry.compactions += 1;
+            }
+
             if parsed.session_id.is_empty() {
+                // Summary lines carry no session id but belong to this transcript.
+                if let (Some(ingest), true) = (entry.ingest.as_mut(), parsed.compaction.is_some()) {
+                    ingested.push(ingest.ingest(&parsed));
+                }
                 line.clear();
                 continue;
             }
@@ -172,6 +301,10 @@ fn process_changes(
                 entry.last_model = Some(model.clone());
             }
 
+            let msg_cost = match (&parsed.usage, &entry.last_model) {
+                (Some(usage), Some(model)) => calculate_cost(usage, model),
+                _ => 0.0,
+            };
             if let Some(ref usage) = parsed.usage {
                 let msg_tokens = usage.input_tokens
                     + usage.output_tokens
@@ -184,18 +317,39 @@ fn process_changes(
                 entry.cumulative_usage.cache_read_input_tokens += usage.cache_read_input_tokens;
                 entry.cumulative_usage.cache_creation_input_tokens += usage.cache_creation_input_tokens;
 
-                if let Some(ref model) = entry.last_model {
-                    entry.cumulative_cost += calculate_cost(usage, model);
+                entry.context_tokens = usage.input_tokens
+                    + usage.cache_read_input_tokens
+                    + usage.cache_creation_input_tokens;
+                entry.peak_context_tokens = entry.peak_context_tokens.max(entry.context_tokens);
+
+                entry.cumulative_cost += msg_cost;
+                state.burn_rates.record(&agent_id, &parsed.timestamp, msg_tokens, msg_cost);
+            }
+
+            if !state.is_replayed(&parsed.timestamp) {
+                let model = entry.last_model.as_deref().unwrap_or("unknown");
+                state.metrics.record_message(&agent_id, &project, model, parsed.usage.as_ref(), msg_cost);
+                for tool in &parsed.tool_calls {
+                    state.metrics.record_tool_call(&agent_id, tool);
+                }
+
+                if parsed.message_type == "assistant" {
+                    check_loop(state, sink, &agent_id, &parsed);
                 }
             }
 
+            if let Some(ref mut ingest) = entry.ingest {
+                ingested.push(ingest.ingest(&parsed));
+            }
+
             entry.cumulative_tool_calls.extend(parsed.tool_calls);
             entry.cumulative_messages += 1;
             new_messages += 1;
 
             if parsed.message_type == "user" && entry.cumulative_messages == 1 {
                 if let Some(ref text) = parsed.content_text {
-                    let _ = app_handle.emit(
+                    emit(
+                        sink,
                         "agent-detected",
                         AgentDetected {
                             agent_id: agent_id.clone(),
@@ -209,10 +363,28 @@ fn process_changes(
         line.clear();
     }
 
-    entry.offset = reader.seek(SeekFrom::Current(0)).map_err(|e| e.to_string())?;
+    entry.offset = reader.stream_position().map_err(|e| e.to_string())?;
+
+    if let Some(ref ingest) = entry.ingest {
+        if !ingested.is_empty() {
+            persist(&state.database, ingest, &ingested)?;
+            let messages: Vec<_> = ingested.iter().filter_map(|line| line.message.as_ref()).collect();
+            if !messages.is_empty() {
+                emit_block_update(state, sink)?;
+            }
+            let live: Vec<_> = messages.into_iter().filter(|m| !state.is_replayed(&m.timestamp)).collect();
+            if !live.is_empty() {
+                for alert in state.anomalies.check_session(&state.database, ingest.record(), &live)? {
+                    emit(sink, "anomaly-detected", alert);
+                }
+            }
+        }
+    }
 
     if new_messages > 0 {
-        let _ = app_handle.emit(
+        let burn_rate = state.burn_rates.agent_rates(&agent_id);
+        emit(
+            sink,
             "session-update",
             SessionUpdate {
                 agent_id,
@@ -223,6 +395,14 @@ fn process_changes(
                 message_count: entry.cumulative_messages,
                 tool_calls: entry.cumulative_tool_calls.clone(),
                 usage: entry.cumulative_usage.clone(),
+                burn_rate,
+                global_burn_rate: state.burn_rates.global_rates(),
+                context_pressure: ContextPressure::new(
+                    entry.last_model.as_deref().unwrap_or_default(),
+                    entry.context_tokens,
+                    entry.peak_context_tokens,
+                    entry.compactions,
+                ),
             },
         );
     }
@@ -230,6 +410,86 @@ fn process_changes(
     Ok(())
 }
 
+/// Feeds an assistant line's text and tool inputs to the loop detector and
+/// reports a loop the first time one is seen.
+fn check_loop(state: &WatchState, sink: &dyn EventSink, agent_id: &str, parsed: &ParsedMessage) {
+    let mut output = parsed.content_text.clone().unwrap_or_default();
+    for tool in &parsed.tool_uses {
+        output.push_str(&format!(" {} {}", tool.name, tool.input));
+    }
+
+    let was_looping = state.loops.is_looping(agent_id);
+    let cost_per_sec = state.burn_rates.agent_rates(agent_id).last_minute.cost_per_sec;
+    if let Some(alert) = state.loops.feed(agent_id, &output, cost_per_sec) {
+        if !was_looping {
+            state.metrics.record_loop_alert(agent_id);
+            emit(sink, "loop-detected", alert);
+        }
+    }
+}
+
+/// The directory a transcript belongs to, from its encoded folder name.
+fn project_of(path: &Path) -> Option<String> {
+    Some(path.parent()?.file_name()?.to_string_lossy().replace('-', "/"))
+}
+
+fn session_ingest(path: &Path, agent_id: &str, database: &Database) -> Option<SessionIngest> {
+    let session_id = path.file_stem()?.to_str()?;
+    if session_id.starts_with("agent-") {
+        return None;
+    }
+    let project = project_of(path)?;
+    let energy = database.get_energy_settings().unwrap_or_default();
+    Some(SessionIngest::new(session_id, agent_id, &project, "running", energy))
+}
+
+/// Writes the session's running totals, then the new lines' messages, search
+/// entries and tool calls. Rows still stored are keyed, so lines seen again
+/// after a restart are not stored twice; lines older than the retention
+/// cutoff are skipped, as their rows may already have been pruned and would
+/// otherwise be counted again.
+fn persist(
+    database: &Database,
+    ingest: &SessionIngest,
+    lines: &[IngestedLine],
+) -> Result<(), String> {
+    if ingest.record().started_at.is_empty() {
+        return Ok(());
+    }
+    database.upsert_session(ingest.record())?;
+    let privacy = database.get_search_privacy()?;
+    let cutoff = database.get_retention_policy()?.cutoff();
+    for line in lines.iter().filter(|line| !line.is_older_than(cutoff.as_deref())) {
+        line.persist(database, &privacy)?;
+    }
+    Ok(())
+}
+
+/// Reports the open usage block after new usage is stored, announcing it
+/// first when it is a block not seen before.
+fn emit_block_update(state: &WatchState, sink: &dyn EventSink) -> Result<(), String> {
+    let mut last_start = state.current_block.lock().map_err(|e| e.to_string())?;
+    let block = match state.database.get_current_block_since(last_start.as_deref())? {
+        Some(block) => block,
+        None => return Ok(()),
+    };
+
+    if last_start.as_deref() != Some(block.block.start.as_str()) {
+        *last_start = Some(block.block.start.clone());
+        emit(sink, "usage-block-started", block.block.clone());
+    }
+    emit(sink, "usage-block-update", block);
+    Ok(())
+}
+
+/// Marks sessions that have written nothing for `SESSION_IDLE_MINUTES` as
+/// completed. Transcripts don't say when a session ends, so going quiet is
+/// taken as the end. Returns how many were marked.
+pub fn complete_idle_sessions(database: &Database) -> Result<usize, String> {
+    let idle_since = Utc::now() - chrono::Duration::minutes(SESSION_IDLE_MINUTES);
+    database.complete_idle_sessions(&calendar::format_utc(idle_since))
+}
+
 pub fn get_claude_projects_dir() -> Option<PathBuf> {
     let home = dirs::home_dir()?;
     let config_path = home.join(".config").join("claude").join("projects");
@@ -242,3 +502,96 @@ pub fn get_claude_projects_dir() -> Option<PathBuf> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::retention::RetentionPolicy;
+    use crate::db::test_util::temp_path;
+    use std::io::Write;
+
+    #[derive(Default)]
+    struct Events(Mutex<Vec<String>>);
+
+    impl EventSink for Events {
+        fn send(&self, event: &str, _payload: serde_json::Value) {
+            self.0.lock().unwrap().push(event.to_string());
+        }
+    }
+
+    fn assistant_line(uuid: &str, timestamp: &str) -> String {
+        format!(
+            r#"{{"type":"assistant","sessionId":"s1","uuid":"{uuid}","timestamp":"{timestamp}","message":{{"id":"msg_{uuid}","model":"claude-sonnet-4-5","usage":{{"input_tokens":10,"output_tokens":20}},"content":[{{"type":"text","text":"Running the parser suite again to see whether the flaky case passes now"}}]}}}}"#
+        ) + "\n"
+    }
+
+    fn messages_total(metrics: &Metrics) -> String {
+        metrics
+            .render(&[])
+            .lines()
+            .find(|line| line.starts_with("sonar_messages_total{"))
+            .map(|line| line.rsplit(' ').next().unwrap().to_string())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn replayed_lines_are_not_counted_or_alerted_on() {
+        let path = temp_path("watcher-replay");
+        let transcript = path.with_file_name("projects").join("-work-app").join("s1.jsonl");
+        std::fs::create_dir_all(transcript.parent().unwrap()).unwrap();
+
+        // Written before the app started: the same step three times.
+        let old: String = (1..=3).map(|i| assistant_line(&format!("old{i}"), "2026-02-02T10:00:00.000Z")).collect();
+        std::fs::write(&transcript, old).unwrap();
+
+        let watcher = SessionWatcher::new(Arc::new(Database::open(&path).unwrap()));
+        watcher.watch_unregistered("a1");
+        let events = Events::default();
+        let changed = || process_changes(&transcript, &watcher.tracked, &watcher.agent_dirs, &watcher.state, &events).unwrap();
+
+        changed();
+        assert_eq!(messages_total(&watcher.metrics()), "");
+        assert!(!events.0.lock().unwrap().iter().any(|e| e == "loop-detected"));
+        // Totals still include them.
+        assert!(events.0.lock().unwrap().iter().any(|e| e == "session-update"));
+
+        let now = calendar::format_utc(Utc::now());
+        let mut file = std::fs::OpenOptions::new().append(true).open(&transcript).unwrap();
+        for i in 1..=3 {
+            file.write_all(assistant_line(&format!("new{i}"), &now).as_bytes()).unwrap();
+        }
+        changed();
+        assert_eq!(messages_total(&watcher.metrics()), "3");
+        let loops = events.0.lock().unwrap().iter().filter(|e| *e == "loop-detected").count();
+        assert_eq!(loops, 1);
+    }
+
+    #[test]
+    fn lines_pruned_by_retention_are_not_stored_again() {
+        let path = temp_path("watcher-retention");
+        let transcript = path.with_file_name("projects").join("-work-app").join("s1.jsonl");
+        std::fs::create_dir_all(transcript.parent().unwrap()).unwrap();
+        let old = calendar::format_utc(Utc::now() - chrono::Duration::days(10));
+        let now = calendar::format_utc(Utc::now());
+        std::fs::write(&transcript, assistant_line("old", &old) + &assistant_line("new", &now)).unwrap();
+
+        let database = Arc::new(Database::open(&path).unwrap());
+        let tokens = || database.get_daily_costs(30).unwrap().iter().map(|day| day.tokens).sum::<u64>();
+        let ingest = |watcher: &SessionWatcher| {
+            watcher.watch_unregistered("a1");
+            process_changes(&transcript, &watcher.tracked, &watcher.agent_dirs, &watcher.state, &Events::default()).unwrap();
+        };
+
+        ingest(&SessionWatcher::new(database.clone()));
+        assert_eq!(tokens(), 60);
+
+        let policy = RetentionPolicy { retention_days: 7, preview_retention_days: None };
+        database.set_retention_policy(&policy).unwrap();
+        assert_eq!(database.apply_retention(&policy).unwrap().messages_deleted, 1);
+
+        // After a restart the whole transcript is read again.
+        ingest(&SessionWatcher::new(database.clone()));
+        assert_eq!(database.get_session_messages("s1").unwrap().len(), 1);
+        assert_eq!(tokens(), 60);
+    }
+}
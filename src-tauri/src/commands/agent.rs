@@ -1,5 +1,5 @@
 use crate::collector::watcher::SessionWatcher;
-use crate::pty::manager::PtyManager;
+use crate::pty::manager::{AgentOptions, PtyManager};
 use std::sync::Arc;
 use tauri::{AppHandle, State};
 
@@ -12,18 +12,26 @@ pub fn spawn_shell(
     pty_manager: State<'_, Arc<PtyManager>>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
-    pty_manager.spawn_shell(&agent_id, &working_dir, rows, cols, app_handle)
+    pty_manager.spawn_shell(&agent_id, &working_dir, rows, cols, Arc::new(app_handle))
 }
 
+/// Without `options` the agent starts in Claude Code's default permission
+/// mode, asking before edits and commands.
 #[tauri::command]
 pub fn spawn_agent(
     agent_id: String,
     task: String,
     working_dir: String,
+    options: Option<AgentOptions>,
     pty_manager: State<'_, Arc<PtyManager>>,
+    watcher: State<'_, Arc<SessionWatcher>>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
-    pty_manager.spawn_agent(&agent_id, &task, &working_dir, app_handle)
+    let options = options.unwrap_or_default();
+    pty_manager.spawn_agent(&agent_id, &task, &working_dir, &options, Arc::new(app_handle))?;
+    watcher.register_agent(&agent_id, &working_dir);
+    watcher.set_permission_mode(&agent_id, options.permission_mode);
+    Ok(())
 }
 
 #[tauri::command]
@@ -0,0 +1,39 @@
+use crate::api::{new_token, ApiServer, ApiSettings};
+use crate::db::Database;
+use crate::pty::manager::PtyManager;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+#[tauri::command]
+pub fn get_api_settings(
+    db: State<'_, Arc<Database>>,
+) -> Result<ApiSettings, String> {
+    ApiSettings::load(&db)
+}
+
+/// Saves the settings and restarts the server with them, or stops it.
+#[tauri::command]
+pub fn set_api_settings(
+    settings: ApiSettings,
+    db: State<'_, Arc<Database>>,
+    pty_manager: State<'_, Arc<PtyManager>>,
+    api: State<'_, ApiServer>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    settings.save(&db)?;
+    api.apply(&settings, app_handle, db.inner().clone(), pty_manager.inner().clone())
+}
+
+/// Replaces the token, disconnecting every client that used the old one.
+#[tauri::command]
+pub fn rotate_api_token(
+    db: State<'_, Arc<Database>>,
+    pty_manager: State<'_, Arc<PtyManager>>,
+    api: State<'_, ApiServer>,
+    app_handle: AppHandle,
+) -> Result<ApiSettings, String> {
+    let settings = ApiSettings { token: new_token(), ..ApiSettings::load(&db)? };
+    settings.save(&db)?;
+    api.apply(&settings, app_handle, db.inner().clone(), pty_manager.inner().clone())?;
+    Ok(settings)
+}
@@ -0,0 +1,53 @@
+use crate::db::policy::PolicyDecisionRecord;
+use crate::db::Database;
+use crate::hooks::install;
+use crate::hooks::policy::PolicySettings;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::State;
+
+/// Installs the hooks in `project_dir`, running `helper_path` or else the
+/// helper shipped next to the app. Returns the settings file written.
+#[tauri::command]
+pub fn install_hooks(
+    project_dir: String,
+    helper_path: Option<String>,
+) -> Result<String, String> {
+    let helper = match helper_path {
+        Some(path) => PathBuf::from(path),
+        None => install::default_helper_path()?,
+    };
+    let path = install::install_hooks(Path::new(&project_dir), &helper)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub fn uninstall_hooks(project_dir: String) -> Result<bool, String> {
+    install::uninstall_hooks(Path::new(&project_dir))
+}
+
+#[tauri::command]
+pub fn hooks_installed(project_dir: String) -> Result<bool, String> {
+    install::hooks_installed(Path::new(&project_dir))
+}
+
+#[tauri::command]
+pub fn get_tool_policy(db: State<'_, Arc<Database>>) -> Result<PolicySettings, String> {
+    PolicySettings::load(&db)
+}
+
+/// Takes effect on the next tool call; agents must have the hooks installed.
+#[tauri::command]
+pub fn set_tool_policy(db: State<'_, Arc<Database>>, settings: PolicySettings) -> Result<(), String> {
+    settings.save(&db)
+}
+
+/// Latest policy decisions, optionally for one session.
+#[tauri::command]
+pub fn get_policy_decisions(
+    db: State<'_, Arc<Database>>,
+    limit: Option<u32>,
+    session_id: Option<String>,
+) -> Result<Vec<PolicyDecisionRecord>, String> {
+    db.get_policy_decisions(limit.unwrap_or(100), session_id.as_deref())
+}
@@ -1,2 +1,4 @@
 pub mod agent;
+pub mod api;
+pub mod hooks;
 pub mod stats;
@@ -1,9 +1,22 @@
-use crate::collector::cost::calculate_cost;
-use crate::collector::parser::parse_jsonl_line;
-use crate::collector::watcher::get_claude_projects_dir;
+use crate::collector::energy::EnergySettings;
+use crate::collector::import;
+use crate::collector::otlp::{self, OtlpReport, OtlpSettings};
+use crate::collector::transcript::{self, TranscriptFormat, TranscriptOptions};
+use crate::db::anomaly::AnomalyAlert;
+use crate::db::blocks::{CurrentBlock, UsageBlocksReport};
+use crate::db::breakdown::BreakdownStats;
+use crate::db::cache::CacheStats;
+use crate::db::calendar::Bucket;
+use crate::db::columnar::{DatasetFile, DatasetFormat};
+use crate::db::context::SessionContext;
+use crate::db::export::{ExportFormat, ExportReport};
+use crate::db::forecast::SpendForecast;
+use crate::db::query::{SessionPage, SessionQuery};
+use crate::db::retention::{RetentionPolicy, RetentionReport};
+use crate::db::search::{SearchHit, SearchPrivacy};
+use crate::db::tools::ToolStatsReport;
 use crate::db::{DailyCost, Database, MessageRecord, ModelStats, SessionRecord};
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::path::Path;
 use std::sync::Arc;
 use tauri::State;
 
@@ -15,6 +28,80 @@ pub fn get_recent_sessions(
     db.get_recent_sessions(limit)
 }
 
+#[tauri::command]
+pub fn query_sessions(
+    query: SessionQuery,
+    db: State<'_, Arc<Database>>,
+) -> Result<SessionPage, String> {
+    db.query_sessions(&query)
+}
+
+#[tauri::command]
+pub fn export_sessions(
+    query: SessionQuery,
+    format: ExportFormat,
+    path: String,
+    db: State<'_, Arc<Database>>,
+) -> Result<ExportReport, String> {
+    db.export_sessions(&query, format, Path::new(&path))
+}
+
+#[tauri::command]
+pub fn export_messages(
+    query: SessionQuery,
+    format: ExportFormat,
+    path: String,
+    db: State<'_, Arc<Database>>,
+) -> Result<ExportReport, String> {
+    db.export_messages(&query, format, Path::new(&path))
+}
+
+#[tauri::command]
+pub fn export_dataset(
+    query: SessionQuery,
+    format: DatasetFormat,
+    dir: String,
+    db: State<'_, Arc<Database>>,
+) -> Result<Vec<DatasetFile>, String> {
+    db.export_dataset(&query, format, Path::new(&dir))
+}
+
+#[tauri::command]
+pub fn export_transcript(
+    session_id: String,
+    format: TranscriptFormat,
+    options: Option<TranscriptOptions>,
+    path: String,
+    db: State<'_, Arc<Database>>,
+) -> Result<ExportReport, String> {
+    transcript::export_transcript(&db, &session_id, format, &options.unwrap_or_default(), Path::new(&path))
+}
+
+#[tauri::command]
+pub fn get_otlp_settings(
+    db: State<'_, Arc<Database>>,
+) -> Result<OtlpSettings, String> {
+    OtlpSettings::load(&db)
+}
+
+#[tauri::command]
+pub fn set_otlp_settings(
+    settings: OtlpSettings,
+    db: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    settings.save(&db)
+}
+
+/// Sends the matching sessions to the configured collector now, whether or
+/// not the background export is on.
+#[tauri::command]
+pub fn export_otlp(
+    query: SessionQuery,
+    db: State<'_, Arc<Database>>,
+) -> Result<OtlpReport, String> {
+    otlp::export_query(&db, &OtlpSettings::load(&db)?, &query)
+}
+
 #[tauri::command]
 pub fn get_session_messages(
     session_id: String,
@@ -23,6 +110,14 @@ pub fn get_session_messages(
     db.get_session_messages(&session_id)
 }
 
+#[tauri::command]
+pub fn get_session_context(
+    session_id: String,
+    db: State<'_, Arc<Database>>,
+) -> Result<SessionContext, String> {
+    db.get_session_context(&session_id)
+}
+
 #[tauri::command]
 pub fn get_daily_costs(
     days: u32,
@@ -31,6 +126,15 @@ pub fn get_daily_costs(
     db.get_daily_costs(days)
 }
 
+#[tauri::command]
+pub fn get_cost_buckets(
+    bucket: Bucket,
+    periods: u32,
+    db: State<'_, Arc<Database>>,
+) -> Result<Vec<DailyCost>, String> {
+    db.get_cost_buckets(bucket, periods)
+}
+
 #[tauri::command]
 pub fn get_model_stats(
     db: State<'_, Arc<Database>>,
@@ -38,6 +142,73 @@ pub fn get_model_stats(
     db.get_model_stats()
 }
 
+#[tauri::command]
+pub fn get_project_stats(
+    from: String,
+    to: String,
+    db: State<'_, Arc<Database>>,
+) -> Result<Vec<BreakdownStats>, String> {
+    db.get_project_stats(&from, &to)
+}
+
+#[tauri::command]
+pub fn get_branch_stats(
+    from: String,
+    to: String,
+    db: State<'_, Arc<Database>>,
+) -> Result<Vec<BreakdownStats>, String> {
+    db.get_branch_stats(&from, &to)
+}
+
+#[tauri::command]
+pub fn get_tool_stats(
+    from: Option<String>,
+    to: Option<String>,
+    db: State<'_, Arc<Database>>,
+) -> Result<ToolStatsReport, String> {
+    db.get_tool_stats(from.as_deref(), to.as_deref())
+}
+
+#[tauri::command]
+pub fn get_cache_stats(
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<u32>,
+    db: State<'_, Arc<Database>>,
+) -> Result<CacheStats, String> {
+    db.get_cache_stats(from.as_deref(), to.as_deref(), limit)
+}
+
+#[tauri::command]
+pub fn get_usage_blocks(
+    from: Option<String>,
+    db: State<'_, Arc<Database>>,
+) -> Result<UsageBlocksReport, String> {
+    db.get_usage_blocks(from.as_deref())
+}
+
+#[tauri::command]
+pub fn get_current_block(
+    db: State<'_, Arc<Database>>,
+) -> Result<Option<CurrentBlock>, String> {
+    db.get_current_block()
+}
+
+#[tauri::command]
+pub fn get_block_token_limit(
+    db: State<'_, Arc<Database>>,
+) -> Result<Option<u64>, String> {
+    db.get_block_token_limit()
+}
+
+#[tauri::command]
+pub fn set_block_token_limit(
+    limit: Option<u64>,
+    db: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    db.set_block_token_limit(limit)
+}
+
 #[tauri::command]
 pub fn get_total_cost_today(
     db: State<'_, Arc<Database>>,
@@ -45,6 +216,28 @@ pub fn get_total_cost_today(
     db.get_total_cost_today()
 }
 
+#[tauri::command]
+pub fn get_spend_forecast(
+    db: State<'_, Arc<Database>>,
+) -> Result<SpendForecast, String> {
+    db.get_spend_forecast()
+}
+
+#[tauri::command]
+pub fn get_timezone(
+    db: State<'_, Arc<Database>>,
+) -> Result<String, String> {
+    db.get_timezone().map(|tz| tz.name().to_string())
+}
+
+#[tauri::command]
+pub fn set_timezone(
+    timezone: String,
+    db: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    db.set_timezone(&timezone)
+}
+
 #[tauri::command]
 pub fn save_session(
     record: SessionRecord,
@@ -53,152 +246,79 @@ pub fn save_session(
     db.upsert_session(&record)
 }
 
+#[tauri::command]
+pub fn get_retention_policy(
+    db: State<'_, Arc<Database>>,
+) -> Result<RetentionPolicy, String> {
+    db.get_retention_policy()
+}
+
+#[tauri::command]
+pub fn set_retention_policy(
+    policy: RetentionPolicy,
+    db: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    db.set_retention_policy(&policy)
+}
+
+#[tauri::command]
+pub fn run_retention(
+    db: State<'_, Arc<Database>>,
+) -> Result<RetentionReport, String> {
+    let policy = db.get_retention_policy()?;
+    db.apply_retention(&policy)
+}
+
+#[tauri::command]
+pub fn get_anomalies(
+    limit: Option<u32>,
+    db: State<'_, Arc<Database>>,
+) -> Result<Vec<AnomalyAlert>, String> {
+    db.get_anomalies(limit.unwrap_or(50))
+}
+
+#[tauri::command]
+pub fn get_energy_settings(
+    db: State<'_, Arc<Database>>,
+) -> Result<EnergySettings, String> {
+    db.get_energy_settings()
+}
+
+#[tauri::command]
+pub fn set_energy_settings(
+    settings: EnergySettings,
+    db: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    db.set_energy_settings(&settings)
+}
+
+#[tauri::command]
+pub fn search_transcripts(
+    query: String,
+    limit: Option<u32>,
+    db: State<'_, Arc<Database>>,
+) -> Result<Vec<SearchHit>, String> {
+    db.search_transcripts(&query, limit)
+}
+
+#[tauri::command]
+pub fn get_search_privacy(
+    db: State<'_, Arc<Database>>,
+) -> Result<SearchPrivacy, String> {
+    db.get_search_privacy()
+}
+
+#[tauri::command]
+pub fn set_search_privacy(
+    privacy: SearchPrivacy,
+    db: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    db.set_search_privacy(&privacy)
+}
+
 #[tauri::command]
 pub fn import_history(
     db: State<'_, Arc<Database>>,
 ) -> Result<u32, String> {
-    let projects_dir = get_claude_projects_dir()
-        .ok_or_else(|| "No ~/.claude/projects directory found".to_string())?;
-
-    let mut imported = 0u32;
-
-    for project_entry in std::fs::read_dir(&projects_dir).map_err(|e| e.to_string())? {
-        let project_entry = project_entry.map_err(|e| e.to_string())?;
-        if !project_entry.file_type().map_err(|e| e.to_string())?.is_dir() {
-            continue;
-        }
-
-        let project_name = project_entry
-            .file_name()
-            .to_string_lossy()
-            .replace('-', "/");
-
-        for file_entry in std::fs::read_dir(project_entry.path()).map_err(|e| e.to_string())? {
-            let file_entry = file_entry.map_err(|e| e.to_string())?;
-            let path = file_entry.path();
-
-            if path.extension().map(|e| e == "jsonl").unwrap_or(false) {
-                let session_id = path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("")
-                    .to_string();
-
-                if session_id.is_empty() || session_id.starts_with("agent-") {
-                    continue;
-                }
-
-                if db.session_exists(&session_id).unwrap_or(true) {
-                    continue;
-                }
-
-                if let Ok(record) = parse_session_file(&path, &session_id, &project_name, &db) {
-                    if let Ok(()) = db.upsert_session(&record) {
-                        imported += 1;
-                    }
-                }
-            }
-        }
-    }
-
-    Ok(imported)
-}
-
-fn parse_session_file(
-    path: &std::path::Path,
-    session_id: &str,
-    project: &str,
-    db: &Arc<Database>,
-) -> Result<SessionRecord, String> {
-    let file = File::open(path).map_err(|e| e.to_string())?;
-    let reader = BufReader::new(file);
-
-    let mut total_cost = 0.0f64;
-    let mut total_tokens = 0u64;
-    let mut input_tokens = 0u64;
-    let mut output_tokens = 0u64;
-    let mut cache_read = 0u64;
-    let mut cache_creation = 0u64;
-    let mut tool_calls = 0u32;
-    let mut last_model = String::from("unknown");
-    let mut first_ts = String::new();
-    let mut last_ts = String::new();
-    let mut msg_count = 0u32;
-
-    for line in reader.lines() {
-        let line = line.map_err(|e| e.to_string())?;
-        if let Some(parsed) = parse_jsonl_line(line.trim()) {
-            if !parsed.timestamp.is_empty() {
-                if first_ts.is_empty() {
-                    first_ts = parsed.timestamp.clone();
-                }
-                last_ts = parsed.timestamp.clone();
-            }
-
-            if let Some(ref model) = parsed.model {
-                last_model = model.clone();
-            }
-
-            if let Some(ref usage) = parsed.usage {
-                input_tokens += usage.input_tokens;
-                output_tokens += usage.output_tokens;
-                cache_read += usage.cache_read_input_tokens;
-                cache_creation += usage.cache_creation_input_tokens;
-                total_tokens += usage.input_tokens + usage.output_tokens
-                    + usage.cache_read_input_tokens + usage.cache_creation_input_tokens;
-
-                if last_model != "unknown" {
-                    let msg_cost = calculate_cost(usage, &last_model);
-                    total_cost += msg_cost;
-
-                    let _ = db.insert_message(&MessageRecord {
-                        id: 0,
-                        session_id: session_id.to_string(),
-                        message_type: parsed.message_type.clone(),
-                        timestamp: parsed.timestamp.clone(),
-                        model: Some(last_model.clone()),
-                        input_tokens: usage.input_tokens,
-                        output_tokens: usage.output_tokens,
-                        cache_read_tokens: usage.cache_read_input_tokens,
-                        cache_creation_tokens: usage.cache_creation_input_tokens,
-                        cost: msg_cost,
-                        content_preview: parsed.content_text.as_ref().map(|t| t.chars().take(200).collect()),
-                        tool_name: parsed.tool_calls.first().cloned(),
-                    });
-                }
-            }
-
-            tool_calls += parsed.tool_calls.len() as u32;
-            msg_count += 1;
-        }
-    }
-
-    let duration_ms = if !first_ts.is_empty() && !last_ts.is_empty() {
-        let start = chrono::DateTime::parse_from_rfc3339(&first_ts).ok();
-        let end = chrono::DateTime::parse_from_rfc3339(&last_ts).ok();
-        match (start, end) {
-            (Some(s), Some(e)) => (e - s).num_milliseconds().max(0) as u64,
-            _ => 0,
-        }
-    } else {
-        0
-    };
-
-    Ok(SessionRecord {
-        id: session_id.to_string(),
-        agent_id: "imported".to_string(),
-        project: project.to_string(),
-        model: last_model,
-        total_cost,
-        total_tokens,
-        input_tokens,
-        output_tokens,
-        cache_read_tokens: cache_read,
-        cache_creation_tokens: cache_creation,
-        duration_ms,
-        status: "completed".to_string(),
-        tool_calls,
-        started_at: first_ts,
-        ended_at: Some(last_ts),
-    })
+    import::import_history(&db)
 }
@@ -0,0 +1,130 @@
+use chrono::NaiveDate;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use super::{calendar, Database};
+
+const LAST_CHECKED_DAY_KEY: &str = "anomaly_last_checked_day";
+
+/// A session or day whose usage was far outside its project's baseline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnomalyAlert {
+    pub id: i64,
+    /// `session` for live sessions, `day` for completed days.
+    pub kind: String,
+    pub project: String,
+    /// Session id, or the local date for day alerts.
+    pub subject: String,
+    /// `session_cost`, `message_tokens` or `daily_cost`.
+    pub metric: String,
+    pub value: f64,
+    pub baseline_median: f64,
+    pub baseline_mad: f64,
+    pub score: f64,
+    pub reason: String,
+    pub created_at: String,
+}
+
+impl Database {
+    /// The last local day checked for daily cost anomalies.
+    pub fn get_last_checked_day(&self) -> Result<Option<NaiveDate>, String> {
+        Ok(self
+            .get_setting(LAST_CHECKED_DAY_KEY)?
+            .and_then(|v| NaiveDate::parse_from_str(&v, "%Y-%m-%d").ok()))
+    }
+
+    pub fn set_last_checked_day(&self, day: NaiveDate) -> Result<(), String> {
+        self.set_setting(LAST_CHECKED_DAY_KEY, &day.format("%Y-%m-%d").to_string())
+    }
+
+    /// Stores an alert unless the same subject was already flagged for the
+    /// metric. Returns the new row's id.
+    pub fn insert_anomaly(&self, a: &AnomalyAlert) -> Result<Option<i64>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let inserted = conn.execute(
+            "INSERT INTO anomaly_alerts (kind, project, subject, metric, value,
+             baseline_median, baseline_mad, score, reason, created_at)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)
+             ON CONFLICT(kind, project, subject, metric) DO NOTHING",
+            params![a.kind, a.project, a.subject, a.metric, a.value,
+                    a.baseline_median, a.baseline_mad, a.score, a.reason, a.created_at],
+        ).map_err(|e| e.to_string())?;
+        Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+    }
+
+    /// Most recent alerts first.
+    pub fn get_anomalies(&self, limit: u32) -> Result<Vec<AnomalyAlert>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, project, subject, metric, value, baseline_median,
+             baseline_mad, score, reason, created_at
+             FROM anomaly_alerts ORDER BY created_at DESC, id DESC LIMIT ?1"
+        ).map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok(AnomalyAlert {
+                id: row.get(0)?, kind: row.get(1)?, project: row.get(2)?,
+                subject: row.get(3)?, metric: row.get(4)?, value: row.get(5)?,
+                baseline_median: row.get(6)?, baseline_mad: row.get(7)?,
+                score: row.get(8)?, reason: row.get(9)?, created_at: row.get(10)?,
+            })
+        }).map_err(|e| e.to_string())?;
+
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Id and total cost of the project's sessions started since `since`.
+    pub fn project_session_costs(&self, project: &str, since: &str) -> Result<Vec<(String, f64)>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT id, total_cost FROM sessions WHERE project = ?1 AND started_at >= ?2")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![project, since], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Tokens of up to `limit` of the project's most recent messages since `since`.
+    pub fn project_message_tokens(&self, project: &str, since: &str, limit: u32) -> Result<Vec<f64>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(
+            "SELECT m.input_tokens + m.output_tokens + m.cache_read_tokens + m.cache_creation_tokens
+             FROM messages m JOIN sessions s ON s.id = m.session_id
+             WHERE s.project = ?1 AND m.timestamp >= ?2
+             ORDER BY m.timestamp DESC LIMIT ?3"
+        ).map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![project, since, limit], |row| row.get::<_, f64>(0))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Cost per project and local day for days with any usage in `[since, until)`.
+    pub fn project_daily_costs(
+        &self,
+        since: &str,
+        until: &str,
+    ) -> Result<HashMap<String, BTreeMap<NaiveDate, f64>>, String> {
+        let tz = self.get_timezone()?;
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(
+            "SELECT project, hour, SUM(cost) FROM usage_hourly
+             WHERE hour >= ?1 AND hour < ?2 GROUP BY project, hour"
+        ).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(params![since, until], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, f64>(2)?))
+        }).map_err(|e| e.to_string())?;
+
+        let mut days: HashMap<String, BTreeMap<NaiveDate, f64>> = HashMap::new();
+        for row in rows {
+            let (project, hour, cost) = row.map_err(|e| e.to_string())?;
+            if let Some(ts) = calendar::parse_utc(&hour) {
+                let date = calendar::bucket_start(ts, tz, calendar::Bucket::Day);
+                *days.entry(project).or_default().entry(date).or_insert(0.0) += cost;
+            }
+        }
+        Ok(days)
+    }
+}
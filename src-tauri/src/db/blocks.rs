@@ -0,0 +1,318 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::calendar::{self, Bucket};
+use super::Database;
+
+const BLOCK_TOKEN_LIMIT_KEY: &str = "block_token_limit";
+const DEFAULT_BLOCK_DAYS: i64 = 28;
+
+/// Length of a subscription usage window.
+pub fn block_length() -> Duration {
+    Duration::hours(5)
+}
+
+/// A 5-hour usage window. A block opens with the first message sent after the
+/// previous block closed and covers every message in the five hours after it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageBlock {
+    pub start: String,
+    pub end: String,
+    pub last_message_at: String,
+    pub messages: u32,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+    /// The block has not closed yet.
+    pub active: bool,
+}
+
+/// Usage per ISO week in the configured timezone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageWeek {
+    /// Local date of the week's Monday.
+    pub week_start: String,
+    /// Blocks that opened during the week.
+    pub blocks: u32,
+    pub total_tokens: u64,
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageBlocksReport {
+    pub blocks: Vec<UsageBlock>,
+    pub weeks: Vec<UsageWeek>,
+}
+
+/// The open block with its burn rate and where that rate leads by the time
+/// the block closes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrentBlock {
+    pub block: UsageBlock,
+    /// Averaged since the block opened.
+    pub tokens_per_minute: f64,
+    pub cost_per_hour: f64,
+    pub minutes_remaining: i64,
+    pub projected_tokens: u64,
+    pub projected_cost: f64,
+    pub token_limit: Option<u64>,
+    pub limit_reached: bool,
+    /// When the limit is hit at the current rate; `None` when it is already
+    /// reached, no limit is set, or the block closes first.
+    pub projected_limit_at: Option<String>,
+}
+
+struct BlockMessage {
+    timestamp: DateTime<Utc>,
+    input_tokens: u64,
+    output_tokens: u64,
+    cache_read_tokens: u64,
+    cache_creation_tokens: u64,
+    cost: f64,
+}
+
+impl BlockMessage {
+    fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_read_tokens + self.cache_creation_tokens
+    }
+}
+
+impl Database {
+    pub fn get_block_token_limit(&self) -> Result<Option<u64>, String> {
+        Ok(self
+            .get_setting(BLOCK_TOKEN_LIMIT_KEY)?
+            .and_then(|v| v.parse().ok()))
+    }
+
+    /// Sets the token budget of a 5-hour block; `None` clears it.
+    pub fn set_block_token_limit(&self, limit: Option<u64>) -> Result<(), String> {
+        match limit {
+            Some(limit) => self.set_setting(BLOCK_TOKEN_LIMIT_KEY, &limit.to_string()),
+            None => self.set_setting(BLOCK_TOKEN_LIMIT_KEY, ""),
+        }
+    }
+
+    /// Blocks overlapping `[from, now]` (four weeks when `from` is omitted)
+    /// and the weekly totals of their messages, oldest first.
+    pub fn get_usage_blocks(&self, from: Option<&str>) -> Result<UsageBlocksReport, String> {
+        let tz = self.get_timezone()?;
+        let now = Utc::now();
+        let from = match from {
+            Some(value) => calendar::parse_bound(value, tz).ok_or_else(|| format!("Invalid date: {}", value))?,
+            None => now - Duration::days(DEFAULT_BLOCK_DAYS),
+        };
+
+        let messages = self.block_messages(from)?;
+        let blocks: Vec<UsageBlock> = group_blocks(&messages, now)
+            .into_iter()
+            .filter(|b| b.end.as_str() > calendar::format_utc(from).as_str())
+            .collect();
+
+        let mut weeks: BTreeMap<NaiveDate, UsageWeek> = BTreeMap::new();
+        for m in messages.iter().filter(|m| m.timestamp >= from) {
+            let week = week_entry(&mut weeks, calendar::bucket_start(m.timestamp, tz, Bucket::Week));
+            week.total_tokens += m.total_tokens();
+            week.cost += m.cost;
+        }
+        for block in &blocks {
+            if let Some(start) = calendar::parse_utc(&block.start).filter(|s| *s >= from) {
+                week_entry(&mut weeks, calendar::bucket_start(start, tz, Bucket::Week)).blocks += 1;
+            }
+        }
+
+        Ok(UsageBlocksReport { blocks, weeks: weeks.into_values().collect() })
+    }
+
+    /// The block that is open right now, or `None` after five idle hours.
+    pub fn get_current_block(&self) -> Result<Option<CurrentBlock>, String> {
+        self.get_current_block_since(None)
+    }
+
+    /// Like `get_current_block`, but reads messages from `known_start`, the
+    /// start of a block reported earlier, instead of searching back for where
+    /// the current block began. Blocks follow each other from any block start,
+    /// so this holds as long as no older messages arrive in the meantime.
+    pub fn get_current_block_since(&self, known_start: Option<&str>) -> Result<Option<CurrentBlock>, String> {
+        let now = Utc::now();
+        let messages = match known_start.and_then(calendar::parse_utc).filter(|start| *start <= now) {
+            Some(start) => {
+                let conn = self.conn.lock().map_err(|e| e.to_string())?;
+                messages_since(&conn, start)?
+            }
+            None => self.block_messages(now - block_length())?,
+        };
+        let block = match group_blocks(&messages, now).pop().filter(|b| b.active) {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+        let token_limit = self.get_block_token_limit()?;
+        Ok(Some(project(block, now, token_limit)))
+    }
+
+    /// Messages from the start of the block containing `from` onwards. Walks
+    /// back from `from` to the last gap of five hours or more, since the
+    /// message right after such a gap always opens a block.
+    fn block_messages(&self, from: DateTime<Utc>) -> Result<Vec<BlockMessage>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+
+        let mut anchor = from;
+        let mut stmt = conn
+            .prepare("SELECT timestamp FROM messages WHERE timestamp < ?1 ORDER BY timestamp DESC")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![calendar::format_utc(from)], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?;
+        for row in rows {
+            let Some(ts) = calendar::parse_utc(&row.map_err(|e| e.to_string())?) else {
+                continue;
+            };
+            if anchor - ts >= block_length() {
+                break;
+            }
+            anchor = ts;
+        }
+        messages_since(&conn, anchor)
+    }
+}
+
+fn messages_since(conn: &rusqlite::Connection, anchor: DateTime<Utc>) -> Result<Vec<BlockMessage>, String> {
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, input_tokens, output_tokens, cache_read_tokens,
+         cache_creation_tokens, cost
+         FROM messages WHERE timestamp >= ?1 ORDER BY timestamp ASC"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(params![calendar::format_utc(anchor)], |row| {
+        let Some(timestamp) = calendar::parse_utc(&row.get::<_, String>(0)?) else {
+            return Ok(None);
+        };
+        Ok(Some(BlockMessage {
+            timestamp,
+            input_tokens: row.get(1)?, output_tokens: row.get(2)?,
+            cache_read_tokens: row.get(3)?, cache_creation_tokens: row.get(4)?,
+            cost: row.get(5)?,
+        }))
+    }).map_err(|e| e.to_string())?;
+
+    let mut messages = Vec::new();
+    for row in rows {
+        messages.extend(row.map_err(|e| e.to_string())?);
+    }
+    Ok(messages)
+}
+
+fn group_blocks(messages: &[BlockMessage], now: DateTime<Utc>) -> Vec<UsageBlock> {
+    let mut blocks: Vec<(DateTime<Utc>, UsageBlock)> = Vec::new();
+    for m in messages {
+        let open = blocks.last().map(|(start, _)| m.timestamp < *start + block_length());
+        if open != Some(true) {
+            let end = m.timestamp + block_length();
+            blocks.push((m.timestamp, UsageBlock {
+                start: calendar::format_utc(m.timestamp),
+                end: calendar::format_utc(end),
+                active: now < end,
+                ..Default::default()
+            }));
+        }
+        if let Some((_, block)) = blocks.last_mut() {
+            block.last_message_at = calendar::format_utc(m.timestamp);
+            block.messages += 1;
+            block.input_tokens += m.input_tokens;
+            block.output_tokens += m.output_tokens;
+            block.cache_read_tokens += m.cache_read_tokens;
+            block.cache_creation_tokens += m.cache_creation_tokens;
+            block.total_tokens += m.total_tokens();
+            block.cost += m.cost;
+        }
+    }
+    blocks.into_iter().map(|(_, block)| block).collect()
+}
+
+fn project(block: UsageBlock, now: DateTime<Utc>, token_limit: Option<u64>) -> CurrentBlock {
+    let start = calendar::parse_utc(&block.start).unwrap_or(now);
+    let end = calendar::parse_utc(&block.end).unwrap_or(now);
+    // At least a minute, so the first message doesn't read as an enormous rate.
+    let elapsed_minutes = ((now - start).num_seconds() as f64 / 60.0).max(1.0);
+    let remaining = (end - now).max(Duration::zero());
+    let remaining_minutes = remaining.num_seconds() as f64 / 60.0;
+
+    let tokens_per_minute = block.total_tokens as f64 / elapsed_minutes;
+    let cost_per_minute = block.cost / elapsed_minutes;
+    let limit_reached = token_limit.map(|l| block.total_tokens >= l).unwrap_or(false);
+    let projected_limit_at = token_limit
+        .filter(|_| !limit_reached && tokens_per_minute > 0.0)
+        .map(|l| (l - block.total_tokens) as f64 / tokens_per_minute)
+        .filter(|minutes| *minutes <= remaining_minutes)
+        .map(|minutes| calendar::format_utc(now + Duration::seconds((minutes * 60.0) as i64)));
+
+    CurrentBlock {
+        tokens_per_minute,
+        cost_per_hour: cost_per_minute * 60.0,
+        minutes_remaining: remaining.num_minutes(),
+        projected_tokens: block.total_tokens + (tokens_per_minute * remaining_minutes) as u64,
+        projected_cost: block.cost + cost_per_minute * remaining_minutes,
+        token_limit,
+        limit_reached,
+        projected_limit_at,
+        block,
+    }
+}
+
+fn week_entry(weeks: &mut BTreeMap<NaiveDate, UsageWeek>, date: NaiveDate) -> &mut UsageWeek {
+    weeks.entry(date).or_insert_with(|| UsageWeek {
+        week_start: date.format("%Y-%m-%d").to_string(),
+        blocks: 0,
+        total_tokens: 0,
+        cost: 0.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::{message, session, temp_db};
+
+    fn at(now: DateTime<Utc>, minutes_ago: i64) -> String {
+        calendar::format_utc(now - Duration::minutes(minutes_ago))
+    }
+
+    #[test]
+    fn current_block_from_a_known_start() {
+        let db = temp_db("blocks-known-start");
+        let now = Utc::now();
+        db.upsert_session(&session("s1", &at(now, 30 * 60))).unwrap();
+        // A message every hour for a day: one block after another, no gap.
+        for hours_ago in (1..=24).rev() {
+            let key = format!("m{}", hours_ago);
+            db.insert_message(&message("s1", Some(&key), &at(now, hours_ago * 60), 1.0)).unwrap();
+        }
+        // An older block, then the five idle hours before the chain began.
+        db.insert_message(&message("s1", Some("old"), &at(now, 30 * 60), 1.0)).unwrap();
+
+        let searched = db.get_current_block().unwrap().unwrap();
+        // Blocks opened 24, 19, 14, 9 and 4 hours ago.
+        assert_eq!(searched.block.start, at(now, 4 * 60));
+        assert_eq!(searched.block.messages, 4);
+
+        for known in [at(now, 24 * 60), at(now, 9 * 60), at(now, 4 * 60)] {
+            let block = db.get_current_block_since(Some(&known)).unwrap().unwrap();
+            assert_eq!(block.block.start, searched.block.start);
+            assert_eq!(block.block.messages, searched.block.messages);
+        }
+    }
+
+    #[test]
+    fn no_current_block_after_five_idle_hours() {
+        let db = temp_db("blocks-idle");
+        let now = Utc::now();
+        db.upsert_session(&session("s1", &at(now, 8 * 60))).unwrap();
+        db.insert_message(&message("s1", Some("m1"), &at(now, 8 * 60), 1.0)).unwrap();
+
+        assert!(db.get_current_block().unwrap().is_none());
+        assert!(db.get_current_block_since(Some(&at(now, 8 * 60))).unwrap().is_none());
+    }
+}
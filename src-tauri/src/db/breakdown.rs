@@ -0,0 +1,142 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::{calendar, Database};
+
+/// Cost of one project (or project + branch) over a date range, next to the
+/// same figures for the equally long period right before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakdownStats {
+    /// Working directory, or the encoded project name for sessions recorded
+    /// before the working directory was tracked.
+    pub project: String,
+    pub branch: Option<String>,
+    pub total_cost: f64,
+    pub total_tokens: u64,
+    pub session_count: u32,
+    pub previous_cost: f64,
+    pub previous_tokens: u64,
+    pub previous_session_count: u32,
+    /// Relative change in cost versus the previous period; `None` when the
+    /// previous period cost nothing.
+    pub cost_change: Option<f64>,
+}
+
+impl Database {
+    /// Sessions started in `[from, to)` grouped by working directory.
+    pub fn get_project_stats(&self, from: &str, to: &str) -> Result<Vec<BreakdownStats>, String> {
+        self.breakdown(from, to, false)
+    }
+
+    /// Sessions started in `[from, to)` grouped by working directory and git branch.
+    pub fn get_branch_stats(&self, from: &str, to: &str) -> Result<Vec<BreakdownStats>, String> {
+        self.breakdown(from, to, true)
+    }
+
+    fn breakdown(&self, from: &str, to: &str, by_branch: bool) -> Result<Vec<BreakdownStats>, String> {
+        let tz = self.get_timezone()?;
+        let start = calendar::parse_bound(from, tz).ok_or_else(|| format!("Invalid date: {}", from))?;
+        let end = calendar::parse_bound(to, tz).ok_or_else(|| format!("Invalid date: {}", to))?;
+        if end <= start {
+            return Err("The end of the range must be after its start".to_string());
+        }
+        let previous_start = start - (end - start);
+
+        let branch = if by_branch { "NULLIF(git_branch, '')" } else { "NULL" };
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT COALESCE(NULLIF(cwd, ''), project) as p, {branch} as b,
+             COALESCE(SUM(CASE WHEN started_at >= ?2 THEN total_cost END), 0.0),
+             COALESCE(SUM(CASE WHEN started_at >= ?2 THEN total_tokens END), 0),
+             COUNT(CASE WHEN started_at >= ?2 THEN 1 END),
+             COALESCE(SUM(CASE WHEN started_at < ?2 THEN total_cost END), 0.0),
+             COALESCE(SUM(CASE WHEN started_at < ?2 THEN total_tokens END), 0),
+             COUNT(CASE WHEN started_at < ?2 THEN 1 END)
+             FROM sessions
+             WHERE started_at >= ?1 AND started_at < ?3
+             GROUP BY p, b
+             ORDER BY 3 DESC, 6 DESC",
+            branch = branch
+        )).map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map(
+            params![
+                calendar::format_utc(previous_start),
+                calendar::format_utc(start),
+                calendar::format_utc(end)
+            ],
+            |row| {
+                let total_cost: f64 = row.get(2)?;
+                let previous_cost: f64 = row.get(5)?;
+                Ok(BreakdownStats {
+                    project: row.get(0)?, branch: row.get(1)?,
+                    total_cost, total_tokens: row.get(3)?, session_count: row.get(4)?,
+                    previous_cost, previous_tokens: row.get(6)?, previous_session_count: row.get(7)?,
+                    cost_change: if previous_cost > 0.0 {
+                        Some((total_cost - previous_cost) / previous_cost)
+                    } else {
+                        None
+                    },
+                })
+            },
+        ).map_err(|e| e.to_string())?;
+
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_util::{session, temp_db};
+
+    fn add(db: &crate::db::Database, id: &str, started_at: &str, cwd: &str, branch: &str, cost: f64) {
+        let mut s = session(id, started_at);
+        s.cwd = Some(cwd.to_string());
+        s.git_branch = Some(branch.to_string());
+        s.total_cost = cost;
+        s.total_tokens = (cost * 1_000.0) as u64;
+        db.upsert_session(&s).unwrap();
+    }
+
+    #[test]
+    fn periods_are_compared_with_the_one_before() {
+        let db = temp_db("breakdown-trend");
+        db.set_timezone("UTC").unwrap();
+        // Current period is 2026-03-08..2026-03-15, the previous one 03-01..03-08.
+        add(&db, "api-now-1", "2026-03-09T10:00:00.000Z", "/work/api", "main", 3.0);
+        add(&db, "api-now-2", "2026-03-14T23:59:00.000Z", "/work/api", "feature", 1.0);
+        add(&db, "api-before", "2026-03-01T00:00:00.000Z", "/work/api", "main", 2.0);
+        add(&db, "web-now", "2026-03-08T00:00:00.000Z", "/work/web", "", 1.5);
+        add(&db, "web-outside", "2026-02-28T23:59:00.000Z", "/work/web", "main", 9.0);
+        add(&db, "web-after", "2026-03-15T00:00:00.000Z", "/work/web", "main", 9.0);
+        add(&db, "docs-before", "2026-03-05T00:00:00.000Z", "/work/docs", "main", 0.5);
+
+        let projects = db.get_project_stats("2026-03-08", "2026-03-15").unwrap();
+        let summary: Vec<_> = projects
+            .iter()
+            .map(|p| (p.project.as_str(), p.session_count, p.previous_session_count))
+            .collect();
+        assert_eq!(summary, [("/work/api", 2, 1), ("/work/web", 1, 0), ("/work/docs", 0, 1)]);
+        let api = &projects[0];
+        assert_eq!((api.total_cost, api.previous_cost), (4.0, 2.0));
+        assert_eq!((api.total_tokens, api.previous_tokens), (4_000, 2_000));
+        assert_eq!(api.cost_change, Some(1.0));
+        assert_eq!(projects[1].cost_change, None);
+        assert_eq!(projects[2].cost_change, Some(-1.0));
+
+        let branches = db.get_branch_stats("2026-03-08", "2026-03-15").unwrap();
+        let summary: Vec<_> = branches
+            .iter()
+            .map(|b| (b.project.as_str(), b.branch.as_deref(), b.total_cost, b.previous_cost))
+            .collect();
+        assert_eq!(summary, [
+            ("/work/api", Some("main"), 3.0, 2.0),
+            ("/work/web", None, 1.5, 0.0),
+            ("/work/api", Some("feature"), 1.0, 0.0),
+            ("/work/docs", Some("main"), 0.0, 0.5),
+        ]);
+
+        assert!(db.get_project_stats("2026-03-15", "2026-03-08").is_err());
+        assert!(db.get_project_stats("soon", "2026-03-08").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::collector::cost::get_pricing;
+
+use super::query::utc_bound;
+use super::Database;
+
+const DEFAULT_RANKING_LIMIT: u32 = 10;
+/// Sessions and projects with less input than this are too small to rank.
+const MIN_RANKED_INPUT_TOKENS: u64 = 100_000;
+
+/// Prompt caching figures for a set of sessions. `hit_ratio` is cache reads
+/// over all input (uncached + cache read + cache write). `savings` is what the
+/// cache reads would have cost as plain input, minus what they did cost;
+/// `write_premium` is what cache writes cost above plain input.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheSummary {
+    pub input_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub hit_ratio: f64,
+    pub write_cost: f64,
+    pub write_premium: f64,
+    pub savings: f64,
+    pub net_savings: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheRanking {
+    /// Session id or project, depending on the ranking.
+    pub key: String,
+    pub summary: CacheSummary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub overall: CacheSummary,
+    /// Lowest hit ratio first.
+    pub worst_sessions: Vec<CacheRanking>,
+    pub worst_projects: Vec<CacheRanking>,
+}
+
+impl CacheSummary {
+    fn add(&mut self, model: &str, input: u64, cache_read: u64, cache_creation: u64) {
+        let pricing = get_pricing(model);
+        let per_token = |price: f64| price / 1_000_000.0;
+
+        self.input_tokens += input;
+        self.cache_read_tokens += cache_read;
+        self.cache_creation_tokens += cache_creation;
+        self.write_cost += cache_creation as f64 * per_token(pricing.cache_creation_per_million);
+        self.write_premium += cache_creation as f64
+            * per_token(pricing.cache_creation_per_million - pricing.input_per_million);
+        self.savings += cache_read as f64
+            * per_token(pricing.input_per_million - pricing.cache_read_per_million);
+
+        let total_input = self.input_tokens + self.cache_read_tokens + self.cache_creation_tokens;
+        self.hit_ratio = if total_input > 0 {
+            self.cache_read_tokens as f64 / total_input as f64
+        } else {
+            0.0
+        };
+        self.net_savings = self.savings - self.write_premium;
+    }
+
+    fn total_input(&self) -> u64 {
+        self.input_tokens + self.cache_read_tokens + self.cache_creation_tokens
+    }
+}
+
+impl Database {
+    /// Cache efficiency for sessions started in `[from, to)` (either bound may
+    /// be omitted; dates are local midnight), with the `limit` worst sessions
+    /// and projects by hit ratio.
+    pub fn get_cache_stats(
+        &self,
+        from: Option<&str>,
+        to: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<CacheStats, String> {
+        let tz = self.get_timezone()?;
+        let from = from.map(|from| utc_bound(from, tz)).transpose()?;
+        let to = to.map(|to| utc_bound(to, tz)).transpose()?;
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(
+            "SELECT id, COALESCE(NULLIF(cwd, ''), project), model,
+             input_tokens, cache_read_tokens, cache_creation_tokens
+             FROM sessions
+             WHERE (?1 IS NULL OR started_at >= ?1) AND (?2 IS NULL OR started_at < ?2)"
+        ).map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map(params![from, to], |row| {
+            Ok((
+                row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?,
+                row.get::<_, u64>(3)?, row.get::<_, u64>(4)?, row.get::<_, u64>(5)?,
+            ))
+        }).map_err(|e| e.to_string())?;
+
+        let mut overall = CacheSummary::default();
+        let mut sessions: Vec<CacheRanking> = Vec::new();
+        let mut projects: HashMap<String, CacheSummary> = HashMap::new();
+
+        for row in rows {
+            let (id, project, model, input, cache_read, cache_creation) = row.map_err(|e| e.to_string())?;
+            overall.add(&model, input, cache_read, cache_creation);
+            projects.entry(project).or_default().add(&model, input, cache_read, cache_creation);
+
+            let mut summary = CacheSummary::default();
+            summary.add(&model, input, cache_read, cache_creation);
+            sessions.push(CacheRanking { key: id, summary });
+        }
+
+        let limit = limit.unwrap_or(DEFAULT_RANKING_LIMIT) as usize;
+        let projects = projects
+            .into_iter()
+            .map(|(key, summary)| CacheRanking { key, summary })
+            .collect();
+
+        Ok(CacheStats {
+            overall,
+            worst_sessions: worst(sessions, limit),
+            worst_projects: worst(projects, limit),
+        })
+    }
+}
+
+fn worst(mut entries: Vec<CacheRanking>, limit: usize) -> Vec<CacheRanking> {
+    entries.retain(|e| e.summary.total_input() >= MIN_RANKED_INPUT_TOKENS);
+    entries.sort_by(|a, b| {
+        a.summary
+            .hit_ratio
+            .partial_cmp(&b.summary.hit_ratio)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(b.summary.total_input().cmp(&a.summary.total_input()))
+    });
+    entries.truncate(limit);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_util::{session, temp_db};
+
+    #[test]
+    fn date_bounds_are_local_midnight() {
+        let db = temp_db("cache-bounds");
+        db.set_timezone("America/New_York").unwrap();
+        for (id, started_at) in [("jan-4", "2026-01-05T04:00:00.000Z"), ("jan-5", "2026-01-05T06:00:00.000Z")] {
+            let mut s = session(id, started_at);
+            s.input_tokens = 1_000;
+            s.cache_read_tokens = 3_000;
+            db.upsert_session(&s).unwrap();
+        }
+
+        let stats = db.get_cache_stats(Some("2026-01-05"), Some("2026-01-06"), None).unwrap();
+        assert_eq!(stats.overall.input_tokens, 1_000);
+        assert_eq!(stats.overall.cache_read_tokens, 3_000);
+        assert!(db.get_cache_stats(Some("soon"), None, None).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// Calendar period used to group usage for charts and totals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    Day,
+    /// ISO weeks, starting on Monday.
+    Week,
+    Month,
+}
+
+/// Resolves a configured IANA name, falling back to the system zone and then UTC.
+pub fn resolve_timezone(configured: Option<&str>) -> Tz {
+    configured
+        .filter(|name| !name.is_empty())
+        .and_then(|name| name.parse().ok())
+        .or_else(|| iana_time_zone::get_timezone().ok().and_then(|name| name.parse().ok()))
+        .unwrap_or(Tz::UTC)
+}
+
+/// First local date of the bucket containing `ts`.
+pub fn bucket_start(ts: DateTime<Utc>, tz: Tz, bucket: Bucket) -> NaiveDate {
+    let date = ts.with_timezone(&tz).date_naive();
+    align(date, bucket)
+}
+
+fn align(date: NaiveDate, bucket: Bucket) -> NaiveDate {
+    match bucket {
+        Bucket::Day => date,
+        Bucket::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        Bucket::Month => date.with_day(1).unwrap_or(date),
+    }
+}
+
+/// First local date of the bucket `periods` buckets before the current one.
+pub fn range_start(now: DateTime<Utc>, tz: Tz, bucket: Bucket, periods: u32) -> NaiveDate {
+    let current = bucket_start(now, tz, bucket);
+    match bucket {
+        Bucket::Day => current - Duration::days(periods as i64),
+        Bucket::Week => current - Duration::weeks(periods as i64),
+        Bucket::Month => {
+            let months = current.year() * 12 + current.month0() as i32 - periods as i32;
+            NaiveDate::from_ymd_opt(months.div_euclid(12), months.rem_euclid(12) as u32 + 1, 1)
+                .unwrap_or(current)
+        }
+    }
+}
+
+/// The UTC instant local `date` begins. When a DST change skips midnight the
+/// day starts at the first local time that exists.
+pub fn local_midnight_utc(date: NaiveDate, tz: Tz) -> DateTime<Utc> {
+    let mut local = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    for _ in 0..24 * 4 {
+        match tz.from_local_datetime(&local) {
+            LocalResult::Single(t) => return t.with_timezone(&Utc),
+            LocalResult::Ambiguous(earliest, _) => return earliest.with_timezone(&Utc),
+            LocalResult::None => local += Duration::minutes(15),
+        }
+    }
+    Utc.from_utc_datetime(&local)
+}
+
+/// Parses the timestamps Claude Code and the rollup tables write (RFC 3339, UTC).
+pub fn parse_utc(ts: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(ts).ok().map(|t| t.with_timezone(&Utc))
+}
+
+/// Parses a range bound given as RFC 3339 or as a `YYYY-MM-DD` date, which
+/// means local midnight in `tz`.
+pub fn parse_bound(value: &str, tz: Tz) -> Option<DateTime<Utc>> {
+    parse_utc(value).or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .map(|date| local_midnight_utc(date, tz))
+    })
+}
+
+/// The format timestamps are stored in, so bounds compare as strings.
+pub fn format_utc(ts: DateTime<Utc>) -> String {
+    ts.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(ts: &str) -> DateTime<Utc> {
+        parse_utc(ts).unwrap()
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn midnight_on_a_spring_forward_day() {
+        // Berlin changes at 02:00, so midnight still exists in winter time.
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        assert_eq!(local_midnight_utc(date("2026-03-29"), berlin), utc("2026-03-28T23:00:00Z"));
+        assert_eq!(local_midnight_utc(date("2026-03-30"), berlin), utc("2026-03-29T22:00:00Z"));
+
+        // São Paulo skipped from 00:00 to 01:00, so the day began at 01:00 -02:00.
+        let sao_paulo: Tz = "America/Sao_Paulo".parse().unwrap();
+        assert_eq!(local_midnight_utc(date("2018-11-04"), sao_paulo), utc("2018-11-04T03:00:00Z"));
+    }
+
+    #[test]
+    fn midnight_on_a_fall_back_day_is_the_earlier_one() {
+        // Havana falls back from 01:00 to 00:00, so midnight happens twice.
+        let havana: Tz = "America/Havana".parse().unwrap();
+        assert_eq!(local_midnight_utc(date("2025-11-02"), havana), utc("2025-11-02T04:00:00Z"));
+    }
+
+    #[test]
+    fn buckets_follow_local_dates() {
+        let kolkata: Tz = "Asia/Kolkata".parse().unwrap();
+        assert_eq!(bucket_start(utc("2025-06-01T18:29:59Z"), kolkata, Bucket::Day), date("2025-06-01"));
+        assert_eq!(bucket_start(utc("2025-06-01T18:30:00Z"), kolkata, Bucket::Day), date("2025-06-02"));
+        // Sunday evening in UTC is already Monday in Kolkata.
+        assert_eq!(bucket_start(utc("2025-06-01T20:00:00Z"), kolkata, Bucket::Week), date("2025-06-02"));
+        assert_eq!(bucket_start(utc("2025-06-30T19:00:00Z"), kolkata, Bucket::Month), date("2025-07-01"));
+    }
+
+    #[test]
+    fn range_start_counts_whole_buckets_back() {
+        let now = utc("2026-03-18T12:00:00Z");
+        assert_eq!(range_start(now, Tz::UTC, Bucket::Day, 6), date("2026-03-12"));
+        assert_eq!(range_start(now, Tz::UTC, Bucket::Week, 2), date("2026-03-02"));
+        assert_eq!(range_start(now, Tz::UTC, Bucket::Month, 3), date("2025-12-01"));
+    }
+
+    #[test]
+    fn bounds_accept_dates_and_timestamps() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        assert_eq!(parse_bound("2026-07-01", berlin), Some(utc("2026-06-30T22:00:00Z")));
+        assert_eq!(parse_bound("2026-07-01T05:00:00+02:00", berlin), Some(utc("2026-07-01T03:00:00Z")));
+        assert_eq!(parse_bound("yesterday", berlin), None);
+    }
+}
@@ -0,0 +1,454 @@
+use arrow_array::builder::{
+    BooleanBuilder, Float64Builder, Int64Builder, StringBuilder, TimestampMillisecondBuilder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params_from_iter, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::query::SessionQuery;
+use super::{calendar, Database};
+
+const BATCH_ROWS: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasetFormat {
+    /// Snappy-compressed Parquet.
+    Parquet,
+    /// Arrow IPC file format (Feather v2).
+    Arrow,
+}
+
+impl DatasetFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            DatasetFormat::Parquet => "parquet",
+            DatasetFormat::Arrow => "arrow",
+        }
+    }
+}
+
+/// One month of one table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetFile {
+    /// `sessions`, `messages` or `tool_calls`.
+    pub table: String,
+    /// `YYYY-MM`, in UTC, or `unknown` for rows without a timestamp.
+    pub month: String,
+    pub path: String,
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Text,
+    Int,
+    Real,
+    Bool,
+    /// RFC 3339 text in the database, millisecond UTC timestamps in the file.
+    Timestamp,
+}
+
+struct Table {
+    name: &'static str,
+    /// Column whose UTC month picks the partition.
+    partition_by: &'static str,
+    /// Name, type and whether it can be null. Same names and order as the
+    /// CSV/JSON schemas in `export`, plus the tool call table.
+    columns: &'static [(&'static str, Kind, bool)],
+    /// `FROM ... WHERE` clause restricting rows to the filtered sessions.
+    source: &'static str,
+}
+
+const SESSIONS: Table = Table {
+    name: "sessions",
+    partition_by: "started_at",
+    columns: &[
+        ("id", Kind::Text, false),
+        ("agent_id", Kind::Text, false),
+        ("project", Kind::Text, false),
+        ("cwd", Kind::Text, true),
+        ("git_branch", Kind::Text, true),
+        ("model", Kind::Text, false),
+        ("status", Kind::Text, false),
+        ("started_at", Kind::Timestamp, true),
+        ("ended_at", Kind::Timestamp, true),
+        ("duration_ms", Kind::Int, false),
+        ("input_tokens", Kind::Int, false),
+        ("output_tokens", Kind::Int, false),
+        ("cache_read_tokens", Kind::Int, false),
+        ("cache_creation_tokens", Kind::Int, false),
+        ("total_tokens", Kind::Int, false),
+        ("total_cost", Kind::Real, false),
+        ("tool_calls", Kind::Int, false),
+        ("energy_wh", Kind::Real, false),
+        ("permission_mode", Kind::Text, true),
+    ],
+    source: "sessions WHERE {filter}",
+};
+
+const MESSAGES: Table = Table {
+    name: "messages",
+    partition_by: "timestamp",
+    columns: &[
+        ("session_id", Kind::Text, false),
+        ("message_key", Kind::Text, true),
+        ("message_type", Kind::Text, false),
+        ("timestamp", Kind::Timestamp, true),
+        ("model", Kind::Text, true),
+        ("input_tokens", Kind::Int, false),
+        ("output_tokens", Kind::Int, false),
+        ("cache_read_tokens", Kind::Int, false),
+        ("cache_creation_tokens", Kind::Int, false),
+        ("context_tokens", Kind::Int, false),
+        ("cost", Kind::Real, false),
+        ("energy_wh", Kind::Real, false),
+        ("tool_name", Kind::Text, true),
+        ("content_preview", Kind::Text, true),
+    ],
+    source: "messages WHERE session_id IN (SELECT id FROM sessions WHERE {filter})",
+};
+
+const TOOL_CALLS: Table = Table {
+    name: "tool_calls",
+    partition_by: "started_at",
+    columns: &[
+        ("tool_use_id", Kind::Text, false),
+        ("session_id", Kind::Text, false),
+        ("message_key", Kind::Text, true),
+        ("name", Kind::Text, false),
+        ("server", Kind::Text, true),
+        ("input_bytes", Kind::Int, false),
+        ("started_at", Kind::Timestamp, true),
+        ("finished_at", Kind::Timestamp, true),
+        ("duration_ms", Kind::Int, true),
+        ("is_error", Kind::Bool, true),
+        ("cost", Kind::Real, false),
+    ],
+    source: "tool_calls WHERE session_id IN (SELECT id FROM sessions WHERE {filter})",
+};
+
+impl Database {
+    /// Writes the sessions matching `query`, their messages and their tool
+    /// calls under `dir` as `<table>/month=YYYY-MM/part-0.<ext>`, the layout
+    /// DuckDB, Polars and Spark read as a partitioned dataset. Rows without a
+    /// timestamp go to `month=unknown`. Re-exporting everything replaces the
+    /// months it writes and leaves other months alone; a filtered export only
+    /// goes to a directory without a dataset, since it would replace whole
+    /// months with part of them.
+    pub fn export_dataset(
+        &self,
+        query: &SessionQuery,
+        format: DatasetFormat,
+        dir: &Path,
+    ) -> Result<Vec<DatasetFile>, String> {
+        if query.has_filters() {
+            for table in [&SESSIONS, &MESSAGES, &TOOL_CALLS] {
+                let table_dir = dir.join(table.name);
+                let existing = std::fs::read_dir(&table_dir).map(|mut e| e.next().is_some()).unwrap_or(false);
+                if existing {
+                    return Err(format!(
+                        "{} already holds a dataset; export a filtered selection to an empty directory",
+                        dir.display()
+                    ));
+                }
+            }
+        }
+
+        // One snapshot for all three tables, read apart from the shared
+        // connection so the watcher can keep writing meanwhile.
+        let (filter, values) = query.filter_sql(self.get_timezone()?)?;
+        let conn = self.read_connection()?;
+        let snapshot = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+
+        let mut files = Vec::new();
+        for table in [&SESSIONS, &MESSAGES, &TOOL_CALLS] {
+            files.extend(export_table(&snapshot, table, &filter, &values, format, dir)?);
+        }
+        Ok(files)
+    }
+}
+
+fn export_table(
+    conn: &Connection,
+    table: &Table,
+    filter: &str,
+    values: &[Value],
+    format: DatasetFormat,
+    dir: &Path,
+) -> Result<Vec<DatasetFile>, String> {
+    let names: Vec<&str> = table.columns.iter().map(|(name, _, _)| *name).collect();
+    let sql = format!(
+        "SELECT {}, COALESCE(NULLIF(substr({}, 1, 7), ''), 'unknown') AS partition_month
+         FROM {} ORDER BY partition_month, {}, rowid",
+        names.join(", "),
+        table.partition_by,
+        table.source.replace("{filter}", filter),
+        table.partition_by
+    );
+    let schema = table.schema();
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let mut rows = stmt.query(params_from_iter(values)).map_err(|e| e.to_string())?;
+
+    let mut files = Vec::new();
+    let mut partition: Option<Partition> = None;
+    let mut builders = table.builders();
+    let mut buffered = 0;
+
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let month: String = row.get(names.len()).map_err(|e| e.to_string())?;
+        if partition.as_ref().is_some_and(|p| p.month != month) {
+            let mut done = partition.take().unwrap();
+            done.write(&schema, &mut builders, buffered)?;
+            buffered = 0;
+            files.push(done.finish(table.name)?);
+        }
+        if partition.is_none() {
+            partition = Some(Partition::create(table.name, &month, format, dir, &schema)?);
+        }
+
+        for (i, builder) in builders.iter_mut().enumerate() {
+            builder.append(row, i)?;
+        }
+        buffered += 1;
+        if buffered == BATCH_ROWS {
+            partition.as_mut().unwrap().write(&schema, &mut builders, buffered)?;
+            buffered = 0;
+        }
+    }
+
+    if let Some(mut done) = partition {
+        done.write(&schema, &mut builders, buffered)?;
+        files.push(done.finish(table.name)?);
+    }
+    Ok(files)
+}
+
+impl Table {
+    fn schema(&self) -> SchemaRef {
+        let fields: Vec<Field> = self
+            .columns
+            .iter()
+            .map(|(name, kind, nullable)| {
+                let data_type = match kind {
+                    Kind::Text => DataType::Utf8,
+                    Kind::Int => DataType::Int64,
+                    Kind::Real => DataType::Float64,
+                    Kind::Bool => DataType::Boolean,
+                    Kind::Timestamp => DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                };
+                Field::new(*name, data_type, *nullable)
+            })
+            .collect();
+        Arc::new(Schema::new(fields))
+    }
+
+    fn builders(&self) -> Vec<ColumnBuilder> {
+        self.columns
+            .iter()
+            .map(|(_, kind, _)| match kind {
+                Kind::Text => ColumnBuilder::Text(StringBuilder::new()),
+                Kind::Int => ColumnBuilder::Int(Int64Builder::new()),
+                Kind::Real => ColumnBuilder::Real(Float64Builder::new()),
+                Kind::Bool => ColumnBuilder::Bool(BooleanBuilder::new()),
+                Kind::Timestamp => ColumnBuilder::Timestamp(
+                    TimestampMillisecondBuilder::new().with_timezone("UTC"),
+                ),
+            })
+            .collect()
+    }
+}
+
+enum ColumnBuilder {
+    Text(StringBuilder),
+    Int(Int64Builder),
+    Real(Float64Builder),
+    Bool(BooleanBuilder),
+    Timestamp(TimestampMillisecondBuilder),
+}
+
+impl ColumnBuilder {
+    fn append(&mut self, row: &Row, i: usize) -> Result<(), String> {
+        let value = row.get_ref(i).map_err(|e| e.to_string())?;
+        match self {
+            ColumnBuilder::Text(b) => b.append_option(match value {
+                ValueRef::Null => None,
+                ValueRef::Integer(n) => Some(n.to_string()),
+                ValueRef::Real(f) => Some(f.to_string()),
+                ValueRef::Text(t) | ValueRef::Blob(t) => Some(String::from_utf8_lossy(t).into_owned()),
+            }),
+            ColumnBuilder::Int(b) => b.append_option(match value {
+                ValueRef::Integer(n) => Some(n),
+                ValueRef::Real(f) => Some(f as i64),
+                _ => None,
+            }),
+            ColumnBuilder::Real(b) => b.append_option(match value {
+                ValueRef::Integer(n) => Some(n as f64),
+                ValueRef::Real(f) => Some(f),
+                _ => None,
+            }),
+            ColumnBuilder::Bool(b) => b.append_option(match value {
+                ValueRef::Integer(n) => Some(n != 0),
+                _ => None,
+            }),
+            ColumnBuilder::Timestamp(b) => b.append_option(match value {
+                ValueRef::Text(t) => std::str::from_utf8(t)
+                    .ok()
+                    .and_then(calendar::parse_utc)
+                    .map(|ts| ts.timestamp_millis()),
+                _ => None,
+            }),
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Text(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int(b) => Arc::new(b.finish()),
+            ColumnBuilder::Real(b) => Arc::new(b.finish()),
+            ColumnBuilder::Bool(b) => Arc::new(b.finish()),
+            ColumnBuilder::Timestamp(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+enum PartitionWriter {
+    Parquet(ArrowWriter<File>),
+    Arrow(FileWriter<File>),
+}
+
+/// A file being written to `<path>.partial`, renamed into place once closed.
+struct Partition {
+    month: String,
+    path: PathBuf,
+    tmp_path: PathBuf,
+    writer: PartitionWriter,
+    rows: u64,
+}
+
+impl Partition {
+    fn create(
+        table: &str,
+        month: &str,
+        format: DatasetFormat,
+        dir: &Path,
+        schema: &SchemaRef,
+    ) -> Result<Self, String> {
+        let partition_dir = dir.join(table).join(format!("month={}", month));
+        std::fs::create_dir_all(&partition_dir)
+            .map_err(|e| format!("Can't create {}: {}", partition_dir.display(), e))?;
+        let path = partition_dir.join(format!("part-0.{}", format.extension()));
+        let tmp_path = partition_dir.join(format!("part-0.{}.partial", format.extension()));
+        let file = File::create(&tmp_path).map_err(|e| e.to_string())?;
+
+        let writer = match format {
+            DatasetFormat::Parquet => {
+                let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+                PartitionWriter::Parquet(
+                    ArrowWriter::try_new(file, schema.clone(), Some(props)).map_err(|e| e.to_string())?,
+                )
+            }
+            DatasetFormat::Arrow => {
+                PartitionWriter::Arrow(FileWriter::try_new(file, schema).map_err(|e| e.to_string())?)
+            }
+        };
+        Ok(Self { month: month.to_string(), path, tmp_path, writer, rows: 0 })
+    }
+
+    fn write(&mut self, schema: &SchemaRef, builders: &mut [ColumnBuilder], rows: usize) -> Result<(), String> {
+        if rows == 0 {
+            return Ok(());
+        }
+        let columns: Vec<ArrayRef> = builders.iter_mut().map(ColumnBuilder::finish).collect();
+        let batch = RecordBatch::try_new(schema.clone(), columns).map_err(|e| e.to_string())?;
+        match &mut self.writer {
+            PartitionWriter::Parquet(w) => w.write(&batch).map_err(|e| e.to_string())?,
+            PartitionWriter::Arrow(w) => w.write(&batch).map_err(|e| e.to_string())?,
+        }
+        self.rows += rows as u64;
+        Ok(())
+    }
+
+    fn finish(self, table: &str) -> Result<DatasetFile, String> {
+        match self.writer {
+            PartitionWriter::Parquet(w) => {
+                w.close().map_err(|e| e.to_string())?;
+            }
+            PartitionWriter::Arrow(mut w) => w.finish().map_err(|e| e.to_string())?,
+        }
+        std::fs::rename(&self.tmp_path, &self.path).map_err(|e| e.to_string())?;
+        let bytes = std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        Ok(DatasetFile {
+            table: table.to_string(),
+            month: self.month,
+            path: self.path.to_string_lossy().to_string(),
+            rows: self.rows,
+            bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::{message, session, temp_path};
+
+    fn months(files: &[DatasetFile], table: &str) -> Vec<(String, u64)> {
+        let mut months: Vec<(String, u64)> = files
+            .iter()
+            .filter(|f| f.table == table)
+            .map(|f| (f.month.clone(), f.rows))
+            .collect();
+        months.sort();
+        months
+    }
+
+    #[test]
+    fn rows_without_a_timestamp_go_to_unknown() {
+        let path = temp_path("dataset-unknown");
+        let db = Database::open(&path).unwrap();
+        db.upsert_session(&session("s1", "2026-01-05T10:00:00.000Z")).unwrap();
+        db.upsert_session(&session("s2", "")).unwrap();
+        db.insert_message(&message("s1", Some("m1"), "2026-02-01T10:00:00.000Z", 1.0)).unwrap();
+        db.insert_message(&message("s1", Some("m2"), "", 1.0)).unwrap();
+
+        let dir = path.with_file_name("dataset");
+        let files = db.export_dataset(&SessionQuery::default(), DatasetFormat::Parquet, &dir).unwrap();
+        assert_eq!(months(&files, "sessions"), [("2026-01".to_string(), 1), ("unknown".to_string(), 1)]);
+        assert_eq!(months(&files, "messages"), [("2026-02".to_string(), 1), ("unknown".to_string(), 1)]);
+        assert!(dir.join("sessions/month=unknown/part-0.parquet").exists());
+    }
+
+    #[test]
+    fn filtered_exports_need_an_empty_directory() {
+        let path = temp_path("dataset-filtered");
+        let db = Database::open(&path).unwrap();
+        db.upsert_session(&session("s1", "2026-01-05T10:00:00.000Z")).unwrap();
+        db.upsert_session(&session("s2", "2026-01-06T10:00:00.000Z")).unwrap();
+        let dir = path.with_file_name("dataset");
+        db.export_dataset(&SessionQuery::default(), DatasetFormat::Arrow, &dir).unwrap();
+
+        let filtered = SessionQuery { from: Some("2026-01-06".to_string()), ..Default::default() };
+        let err = db.export_dataset(&filtered, DatasetFormat::Arrow, &dir).unwrap_err();
+        assert!(err.contains("already holds a dataset"), "{}", err);
+        // The full export is still there.
+        let files = db.export_dataset(&SessionQuery::default(), DatasetFormat::Arrow, &dir).unwrap();
+        assert_eq!(months(&files, "sessions"), [("2026-01".to_string(), 2)]);
+
+        let other = path.with_file_name("filtered");
+        let files = db.export_dataset(&filtered, DatasetFormat::Arrow, &other).unwrap();
+        assert_eq!(months(&files, "sessions"), [("2026-01".to_string(), 1)]);
+    }
+}
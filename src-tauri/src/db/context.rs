@@ -0,0 +1,161 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::collector::context::context_window;
+
+use super::Database;
+
+/// A point where Claude Code compacted the conversation: a `compact_boundary`
+/// system line, or a `summary` line standing in for earlier turns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionEvent {
+    pub session_id: String,
+    /// Line uuid, or `summary:<session>:<leafUuid>` for summaries, which
+    /// resumed sessions repeat in their own files.
+    pub event_key: String,
+    /// `compact_boundary` or `summary`.
+    pub kind: String,
+    pub timestamp: String,
+    /// `auto` or `manual`, when Claude Code recorded it.
+    pub trigger: Option<String>,
+    /// Context size right before compacting.
+    pub pre_tokens: Option<u64>,
+    pub summary: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextPoint {
+    pub timestamp: String,
+    pub context_tokens: u64,
+    /// Share of the context window in use.
+    pub ratio: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionContext {
+    pub context_limit: u64,
+    pub peak_context_tokens: u64,
+    pub points: Vec<ContextPoint>,
+    pub compactions: Vec<CompactionEvent>,
+}
+
+impl Database {
+    pub fn insert_compaction(&self, e: &CompactionEvent) -> Result<bool, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let inserted = conn.execute(
+            "INSERT INTO compaction_events (session_id, event_key, kind, timestamp, trigger,
+             pre_tokens, summary)
+             VALUES (?1,?2,?3,?4,?5,?6,?7)
+             ON CONFLICT(event_key) DO NOTHING",
+            params![e.session_id, e.event_key, e.kind, e.timestamp, e.trigger,
+                    e.pre_tokens, e.summary],
+        ).map_err(|e| e.to_string())?;
+        Ok(inserted > 0)
+    }
+
+    /// Context size of each of the session's messages against the model's
+    /// window, with the compactions in between.
+    pub fn get_session_context(&self, session_id: &str) -> Result<SessionContext, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+
+        let model: String = conn.query_row(
+            "SELECT model FROM sessions WHERE id = ?1", params![session_id], |row| row.get(0),
+        ).map_err(|e| e.to_string())?;
+
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, context_tokens FROM messages
+             WHERE session_id = ?1 ORDER BY timestamp ASC"
+        ).map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![session_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?)))
+            .map_err(|e| e.to_string())?;
+        let samples = rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+
+        let mut stmt = conn.prepare(
+            "SELECT session_id, event_key, kind, timestamp, trigger, pre_tokens, summary
+             FROM compaction_events WHERE session_id = ?1 ORDER BY timestamp ASC, id ASC"
+        ).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(params![session_id], |row| {
+            Ok(CompactionEvent {
+                session_id: row.get(0)?, event_key: row.get(1)?, kind: row.get(2)?,
+                timestamp: row.get(3)?, trigger: row.get(4)?, pre_tokens: row.get(5)?,
+                summary: row.get(6)?,
+            })
+        }).map_err(|e| e.to_string())?;
+        let compactions = rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+
+        let peak_context_tokens = samples.iter().map(|(_, tokens)| *tokens).max().unwrap_or(0);
+        let context_limit = context_window(&model, peak_context_tokens);
+        let points = samples
+            .into_iter()
+            .map(|(timestamp, context_tokens)| ContextPoint {
+                timestamp,
+                context_tokens,
+                ratio: context_tokens as f64 / context_limit as f64,
+            })
+            .collect();
+
+        Ok(SessionContext { context_limit, peak_context_tokens, points, compactions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompactionEvent;
+    use crate::db::test_util::{message, session, temp_db};
+
+    fn compaction(key: &str, kind: &str, timestamp: &str, pre_tokens: Option<u64>) -> CompactionEvent {
+        CompactionEvent {
+            session_id: "s1".to_string(),
+            event_key: key.to_string(),
+            kind: kind.to_string(),
+            timestamp: timestamp.to_string(),
+            trigger: pre_tokens.map(|_| "auto".to_string()),
+            pre_tokens,
+            summary: None,
+        }
+    }
+
+    #[test]
+    fn context_points_are_measured_against_the_window() {
+        let db = temp_db("context-points");
+        db.upsert_session(&session("s1", "2026-03-01T10:00:00.000Z")).unwrap();
+        for (key, timestamp, context_tokens) in [
+            ("m2", "2026-03-01T10:02:00.000Z", 150_000),
+            ("m1", "2026-03-01T10:01:00.000Z", 50_000),
+            ("m3", "2026-03-01T10:03:00.000Z", 20_000),
+        ] {
+            let mut m = message("s1", Some(key), timestamp, 0.01);
+            m.context_tokens = context_tokens;
+            db.insert_message(&m).unwrap();
+        }
+        assert!(db.insert_compaction(&compaction("c1", "compact_boundary", "2026-03-01T10:02:30.000Z", Some(160_000))).unwrap());
+        assert!(db.insert_compaction(&compaction("summary:s1:m2", "summary", "2026-03-01T10:02:31.000Z", None)).unwrap());
+        // Resumed sessions repeat the summary line.
+        assert!(!db.insert_compaction(&compaction("summary:s1:m2", "summary", "2026-03-01T10:05:00.000Z", None)).unwrap());
+
+        let context = db.get_session_context("s1").unwrap();
+        assert_eq!(context.context_limit, 200_000);
+        assert_eq!(context.peak_context_tokens, 150_000);
+        let points: Vec<_> = context.points.iter().map(|p| (p.context_tokens, p.ratio)).collect();
+        assert_eq!(points, [(50_000, 0.25), (150_000, 0.75), (20_000, 0.1)]);
+        let kinds: Vec<_> = context.compactions.iter().map(|c| (c.kind.as_str(), c.pre_tokens)).collect();
+        assert_eq!(kinds, [("compact_boundary", Some(160_000)), ("summary", None)]);
+
+        assert!(db.get_session_context("missing").is_err());
+    }
+
+    #[test]
+    fn contexts_past_the_default_window_use_the_extended_one() {
+        let db = temp_db("context-extended");
+        db.upsert_session(&session("s1", "2026-03-01T10:00:00.000Z")).unwrap();
+        let mut m = message("s1", Some("m1"), "2026-03-01T10:01:00.000Z", 0.01);
+        m.context_tokens = 250_000;
+        db.insert_message(&m).unwrap();
+
+        let context = db.get_session_context("s1").unwrap();
+        assert_eq!(context.context_limit, 1_000_000);
+        assert_eq!(context.points[0].ratio, 0.25);
+        assert!(context.compactions.is_empty());
+    }
+}
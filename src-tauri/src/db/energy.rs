@@ -0,0 +1,125 @@
+use rusqlite::{params, Connection};
+
+use crate::collector::energy::EnergySettings;
+
+use super::Database;
+
+const ENERGY_SETTINGS_KEY: &str = "energy_settings";
+
+impl Database {
+    pub fn get_energy_settings(&self) -> Result<EnergySettings, String> {
+        Ok(self
+            .get_setting(ENERGY_SETTINGS_KEY)?
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default())
+    }
+
+    /// Saves new factors and recomputes the energy already stored with them.
+    pub fn set_energy_settings(&self, settings: &EnergySettings) -> Result<(), String> {
+        let negative = settings.default_wh_per_token < 0.0
+            || settings.grid_g_co2_per_kwh < 0.0
+            || settings.factors.iter().any(|f| f.wh_per_token < 0.0);
+        if negative {
+            return Err("Energy factors and grid intensity can't be negative".to_string());
+        }
+
+        let json = serde_json::to_string(settings).map_err(|e| e.to_string())?;
+        self.set_setting(ENERGY_SETTINGS_KEY, &json)?;
+
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        recompute_energy(&tx, settings)?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+}
+
+/// Rewrites `energy_wh` on messages, sessions and hourly rollups from their
+/// token counts and model.
+fn recompute_energy(conn: &Connection, settings: &EnergySettings) -> Result<(), String> {
+    for table in ["messages", "sessions", "usage_hourly"] {
+        let models = {
+            let mut stmt = conn
+                .prepare(&format!("SELECT DISTINCT COALESCE(model, '') FROM {}", table))
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?
+        };
+
+        for model in models {
+            conn.execute(
+                &format!(
+                    "UPDATE {} SET energy_wh = (input_tokens + output_tokens + cache_read_tokens
+                     + cache_creation_tokens) * ?2 WHERE COALESCE(model, '') = ?1",
+                    table
+                ),
+                params![model, settings.wh_per_token(&model)],
+            ).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collector::energy::{EnergyFactor, EnergySettings};
+    use crate::db::test_util::{message, session, temp_db};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn new_factors_recompute_stored_energy() {
+        let db = temp_db("energy-recompute");
+        let mut opus = session("s1", "2026-03-01T10:00:00.000Z");
+        opus.model = "claude-opus-4-1".to_string();
+        opus.input_tokens = 100;
+        opus.output_tokens = 200;
+        db.upsert_session(&opus).unwrap();
+        db.upsert_session(&session("s2", "2026-03-01T10:00:00.000Z")).unwrap();
+
+        let mut m = message("s1", Some("m1"), "2026-03-01T10:01:00.000Z", 0.1);
+        m.model = Some("claude-opus-4-1".to_string());
+        db.insert_message(&m).unwrap();
+        db.insert_message(&message("s2", Some("m2"), "2026-03-01T10:02:00.000Z", 0.01)).unwrap();
+        db.insert_message(&message("s2", Some("m3"), "2026-03-01T10:03:00.000Z", 0.01)).unwrap();
+
+        let settings = EnergySettings {
+            factors: vec![
+                EnergyFactor { model_pattern: "opus".to_string(), wh_per_token: 0.01 },
+                EnergyFactor { model_pattern: "sonnet".to_string(), wh_per_token: 0.002 },
+            ],
+            default_wh_per_token: 0.001,
+            grid_g_co2_per_kwh: 500.0,
+        };
+        db.set_energy_settings(&settings).unwrap();
+        assert_eq!(db.get_energy_settings().unwrap().grid_g_co2_per_kwh, 500.0);
+
+        // 30 tokens per message, 300 on the opus session.
+        assert!(close(db.get_session_messages("s1").unwrap()[0].energy_wh, 0.3));
+        assert!(close(db.get_session("s1").unwrap().unwrap().energy_wh, 3.0));
+        assert!(close(db.get_session("s2").unwrap().unwrap().energy_wh, 0.0));
+
+        let stats = db.get_model_stats().unwrap();
+        let energy: Vec<_> = stats.iter().map(|s| (s.model.as_str(), s.energy_wh, s.co2_g)).collect();
+        assert_eq!(energy.len(), 2);
+        assert_eq!(energy[0].0, "claude-opus-4-1");
+        assert!(close(energy[0].1, 0.3) && close(energy[0].2, 0.15));
+        assert_eq!(energy[1].0, "claude-sonnet-4-5");
+        assert!(close(energy[1].1, 0.12) && close(energy[1].2, 0.06));
+    }
+
+    #[test]
+    fn negative_factors_are_rejected() {
+        let db = temp_db("energy-negative");
+        let mut settings = EnergySettings::default();
+        settings.factors[1].wh_per_token = -0.1;
+        assert!(db.set_energy_settings(&settings).is_err());
+
+        let settings = EnergySettings { grid_g_co2_per_kwh: -1.0, ..EnergySettings::default() };
+        assert!(db.set_energy_settings(&settings).is_err());
+        assert_eq!(db.get_energy_settings().unwrap().grid_g_co2_per_kwh, 385.0);
+    }
+}
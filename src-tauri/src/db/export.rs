@@ -0,0 +1,310 @@
+use rusqlite::types::ValueRef;
+use rusqlite::{params_from_iter, Row};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::query::SessionQuery;
+use super::Database;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// RFC 4180, with a header row.
+    Csv,
+    /// One array of objects.
+    Json,
+    /// One object per line.
+    Ndjson,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportReport {
+    pub path: String,
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+/// Columns of `export_sessions`, in order. This is a stable schema: columns
+/// are only ever appended.
+///
+/// | column | type |
+/// |---|---|
+/// | id, agent_id, project | text |
+/// | cwd, git_branch | text, empty/null when unknown |
+/// | model, status | text |
+/// | started_at, ended_at | RFC 3339 UTC text; `ended_at` may be null |
+/// | duration_ms | integer |
+/// | input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens, total_tokens | integer |
+/// | total_cost | real, USD |
+/// | tool_calls | integer |
+/// | energy_wh | real |
+/// | permission_mode | text, null unless the app started the agent |
+pub const SESSION_EXPORT_COLUMNS: &[&str] = &[
+    "id", "agent_id", "project", "cwd", "git_branch", "model", "status",
+    "started_at", "ended_at", "duration_ms",
+    "input_tokens", "output_tokens", "cache_read_tokens", "cache_creation_tokens", "total_tokens",
+    "total_cost", "tool_calls", "energy_wh", "permission_mode",
+];
+
+/// Columns of `export_messages`, in order. Stable like the session schema.
+///
+/// | column | type |
+/// |---|---|
+/// | session_id | text |
+/// | message_key | text, null for rows stored before keys existed |
+/// | message_type | text |
+/// | timestamp | RFC 3339 UTC text |
+/// | model | text or null |
+/// | input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens, context_tokens | integer |
+/// | cost | real, USD |
+/// | energy_wh | real |
+/// | tool_name | text or null, the first tool the message called |
+/// | content_preview | text or null, cleared by preview retention |
+pub const MESSAGE_EXPORT_COLUMNS: &[&str] = &[
+    "session_id", "message_key", "message_type", "timestamp", "model",
+    "input_tokens", "output_tokens", "cache_read_tokens", "cache_creation_tokens", "context_tokens",
+    "cost", "energy_wh", "tool_name", "content_preview",
+];
+
+impl Database {
+    /// Writes every session matching `query` to `path`, in the query's sort
+    /// order. `limit` and `cursor` are ignored.
+    pub fn export_sessions(
+        &self,
+        query: &SessionQuery,
+        format: ExportFormat,
+        path: &Path,
+    ) -> Result<ExportReport, String> {
+        let (filter, values) = query.filter_sql(self.get_timezone()?)?;
+        let order = if query.descending.unwrap_or(true) { "DESC" } else { "ASC" };
+        let sql = format!(
+            "SELECT {} FROM sessions WHERE {} ORDER BY {} {order}, id {order}",
+            SESSION_EXPORT_COLUMNS.join(", "),
+            filter,
+            query.sort.column(),
+            order = order
+        );
+        self.export(&sql, values, SESSION_EXPORT_COLUMNS, format, path)
+    }
+
+    /// Writes the messages of every session matching `query` to `path`,
+    /// ordered by session and time.
+    pub fn export_messages(
+        &self,
+        query: &SessionQuery,
+        format: ExportFormat,
+        path: &Path,
+    ) -> Result<ExportReport, String> {
+        let (filter, values) = query.filter_sql(self.get_timezone()?)?;
+        let sql = format!(
+            "SELECT {} FROM messages
+             WHERE session_id IN (SELECT id FROM sessions WHERE {})
+             ORDER BY session_id, timestamp, id",
+            MESSAGE_EXPORT_COLUMNS.join(", "),
+            filter
+        );
+        self.export(&sql, values, MESSAGE_EXPORT_COLUMNS, format, path)
+    }
+
+    /// Streams the rows of `sql` into a temporary file next to `path` and
+    /// renames it into place, so a failed export never leaves a partial file.
+    /// Rows are read on a connection of their own, so a long export doesn't
+    /// hold up the watcher.
+    fn export(
+        &self,
+        sql: &str,
+        values: Vec<rusqlite::types::Value>,
+        columns: &[&str],
+        format: ExportFormat,
+        path: &Path,
+    ) -> Result<ExportReport, String> {
+        let tmp_path = temp_path(path);
+        let file = File::create(&tmp_path).map_err(|e| format!("Can't create {}: {}", tmp_path.display(), e))?;
+        let mut out = BufWriter::new(file);
+
+        let result = (|| {
+            let conn = self.read_connection()?;
+            let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+            let mut rows = stmt.query(params_from_iter(values.iter())).map_err(|e| e.to_string())?;
+
+            let mut count = 0u64;
+            match format {
+                ExportFormat::Csv => writeln!(out, "{}", columns.join(",")),
+                ExportFormat::Json => write!(out, "["),
+                ExportFormat::Ndjson => Ok(()),
+            }
+            .map_err(|e| e.to_string())?;
+
+            while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+                let line = match format {
+                    ExportFormat::Csv => csv_line(row, columns.len())?,
+                    ExportFormat::Json | ExportFormat::Ndjson => json_object(row, columns)?,
+                };
+                match format {
+                    ExportFormat::Json if count > 0 => write!(out, ",\n{}", line),
+                    ExportFormat::Json => write!(out, "\n{}", line),
+                    _ => writeln!(out, "{}", line),
+                }
+                .map_err(|e| e.to_string())?;
+                count += 1;
+            }
+
+            if format == ExportFormat::Json {
+                writeln!(out, "\n]").map_err(|e| e.to_string())?;
+            }
+            out.flush().map_err(|e| e.to_string())?;
+            Ok(count)
+        })();
+
+        let rows = match result {
+            Ok(rows) => rows,
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp_path);
+                return Err(e);
+            }
+        };
+
+        std::fs::rename(&tmp_path, path).map_err(|e| e.to_string())?;
+        let bytes = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        Ok(ExportReport { path: path.to_string_lossy().to_string(), rows, bytes })
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+fn json_value(value: ValueRef) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => serde_json::Number::from_f64(f)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        ValueRef::Text(t) | ValueRef::Blob(t) => String::from_utf8_lossy(t).into(),
+    }
+}
+
+/// Builds the object by hand so keys keep the schema's column order.
+fn json_object(row: &Row, columns: &[&str]) -> Result<String, String> {
+    let mut fields = Vec::with_capacity(columns.len());
+    for (i, column) in columns.iter().enumerate() {
+        let value = json_value(row.get_ref(i).map_err(|e| e.to_string())?);
+        fields.push(format!(
+            "{}:{}",
+            serde_json::to_string(column).map_err(|e| e.to_string())?,
+            serde_json::to_string(&value).map_err(|e| e.to_string())?
+        ));
+    }
+    Ok(format!("{{{}}}", fields.join(",")))
+}
+
+/// Nulls are written as empty fields.
+fn csv_line(row: &Row, width: usize) -> Result<String, String> {
+    let mut fields = Vec::with_capacity(width);
+    for i in 0..width {
+        let field = match row.get_ref(i).map_err(|e| e.to_string())? {
+            ValueRef::Null => String::new(),
+            ValueRef::Integer(n) => n.to_string(),
+            ValueRef::Real(f) => f.to_string(),
+            ValueRef::Text(t) | ValueRef::Blob(t) => csv_escape(&String::from_utf8_lossy(t)),
+        };
+        fields.push(field);
+    }
+    Ok(fields.join(","))
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::{message, session, temp_path};
+
+    fn exported(db: &Database, format: ExportFormat, name: &str) -> (ExportReport, String) {
+        let path = temp_path(name).with_file_name(name);
+        let report = db.export_sessions(&SessionQuery { descending: Some(false), ..Default::default() }, format, &path).unwrap();
+        (report, std::fs::read_to_string(&path).unwrap())
+    }
+
+    #[test]
+    fn csv_fields_with_commas_quotes_and_newlines_are_quoted() {
+        let path = temp_path("export-csv");
+        let db = Database::open(&path).unwrap();
+        let mut tricky = session("s1", "2026-01-05T10:00:00.000Z");
+        tricky.project = "/work/app, v2".to_string();
+        tricky.git_branch = Some("fix \"quotes\"".to_string());
+        tricky.cwd = Some("/work/line\nbreak".to_string());
+        db.upsert_session(&tricky).unwrap();
+        let mut message = message("s1", Some("m1"), "2026-01-05T10:00:01.000Z", 0.25);
+        message.content_preview = Some("a,\"b\"\r\nc".to_string());
+        db.insert_message(&message).unwrap();
+
+        let sessions_csv = path.with_file_name("sessions.csv");
+        let report = db.export_sessions(&SessionQuery::default(), ExportFormat::Csv, &sessions_csv).unwrap();
+        let text = std::fs::read_to_string(&sessions_csv).unwrap();
+        assert_eq!(text.lines().next().unwrap(), SESSION_EXPORT_COLUMNS.join(","));
+        assert!(
+            text.contains("s1,agent,\"/work/app, v2\",\"/work/line\nbreak\",\"fix \"\"quotes\"\"\",claude-sonnet-4-5,completed,"),
+            "{}",
+            text
+        );
+        assert_eq!(report.rows, 1);
+
+        let messages_csv = path.with_file_name("messages.csv");
+        db.export_messages(&SessionQuery::default(), ExportFormat::Csv, &messages_csv).unwrap();
+        let text = std::fs::read_to_string(&messages_csv).unwrap();
+        assert!(text.ends_with(",\"a,\"\"b\"\"\r\nc\"\n"), "{}", text);
+        // Nulls are empty fields, numbers are bare.
+        assert!(text.contains("s1,m1,assistant,2026-01-05T10:00:01.000Z,claude-sonnet-4-5,10,20,0,0,10,0.25,0,,"), "{}", text);
+    }
+
+    #[test]
+    fn reports_count_rows_and_bytes_in_every_format() {
+        let path = temp_path("export-report");
+        let db = Database::open(&path).unwrap();
+        for (id, started_at) in [("s1", "2026-01-05T10:00:00.000Z"), ("s2", "2026-01-06T10:00:00.000Z"), ("s3", "2026-01-07T10:00:00.000Z")] {
+            db.upsert_session(&session(id, started_at)).unwrap();
+        }
+
+        for format in [ExportFormat::Csv, ExportFormat::Json, ExportFormat::Ndjson] {
+            let name = format!("export-report-{:?}", format);
+            let (report, text) = exported(&db, format, &name);
+            assert_eq!(report.rows, 3, "{:?}", format);
+            assert_eq!(report.bytes, text.len() as u64, "{:?}", format);
+            assert!(!Path::new(&format!("{}.partial", report.path)).exists());
+
+            let ids: Vec<String> = match format {
+                ExportFormat::Csv => text.lines().skip(1).map(|l| l.split(',').next().unwrap().to_string()).collect(),
+                ExportFormat::Json => serde_json::from_str::<Vec<serde_json::Value>>(&text)
+                    .unwrap()
+                    .iter()
+                    .map(|row| row["id"].as_str().unwrap().to_string())
+                    .collect(),
+                ExportFormat::Ndjson => text
+                    .lines()
+                    .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap()["id"].as_str().unwrap().to_string())
+                    .collect(),
+            };
+            assert_eq!(ids, ["s1", "s2", "s3"], "{:?}", format);
+        }
+
+        // An empty selection is still a valid file with no rows.
+        let none = SessionQuery { status: Some("running".to_string()), ..Default::default() };
+        let empty = path.with_file_name("empty.json");
+        let report = db.export_sessions(&none, ExportFormat::Json, &empty).unwrap();
+        assert_eq!(report.rows, 0);
+        let rows: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(&empty).unwrap()).unwrap();
+        assert!(rows.is_empty());
+    }
+}
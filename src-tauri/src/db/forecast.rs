@@ -0,0 +1,243 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::calendar::{self, Bucket};
+use super::Database;
+
+/// Weeks of history the hourly profile is averaged over.
+const HISTORY_WEEKS: i64 = 4;
+const HOURS_PER_WEEK: usize = 7 * 24;
+
+/// Spend so far today and this month, and where each ends up if the rest of
+/// the period follows the usual usage for its weekday and hour.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendForecast {
+    pub today_cost: f64,
+    pub today_tokens: u64,
+    pub projected_today_cost: f64,
+    pub projected_today_tokens: u64,
+    pub month_cost: f64,
+    pub month_tokens: u64,
+    pub projected_month_cost: f64,
+    pub projected_month_tokens: u64,
+    /// Days of history the profile is based on; projections are rough with few.
+    pub history_days: u32,
+}
+
+/// Average cost and tokens for each hour of the week (Monday 00:00 first) in
+/// the configured timezone.
+struct HourlyProfile {
+    cost: [f64; HOURS_PER_WEEK],
+    tokens: [f64; HOURS_PER_WEEK],
+}
+
+impl HourlyProfile {
+    /// Expected spend from `from` until `to`, prorating partial hours.
+    fn expected(&self, from: DateTime<Utc>, to: DateTime<Utc>, tz: chrono_tz::Tz) -> (f64, f64) {
+        let mut cost = 0.0;
+        let mut tokens = 0.0;
+        let mut at = from;
+        while at < to {
+            let hour_end = (truncate_hour(at) + Duration::hours(1)).min(to);
+            let fraction = (hour_end - at).num_seconds() as f64 / 3600.0;
+            let slot = week_slot(at, tz);
+            cost += self.cost[slot] * fraction;
+            tokens += self.tokens[slot] * fraction;
+            at = hour_end;
+        }
+        (cost, tokens)
+    }
+}
+
+impl Database {
+    pub fn get_spend_forecast(&self) -> Result<SpendForecast, String> {
+        self.spend_forecast_at(Utc::now())
+    }
+
+    fn spend_forecast_at(&self, now: DateTime<Utc>) -> Result<SpendForecast, String> {
+        let tz = self.get_timezone()?;
+        let today_start = calendar::local_midnight_utc(calendar::bucket_start(now, tz, Bucket::Day), tz);
+        let month_start_date = calendar::bucket_start(now, tz, Bucket::Month);
+        let month_start = calendar::local_midnight_utc(month_start_date, tz);
+        let tomorrow_start = calendar::local_midnight_utc(
+            now.with_timezone(&tz).date_naive() + Duration::days(1),
+            tz,
+        );
+        let next_month_start = calendar::local_midnight_utc(next_month(month_start_date), tz);
+
+        let (today_cost, today_tokens) = self.usage_between(today_start, now)?;
+        let (month_cost, month_tokens) = self.usage_between(month_start, now)?;
+        let (profile, history_days) = self.hourly_profile(today_start, tz)?;
+
+        let (rest_of_today_cost, rest_of_today_tokens) = profile.expected(now, tomorrow_start, tz);
+        let (rest_of_month_cost, rest_of_month_tokens) = profile.expected(now, next_month_start, tz);
+
+        Ok(SpendForecast {
+            today_cost,
+            today_tokens,
+            projected_today_cost: today_cost + rest_of_today_cost,
+            projected_today_tokens: today_tokens + rest_of_today_tokens as u64,
+            month_cost,
+            month_tokens,
+            projected_month_cost: month_cost + rest_of_month_cost,
+            projected_month_tokens: month_tokens + rest_of_month_tokens as u64,
+            history_days,
+        })
+    }
+
+    /// Usage from `from` through the hour `to` falls in. When `from` is inside
+    /// an hour (zones with half-hour offsets), that hour is split by the
+    /// timestamps of its messages, as in `get_cost_buckets`.
+    fn usage_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<(f64, u64), String> {
+        let first_hour = match truncate_hour(from) {
+            hour if hour == from => hour,
+            hour => hour + Duration::hours(1),
+        };
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let (mut cost, mut tokens): (f64, u64) = conn.query_row(
+            "SELECT COALESCE(SUM(cost), 0.0),
+             COALESCE(SUM(input_tokens + output_tokens + cache_read_tokens + cache_creation_tokens), 0)
+             FROM usage_hourly WHERE hour >= ?1 AND hour < ?2",
+            params![hour_key(first_hour), hour_key(to + Duration::hours(1))],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).map_err(|e| e.to_string())?;
+        if first_hour > from {
+            let (part_cost, part_tokens): (f64, u64) = conn.query_row(
+                "SELECT COALESCE(SUM(cost), 0.0),
+                 COALESCE(SUM(input_tokens + output_tokens + cache_read_tokens + cache_creation_tokens), 0)
+                 FROM messages WHERE timestamp >= ?1 AND timestamp < ?2",
+                params![calendar::format_utc(from), calendar::format_utc(first_hour)],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).map_err(|e| e.to_string())?;
+            cost += part_cost;
+            tokens += part_tokens;
+        }
+        Ok((cost, tokens))
+    }
+
+    /// Averages the hourly rollups of the weeks before `until`. Only hours
+    /// since the first recorded usage count, so a new install isn't averaged
+    /// against weeks of zeros.
+    fn hourly_profile(&self, until: DateTime<Utc>, tz: chrono_tz::Tz) -> Result<(HourlyProfile, u32), String> {
+        let history_start = until - Duration::weeks(HISTORY_WEEKS);
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+
+        let first: Option<String> = conn.query_row(
+            "SELECT MIN(hour) FROM usage_hourly WHERE hour >= ?1 AND hour < ?2",
+            params![hour_key(history_start), hour_key(until)],
+            |row| row.get(0),
+        ).map_err(|e| e.to_string())?;
+        let from = first
+            .as_deref()
+            .and_then(calendar::parse_utc)
+            .unwrap_or(until)
+            .max(history_start);
+
+        let mut profile = HourlyProfile { cost: [0.0; HOURS_PER_WEEK], tokens: [0.0; HOURS_PER_WEEK] };
+        let mut stmt = conn.prepare(
+            "SELECT hour, SUM(cost),
+             SUM(input_tokens + output_tokens + cache_read_tokens + cache_creation_tokens)
+             FROM usage_hourly WHERE hour >= ?1 AND hour < ?2 GROUP BY hour"
+        ).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(params![hour_key(from), hour_key(until)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, u64>(2)?))
+        }).map_err(|e| e.to_string())?;
+        for row in rows {
+            let (hour, cost, tokens) = row.map_err(|e| e.to_string())?;
+            if let Some(ts) = calendar::parse_utc(&hour) {
+                let slot = week_slot(ts, tz);
+                profile.cost[slot] += cost;
+                profile.tokens[slot] += tokens as f64;
+            }
+        }
+
+        let mut samples = [0u32; HOURS_PER_WEEK];
+        let mut at = truncate_hour(from);
+        while at < until {
+            samples[week_slot(at, tz)] += 1;
+            at += Duration::hours(1);
+        }
+        let slots = profile.cost.iter_mut().zip(profile.tokens.iter_mut()).zip(samples);
+        for ((cost, tokens), samples) in slots.filter(|(_, samples)| *samples > 0) {
+            *cost /= samples as f64;
+            *tokens /= samples as f64;
+        }
+
+        let history_days = ((until - truncate_hour(from)).num_hours().max(0) / 24) as u32;
+        Ok((profile, history_days))
+    }
+}
+
+fn week_slot(ts: DateTime<Utc>, tz: chrono_tz::Tz) -> usize {
+    let local = ts.with_timezone(&tz);
+    local.weekday().num_days_from_monday() as usize * 24 + local.hour() as usize
+}
+
+fn truncate_hour(ts: DateTime<Utc>) -> DateTime<Utc> {
+    ts - Duration::seconds(ts.timestamp().rem_euclid(3600))
+        - Duration::nanoseconds(ts.timestamp_subsec_nanos() as i64)
+}
+
+/// Keys `usage_hourly` rows are stored under.
+fn hour_key(ts: DateTime<Utc>) -> String {
+    ts.format("%Y-%m-%dT%H:00:00Z").to_string()
+}
+
+fn next_month(first: NaiveDate) -> NaiveDate {
+    let months = first.year() * 12 + first.month0() as i32 + 1;
+    NaiveDate::from_ymd_opt(months.div_euclid(12), months.rem_euclid(12) as u32 + 1, 1).unwrap_or(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::{message, session, temp_db};
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        calendar::parse_utc(timestamp).unwrap()
+    }
+
+    #[test]
+    fn day_starting_inside_an_hour_splits_it() {
+        let db = temp_db("forecast-half-hour");
+        // Midnight in Kolkata is 18:30 UTC.
+        db.set_timezone("Asia/Kolkata").unwrap();
+        db.upsert_session(&session("s1", "2026-03-03T07:00:00.000Z")).unwrap();
+        for (timestamp, cost) in [
+            // Tuesday a week ago, 12:30 local.
+            ("2026-03-03T07:00:00.000Z", 8.0),
+            // 23:40 and 00:10 local, in the same UTC hour.
+            ("2026-03-09T18:10:00.000Z", 1.0),
+            ("2026-03-09T18:40:00.000Z", 2.0),
+            ("2026-03-10T05:00:00.000Z", 4.0),
+        ] {
+            db.insert_message(&message("s1", Some(timestamp), timestamp, cost)).unwrap();
+        }
+
+        // Tuesday, 11:30 local.
+        let forecast = db.spend_forecast_at(at("2026-03-10T06:00:00.000Z")).unwrap();
+        assert!((forecast.today_cost - 6.0).abs() < 1e-9);
+        assert_eq!(forecast.today_tokens, 60);
+        assert!((forecast.month_cost - 15.0).abs() < 1e-9);
+        assert_eq!(forecast.history_days, 6);
+        // The hour that cost 8.0 last Tuesday is still to come today, and on
+        // the three Tuesdays left in March.
+        assert!((forecast.projected_today_cost - 14.0).abs() < 1e-9);
+        assert!((forecast.projected_month_cost - 47.0).abs() < 1e-9);
+        assert_eq!(forecast.projected_month_tokens, 120 + 4 * 30);
+    }
+
+    #[test]
+    fn partial_hours_are_prorated() {
+        let mut profile = HourlyProfile { cost: [0.0; HOURS_PER_WEEK], tokens: [0.0; HOURS_PER_WEEK] };
+        // Monday 10:00 and 11:00 UTC.
+        profile.cost[10] = 6.0;
+        profile.cost[11] = 2.0;
+        profile.tokens[10] = 600.0;
+
+        let (cost, tokens) = profile.expected(at("2026-03-09T10:30:00.000Z"), at("2026-03-09T11:15:00.000Z"), chrono_tz::UTC);
+        assert!((cost - 3.5).abs() < 1e-9);
+        assert!((tokens - 300.0).abs() < 1e-9);
+    }
+}
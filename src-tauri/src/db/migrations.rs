@@ -0,0 +1,733 @@
+use rusqlite::{Connection, Transaction};
+use std::path::{Path, PathBuf};
+
+/// A schema change applied once, in order. `version` is the value of
+/// `PRAGMA user_version` after the migration has run.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    up: fn(&Transaction) -> Result<(), String>,
+}
+
+/// Append-only: never edit a migration that has shipped, add a new one.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial sessions and messages schema",
+        up: initial_schema,
+    },
+    Migration {
+        version: 2,
+        description: "unique message keys",
+        up: unique_message_keys,
+    },
+    Migration {
+        version: 3,
+        description: "settings",
+        up: settings,
+    },
+    Migration {
+        version: 4,
+        description: "hourly usage rollups",
+        up: hourly_rollups,
+    },
+    Migration {
+        version: 5,
+        description: "session query indexes",
+        up: session_query_indexes,
+    },
+    Migration {
+        version: 6,
+        description: "transcript full-text search",
+        up: transcript_search,
+    },
+    Migration {
+        version: 7,
+        description: "per tool call records",
+        up: tool_calls,
+    },
+    Migration {
+        version: 8,
+        description: "session working directory and git branch",
+        up: session_cwd_and_branch,
+    },
+    Migration {
+        version: 9,
+        description: "anomaly alerts",
+        up: anomaly_alerts,
+    },
+    Migration {
+        version: 10,
+        description: "context size and compaction events",
+        up: context_tracking,
+    },
+    Migration {
+        version: 11,
+        description: "energy estimates",
+        up: energy_columns,
+    },
+    Migration {
+        version: 12,
+        description: "tool policy decisions",
+        up: policy_decisions,
+    },
+    Migration {
+        version: 13,
+        description: "session permission mode",
+        up: session_permission_mode,
+    },
+    Migration {
+        version: 14,
+        description: "pending reindex",
+        up: pending_reindex,
+    },
+    Migration {
+        version: 15,
+        description: "otlp export watermarks",
+        up: otlp_exported,
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn user_version(conn: &Connection) -> Result<u32, String> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+/// Brings the database at `db_path` up to the latest schema. Each pending
+/// migration runs in its own transaction. Before the first one, a backup of
+/// the current file is written next to it as `sessions.db.v<N>.bak`; once all
+/// have run it replaces the backup left by the previous upgrade.
+pub fn run(conn: &mut Connection, db_path: &Path) -> Result<(), String> {
+    let current = user_version(conn)?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(format!(
+            "Database schema v{} is newer than this version of Sonar supports (v{})",
+            current, latest
+        ));
+    }
+    if current == latest {
+        return Ok(());
+    }
+
+    // A brand-new database has nothing worth backing up.
+    let backup_path = match has_tables(conn)? {
+        true => Some(backup(conn, db_path, current)?),
+        false => None,
+    };
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        (migration.up)(&tx).map_err(|e| {
+            format!("Migration v{} ({}) failed: {}", migration.version, migration.description, e)
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        log::info!("Applied database migration v{}: {}", migration.version, migration.description);
+    }
+
+    if let Some(backup_path) = backup_path {
+        remove_older_backups(db_path, &backup_path);
+    }
+    Ok(())
+}
+
+fn backup(conn: &Connection, db_path: &Path, version: u32) -> Result<PathBuf, String> {
+    let backup_path = db_path.with_file_name(format!("{}.v{}.bak", db_file_name(db_path), version));
+    if backup_path.exists() {
+        std::fs::remove_file(&backup_path).map_err(|e| e.to_string())?;
+    }
+    conn.execute("VACUUM INTO ?1", [backup_path.to_string_lossy()])
+        .map_err(|e| format!("Failed to back up database before migration: {}", e))?;
+    Ok(backup_path)
+}
+
+/// Removes the backups of earlier upgrades, keeping `kept`.
+fn remove_older_backups(db_path: &Path, kept: &Path) {
+    let prefix = format!("{}.v", db_file_name(db_path));
+    let Some(Ok(entries)) = db_path.parent().map(std::fs::read_dir) else { return };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let is_backup = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".bak"));
+        if is_backup && path != kept {
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::warn!("Can't remove old backup {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+fn db_file_name(db_path: &Path) -> String {
+    db_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "sessions.db".to_string())
+}
+
+fn has_tables(conn: &Connection) -> Result<bool, String> {
+    let count: u32 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(count > 0)
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| e.to_string())?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(names.iter().any(|n| n == column))
+}
+
+/// The unversioned (v0) schema. Existing v0 databases already have these
+/// tables, so everything is `IF NOT EXISTS`.
+fn initial_schema(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sessions (
+            id TEXT PRIMARY KEY,
+            agent_id TEXT NOT NULL,
+            project TEXT NOT NULL DEFAULT '',
+            model TEXT NOT NULL DEFAULT 'unknown',
+            total_cost REAL NOT NULL DEFAULT 0.0,
+            total_tokens INTEGER NOT NULL DEFAULT 0,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_creation_tokens INTEGER NOT NULL DEFAULT 0,
+            duration_ms INTEGER NOT NULL DEFAULT 0,
+            status TEXT NOT NULL DEFAULT 'completed',
+            tool_calls INTEGER NOT NULL DEFAULT 0,
+            started_at TEXT NOT NULL,
+            ended_at TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            message_type TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            model TEXT,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_creation_tokens INTEGER NOT NULL DEFAULT 0,
+            cost REAL NOT NULL DEFAULT 0.0,
+            content_preview TEXT,
+            tool_name TEXT,
+            FOREIGN KEY (session_id) REFERENCES sessions(id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_sessions_started_at ON sessions(started_at);
+        CREATE INDEX IF NOT EXISTS idx_sessions_model ON sessions(model);
+        CREATE INDEX IF NOT EXISTS idx_messages_session ON messages(session_id);
+        CREATE INDEX IF NOT EXISTS idx_messages_timestamp ON messages(timestamp);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Drops rows duplicated by repeated imports, adds the `message_key` column
+/// and recomputes session totals from the surviving messages. Databases that
+/// already picked up the column before migrations existed skip the cleanup.
+/// The surviving rows keep a null key: it comes from the transcript line,
+/// which wasn't stored, so `Database::insert_message` fills it in when the
+/// line is read again.
+fn unique_message_keys(tx: &Transaction) -> Result<(), String> {
+    if !has_column(tx, "messages", "message_key")? {
+        tx.execute_batch(
+            "DELETE FROM messages WHERE id NOT IN (
+                 SELECT MIN(id) FROM messages
+                 GROUP BY session_id, message_type, timestamp, model,
+                          input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens
+             );
+             ALTER TABLE messages ADD COLUMN message_key TEXT;
+             UPDATE sessions SET
+                 total_cost = (SELECT SUM(cost) FROM messages WHERE session_id = sessions.id),
+                 input_tokens = (SELECT SUM(input_tokens) FROM messages WHERE session_id = sessions.id),
+                 output_tokens = (SELECT SUM(output_tokens) FROM messages WHERE session_id = sessions.id),
+                 cache_read_tokens = (SELECT SUM(cache_read_tokens) FROM messages WHERE session_id = sessions.id),
+                 cache_creation_tokens = (SELECT SUM(cache_creation_tokens) FROM messages WHERE session_id = sessions.id),
+                 total_tokens = (SELECT SUM(input_tokens + output_tokens + cache_read_tokens + cache_creation_tokens)
+                                 FROM messages WHERE session_id = sessions.id)
+             WHERE id IN (SELECT DISTINCT session_id FROM messages);",
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    tx.execute_batch("CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_key ON messages(message_key);")
+        .map_err(|e| e.to_string())
+}
+
+fn settings(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Hourly totals keyed by model, project and agent, kept in step with
+/// `messages` by `Database::insert_message`, so long-range charts survive the
+/// retention job. Backfills from stored messages and from sessions that have
+/// no message detail, attributed to their start hour.
+fn hourly_rollups(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS usage_hourly (
+            hour TEXT NOT NULL,
+            model TEXT NOT NULL DEFAULT 'unknown',
+            project TEXT NOT NULL DEFAULT '',
+            agent_id TEXT NOT NULL DEFAULT '',
+            messages INTEGER NOT NULL DEFAULT 0,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_creation_tokens INTEGER NOT NULL DEFAULT 0,
+            cost REAL NOT NULL DEFAULT 0.0,
+            PRIMARY KEY (hour, model, project, agent_id)
+        );
+
+        INSERT INTO usage_hourly (hour, model, project, agent_id, messages, input_tokens,
+            output_tokens, cache_read_tokens, cache_creation_tokens, cost)
+        SELECT strftime('%Y-%m-%dT%H:00:00Z', m.timestamp), COALESCE(m.model, 'unknown'),
+               COALESCE(s.project, ''), COALESCE(s.agent_id, ''), COUNT(*),
+               SUM(m.input_tokens), SUM(m.output_tokens), SUM(m.cache_read_tokens),
+               SUM(m.cache_creation_tokens), SUM(m.cost)
+        FROM messages m LEFT JOIN sessions s ON s.id = m.session_id
+        WHERE strftime('%Y-%m-%dT%H:00:00Z', m.timestamp) IS NOT NULL
+        GROUP BY 1, 2, 3, 4;
+
+        INSERT INTO usage_hourly (hour, model, project, agent_id, messages, input_tokens,
+            output_tokens, cache_read_tokens, cache_creation_tokens, cost)
+        SELECT strftime('%Y-%m-%dT%H:00:00Z', started_at), model, project, agent_id, 0,
+               SUM(input_tokens), SUM(output_tokens), SUM(cache_read_tokens),
+               SUM(cache_creation_tokens), SUM(total_cost)
+        FROM sessions
+        WHERE id NOT IN (SELECT DISTINCT session_id FROM messages)
+          AND strftime('%Y-%m-%dT%H:00:00Z', started_at) IS NOT NULL
+        GROUP BY 1, 2, 3, 4
+        ON CONFLICT(hour, model, project, agent_id) DO UPDATE SET
+            input_tokens = input_tokens + excluded.input_tokens,
+            output_tokens = output_tokens + excluded.output_tokens,
+            cache_read_tokens = cache_read_tokens + excluded.cache_read_tokens,
+            cache_creation_tokens = cache_creation_tokens + excluded.cache_creation_tokens,
+            cost = cost + excluded.cost;
+
+        CREATE INDEX IF NOT EXISTS idx_usage_hourly_model ON usage_hourly(model);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Indexes backing the `query_sessions` filters and sort keys.
+fn session_query_indexes(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_sessions_project ON sessions(project, started_at);
+        CREATE INDEX IF NOT EXISTS idx_sessions_agent ON sessions(agent_id, started_at);
+        CREATE INDEX IF NOT EXISTS idx_sessions_status ON sessions(status, started_at);
+        CREATE INDEX IF NOT EXISTS idx_sessions_cost ON sessions(total_cost, id);
+        CREATE INDEX IF NOT EXISTS idx_sessions_tokens ON sessions(total_tokens, id);
+        CREATE INDEX IF NOT EXISTS idx_sessions_duration ON sessions(duration_ms, id);
+        CREATE INDEX IF NOT EXISTS idx_sessions_started_id ON sessions(started_at, id);
+        CREATE INDEX IF NOT EXISTS idx_messages_tool ON messages(tool_name, session_id);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// `search_documents` holds one row per indexed transcript line and shares
+/// its id with the FTS5 rowid, which gives the index a unique key.
+fn transcript_search(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS search_documents (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            message_key TEXT NOT NULL UNIQUE,
+            message_type TEXT NOT NULL,
+            timestamp TEXT NOT NULL
+        );
+
+        CREATE VIRTUAL TABLE IF NOT EXISTS transcript_fts USING fts5(
+            text, tool_input, tool_result,
+            tokenize = 'porter unicode61'
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_search_documents_session ON search_documents(session_id);
+        CREATE INDEX IF NOT EXISTS idx_search_documents_timestamp ON search_documents(timestamp);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// One row per `tool_use`, completed by its `tool_result`. Filled from
+/// transcripts as they are imported or watched; stored messages don't keep
+/// enough detail to backfill it.
+fn tool_calls(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tool_calls (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            tool_use_id TEXT NOT NULL UNIQUE,
+            session_id TEXT NOT NULL,
+            message_key TEXT,
+            name TEXT NOT NULL,
+            server TEXT,
+            input_bytes INTEGER NOT NULL DEFAULT 0,
+            started_at TEXT NOT NULL,
+            finished_at TEXT,
+            duration_ms INTEGER,
+            is_error INTEGER,
+            cost REAL NOT NULL DEFAULT 0.0
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_tool_calls_session ON tool_calls(session_id);
+        CREATE INDEX IF NOT EXISTS idx_tool_calls_name ON tool_calls(name, started_at);
+        CREATE INDEX IF NOT EXISTS idx_tool_calls_started_at ON tool_calls(started_at);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn session_cwd_and_branch(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "ALTER TABLE sessions ADD COLUMN cwd TEXT;
+        ALTER TABLE sessions ADD COLUMN git_branch TEXT;
+
+        CREATE INDEX IF NOT EXISTS idx_sessions_cwd ON sessions(cwd, started_at);
+        CREATE INDEX IF NOT EXISTS idx_sessions_branch ON sessions(git_branch, started_at);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// `subject` is the session id for live session alerts and the local date for
+/// completed day alerts, so each is raised at most once per metric.
+fn anomaly_alerts(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS anomaly_alerts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            project TEXT NOT NULL,
+            subject TEXT NOT NULL,
+            metric TEXT NOT NULL,
+            value REAL NOT NULL,
+            baseline_median REAL NOT NULL,
+            baseline_mad REAL NOT NULL,
+            score REAL NOT NULL,
+            reason TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            UNIQUE(kind, project, subject, metric)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_anomaly_alerts_created_at ON anomaly_alerts(created_at);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Context size is what the model was sent: uncached input plus cache reads
+/// and writes, so it can be backfilled from stored messages.
+fn context_tracking(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "ALTER TABLE messages ADD COLUMN context_tokens INTEGER NOT NULL DEFAULT 0;
+        UPDATE messages SET context_tokens = input_tokens + cache_read_tokens + cache_creation_tokens;
+
+        CREATE TABLE IF NOT EXISTS compaction_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            event_key TEXT NOT NULL UNIQUE,
+            kind TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            trigger TEXT,
+            pre_tokens INTEGER,
+            summary TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_compaction_events_session ON compaction_events(session_id, timestamp);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Stored usage is backfilled with the default factors as they were when
+/// energy estimates were added; saving other factors recomputes it.
+fn energy_columns(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "ALTER TABLE messages ADD COLUMN energy_wh REAL NOT NULL DEFAULT 0.0;
+        ALTER TABLE sessions ADD COLUMN energy_wh REAL NOT NULL DEFAULT 0.0;
+        ALTER TABLE usage_hourly ADD COLUMN energy_wh REAL NOT NULL DEFAULT 0.0;",
+    )
+    .map_err(|e| e.to_string())?;
+    for table in ["messages", "sessions", "usage_hourly"] {
+        tx.execute(
+            &format!(
+                "UPDATE {} SET energy_wh = (input_tokens + output_tokens + cache_read_tokens
+                 + cache_creation_tokens) * CASE
+                     WHEN instr(COALESCE(model, ''), 'opus') > 0 THEN 0.000004
+                     WHEN instr(COALESCE(model, ''), 'sonnet') > 0 THEN 0.000001
+                     WHEN instr(COALESCE(model, ''), 'haiku') > 0 THEN 0.0000005
+                     ELSE 0.000001
+                 END",
+                table
+            ),
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn policy_decisions(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS policy_decisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            created_at TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            agent_id TEXT,
+            tool_name TEXT NOT NULL,
+            tool_input TEXT NOT NULL,
+            decision TEXT NOT NULL,
+            rule TEXT,
+            reason TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_policy_decisions_created_at ON policy_decisions(created_at);
+        CREATE INDEX IF NOT EXISTS idx_policy_decisions_session ON policy_decisions(session_id);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Null for sessions not started by the app, and for those before it was
+/// recorded.
+fn session_permission_mode(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch("ALTER TABLE sessions ADD COLUMN permission_mode TEXT;")
+        .map_err(|e| e.to_string())
+}
+
+/// Sessions stored before search, tool calls or message keys existed, whose
+/// transcripts are read again by `collector::import::reindex_pending`.
+fn pending_reindex(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS pending_reindex (
+            session_id TEXT PRIMARY KEY
+        );
+
+        INSERT OR IGNORE INTO pending_reindex (session_id)
+        SELECT id FROM sessions s
+        WHERE NOT EXISTS (SELECT 1 FROM search_documents d WHERE d.session_id = s.id)
+           OR EXISTS (SELECT 1 FROM messages m WHERE m.session_id = s.id AND m.message_key IS NULL)
+           OR (s.tool_calls > 0
+               AND NOT EXISTS (SELECT 1 FROM tool_calls t WHERE t.session_id = s.id));",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// How far each session has been sent to the OTLP collector. Sessions that
+/// ended before the background export's clock were sent up to their end.
+fn otlp_exported(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS otlp_exported (
+            session_id TEXT PRIMARY KEY,
+            exported_until TEXT NOT NULL
+        );
+
+        INSERT OR IGNORE INTO otlp_exported (session_id, exported_until)
+        SELECT id, ended_at FROM sessions
+        WHERE ended_at <= (SELECT value FROM settings WHERE key = 'otlp_exported_until' AND value != '');",
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::temp_path;
+
+    /// The schema `Database::open` created before migrations existed.
+    const V0_SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS sessions (
+            id TEXT PRIMARY KEY,
+            agent_id TEXT NOT NULL,
+            project TEXT NOT NULL DEFAULT '',
+            model TEXT NOT NULL DEFAULT 'unknown',
+            total_cost REAL NOT NULL DEFAULT 0.0,
+            total_tokens INTEGER NOT NULL DEFAULT 0,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_creation_tokens INTEGER NOT NULL DEFAULT 0,
+            duration_ms INTEGER NOT NULL DEFAULT 0,
+            status TEXT NOT NULL DEFAULT 'completed',
+            tool_calls INTEGER NOT NULL DEFAULT 0,
+            started_at TEXT NOT NULL,
+            ended_at TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            message_type TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            model TEXT,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_creation_tokens INTEGER NOT NULL DEFAULT 0,
+            cost REAL NOT NULL DEFAULT 0.0,
+            content_preview TEXT,
+            tool_name TEXT,
+            FOREIGN KEY (session_id) REFERENCES sessions(id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_sessions_started_at ON sessions(started_at);
+        CREATE INDEX IF NOT EXISTS idx_sessions_model ON sessions(model);
+        CREATE INDEX IF NOT EXISTS idx_messages_session ON messages(session_id);
+        CREATE INDEX IF NOT EXISTS idx_messages_timestamp ON messages(timestamp);";
+
+    /// Two sessions; the first was imported twice, so each of its messages
+    /// is stored twice and its totals are doubled.
+    fn v0_database(path: &Path) -> Connection {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(V0_SCHEMA).unwrap();
+        conn.execute_batch(
+            "INSERT INTO sessions (id, agent_id, project, model, total_cost, total_tokens,
+                 input_tokens, output_tokens, started_at, ended_at)
+             VALUES ('s1', 'imported', '/work/app', 'claude-sonnet-4-5', 3.0, 600, 200, 400,
+                     '2026-01-05T10:00:00.000Z', '2026-01-05T10:05:00.000Z'),
+                    ('s2', 'agent-1', '/work/lib', 'claude-opus-4-1', 2.0, 150, 50, 100,
+                     '2026-01-06T09:00:00.000Z', NULL);
+
+             INSERT INTO messages (session_id, message_type, timestamp, model, input_tokens,
+                 output_tokens, cost, content_preview, tool_name)
+             VALUES ('s1', 'assistant', '2026-01-05T10:00:00.000Z', 'claude-sonnet-4-5', 50, 100, 0.5, 'first', NULL),
+                    ('s1', 'assistant', '2026-01-05T10:04:00.000Z', 'claude-sonnet-4-5', 50, 100, 1.0, 'second', 'Bash'),
+                    ('s1', 'assistant', '2026-01-05T10:00:00.000Z', 'claude-sonnet-4-5', 50, 100, 0.5, 'first', NULL),
+                    ('s1', 'assistant', '2026-01-05T10:04:00.000Z', 'claude-sonnet-4-5', 50, 100, 1.0, 'second', 'Bash'),
+                    ('s2', 'assistant', '2026-01-06T09:00:00.000Z', 'claude-opus-4-1', 50, 100, 2.0, 'only', NULL);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    fn backups(path: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".bak"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn migrates_v0_database_keeping_its_data() {
+        let path = temp_path("migrate-v0");
+        let mut conn = v0_database(&path);
+
+        run(&mut conn, &path).unwrap();
+
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM sessions"), 2);
+        // Duplicates are gone and the doubled totals follow.
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM messages"), 3);
+        let (cost, tokens): (f64, i64) = conn
+            .query_row("SELECT total_cost, total_tokens FROM sessions WHERE id = 's1'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert!((cost - 1.5).abs() < 1e-9);
+        assert_eq!(tokens, 300);
+        let preview: String = conn
+            .query_row("SELECT content_preview FROM messages WHERE tool_name = 'Bash'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(preview, "second");
+
+        // Rollups are backfilled from the surviving messages.
+        let rolled: f64 = conn.query_row("SELECT SUM(cost) FROM usage_hourly", [], |row| row.get(0)).unwrap();
+        assert!((rolled - 3.5).abs() < 1e-9);
+        assert_eq!(count(&conn, "SELECT SUM(messages) FROM usage_hourly"), 3);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM sqlite_master WHERE name = 'daily_usage'"), 0);
+
+        // Added columns start out empty.
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM sessions WHERE cwd IS NULL AND permission_mode IS NULL"), 2);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM messages WHERE message_key IS NULL"), 3);
+        // Their transcripts are read again for search and tool calls.
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM pending_reindex"), 2);
+    }
+
+    #[test]
+    fn backs_up_once_and_keeps_only_the_latest_backup() {
+        let path = temp_path("migrate-backup");
+        let mut conn = v0_database(&path);
+        // Left by an earlier upgrade.
+        std::fs::write(path.with_file_name("sessions.db.v7.bak"), "old").unwrap();
+
+        run(&mut conn, &path).unwrap();
+
+        assert_eq!(backups(&path), ["sessions.db.v0.bak"]);
+        // The backup is the untouched v0 file.
+        let v0 = Connection::open(path.with_file_name("sessions.db.v0.bak")).unwrap();
+        assert_eq!(user_version(&v0).unwrap(), 0);
+        assert_eq!(count(&v0, "SELECT COUNT(*) FROM messages"), 5);
+    }
+
+    #[test]
+    fn backfills_energy_with_the_factors_of_the_time() {
+        let path = temp_path("migrate-energy");
+        let mut conn = v0_database(&path);
+
+        run(&mut conn, &path).unwrap();
+
+        let energy = |sql: &str| -> f64 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        // s2 is 150 opus tokens, s1 300 sonnet tokens.
+        assert!((energy("SELECT energy_wh FROM sessions WHERE id = 's2'") - 150.0 * 0.000004).abs() < 1e-12);
+        assert!((energy("SELECT energy_wh FROM sessions WHERE id = 's1'") - 300.0 * 0.000001).abs() < 1e-12);
+        assert!((energy("SELECT SUM(energy_wh) FROM usage_hourly") - (300.0 * 0.000001 + 150.0 * 0.000004)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn second_run_is_a_no_op() {
+        let path = temp_path("migrate-twice");
+        let mut conn = v0_database(&path);
+        run(&mut conn, &path).unwrap();
+        let messages = count(&conn, "SELECT COUNT(*) FROM messages");
+
+        run(&mut conn, &path).unwrap();
+
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM messages"), messages);
+        assert_eq!(backups(&path), ["sessions.db.v0.bak"]);
+    }
+
+    #[test]
+    fn new_database_is_not_backed_up() {
+        let path = temp_path("migrate-new");
+        let mut conn = Connection::open(&path).unwrap();
+
+        run(&mut conn, &path).unwrap();
+
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        assert!(backups(&path).is_empty());
+    }
+
+    #[test]
+    fn refuses_newer_schema() {
+        let path = temp_path("migrate-newer");
+        let mut conn = Connection::open(&path).unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+
+        assert!(run(&mut conn, &path).is_err());
+    }
+}
@@ -1,7 +1,35 @@
-use rusqlite::{params, Connection};
+pub mod anomaly;
+pub mod blocks;
+pub mod breakdown;
+pub mod cache;
+pub mod calendar;
+pub mod columnar;
+pub mod context;
+pub mod energy;
+pub mod export;
+pub mod forecast;
+mod migrations;
+pub mod policy;
+pub mod query;
+pub mod reindex;
+pub mod report;
+pub mod retention;
+pub mod search;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod tools;
+
+use calendar::Bucket;
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionRecord {
@@ -20,12 +48,24 @@ pub struct SessionRecord {
     pub tool_calls: u32,
     pub started_at: String,
     pub ended_at: Option<String>,
+    /// Working directory and git branch Claude Code reported for the session.
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub git_branch: Option<String>,
+    /// Estimated inference energy, from the configured per-model factors.
+    #[serde(default)]
+    pub energy_wh: f64,
+    /// Permission mode the app started the agent with.
+    #[serde(default)]
+    pub permission_mode: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageRecord {
     pub id: i64,
     pub session_id: String,
+    pub message_key: Option<String>,
     pub message_type: String,
     pub timestamp: String,
     pub model: Option<String>,
@@ -36,14 +76,22 @@ pub struct MessageRecord {
     pub cost: f64,
     pub content_preview: Option<String>,
     pub tool_name: Option<String>,
+    /// Tokens the model was sent for this message: input plus cache reads and writes.
+    #[serde(default)]
+    pub context_tokens: u64,
+    #[serde(default)]
+    pub energy_wh: f64,
 }
 
+/// Usage for one calendar bucket; `date` is the bucket's first local day.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DailyCost {
     pub date: String,
     pub cost: f64,
     pub tokens: u64,
     pub sessions: u32,
+    pub energy_wh: f64,
+    pub co2_g: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,10 +102,33 @@ pub struct ModelStats {
     pub session_count: u32,
     pub avg_cost_per_session: f64,
     pub avg_tokens_per_session: u64,
+    pub energy_wh: f64,
+    pub co2_g: f64,
+}
+
+const TIMEZONE_KEY: &str = "timezone";
+
+/// Column list matching `session_from_row`.
+pub(crate) const SESSION_COLUMNS: &str = "id, agent_id, project, model, total_cost, total_tokens,
+    input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens,
+    duration_ms, status, tool_calls, started_at, ended_at, cwd, git_branch, energy_wh,
+    permission_mode";
+
+pub(crate) fn session_from_row(row: &rusqlite::Row) -> rusqlite::Result<SessionRecord> {
+    Ok(SessionRecord {
+        id: row.get(0)?, agent_id: row.get(1)?, project: row.get(2)?,
+        model: row.get(3)?, total_cost: row.get(4)?, total_tokens: row.get(5)?,
+        input_tokens: row.get(6)?, output_tokens: row.get(7)?,
+        cache_read_tokens: row.get(8)?, cache_creation_tokens: row.get(9)?,
+        duration_ms: row.get(10)?, status: row.get(11)?, tool_calls: row.get(12)?,
+        started_at: row.get(13)?, ended_at: row.get(14)?, cwd: row.get(15)?,
+        git_branch: row.get(16)?, energy_wh: row.get(17)?, permission_mode: row.get(18)?,
+    })
 }
 
 pub struct Database {
     conn: Arc<Mutex<Connection>>,
+    path: PathBuf,
 }
 
 impl Database {
@@ -67,51 +138,29 @@ impl Database {
             std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
         }
 
-        let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
-
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS sessions (
-                id TEXT PRIMARY KEY,
-                agent_id TEXT NOT NULL,
-                project TEXT NOT NULL DEFAULT '',
-                model TEXT NOT NULL DEFAULT 'unknown',
-                total_cost REAL NOT NULL DEFAULT 0.0,
-                total_tokens INTEGER NOT NULL DEFAULT 0,
-                input_tokens INTEGER NOT NULL DEFAULT 0,
-                output_tokens INTEGER NOT NULL DEFAULT 0,
-                cache_read_tokens INTEGER NOT NULL DEFAULT 0,
-                cache_creation_tokens INTEGER NOT NULL DEFAULT 0,
-                duration_ms INTEGER NOT NULL DEFAULT 0,
-                status TEXT NOT NULL DEFAULT 'completed',
-                tool_calls INTEGER NOT NULL DEFAULT 0,
-                started_at TEXT NOT NULL,
-                ended_at TEXT
-            );
-
-            CREATE TABLE IF NOT EXISTS messages (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                session_id TEXT NOT NULL,
-                message_type TEXT NOT NULL,
-                timestamp TEXT NOT NULL,
-                model TEXT,
-                input_tokens INTEGER NOT NULL DEFAULT 0,
-                output_tokens INTEGER NOT NULL DEFAULT 0,
-                cache_read_tokens INTEGER NOT NULL DEFAULT 0,
-                cache_creation_tokens INTEGER NOT NULL DEFAULT 0,
-                cost REAL NOT NULL DEFAULT 0.0,
-                content_preview TEXT,
-                tool_name TEXT,
-                FOREIGN KEY (session_id) REFERENCES sessions(id)
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_sessions_started_at ON sessions(started_at);
-            CREATE INDEX IF NOT EXISTS idx_sessions_model ON sessions(model);
-            CREATE INDEX IF NOT EXISTS idx_messages_session ON messages(session_id);
-            CREATE INDEX IF NOT EXISTS idx_messages_timestamp ON messages(timestamp);",
-        )
-        .map_err(|e| e.to_string())?;
+        Self::open(&db_path)
+    }
 
-        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    pub fn open(db_path: &Path) -> Result<Self, String> {
+        let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+        // The app, the CLI and the hook helper's socket handler can all have
+        // the file open: WAL lets them read while one writes, and a writer
+        // waits for another instead of failing with "database is locked".
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+        migrations::run(&mut conn, db_path)?;
+
+        Ok(Self { conn: Arc::new(Mutex::new(conn)), path: db_path.to_path_buf() })
+    }
+
+    /// A read-only connection of its own, for long reads such as exports
+    /// that shouldn't hold up everything else sharing `conn`. WAL lets it
+    /// read a snapshot while others write.
+    pub(crate) fn read_connection(&self) -> Result<Connection, String> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let conn = Connection::open_with_flags(&self.path, flags).map_err(|e| e.to_string())?;
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+        Ok(conn)
     }
 
     pub fn upsert_session(&self, r: &SessionRecord) -> Result<(), String> {
@@ -119,54 +168,102 @@ impl Database {
         conn.execute(
             "INSERT INTO sessions (id, agent_id, project, model, total_cost, total_tokens,
              input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens,
-             duration_ms, status, tool_calls, started_at, ended_at)
-             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15)
+             duration_ms, status, tool_calls, started_at, ended_at, cwd, git_branch, energy_wh,
+             permission_mode)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19)
              ON CONFLICT(id) DO UPDATE SET
              model=excluded.model, total_cost=excluded.total_cost,
              total_tokens=excluded.total_tokens, input_tokens=excluded.input_tokens,
              output_tokens=excluded.output_tokens, cache_read_tokens=excluded.cache_read_tokens,
              cache_creation_tokens=excluded.cache_creation_tokens, duration_ms=excluded.duration_ms,
-             status=excluded.status, tool_calls=excluded.tool_calls, ended_at=excluded.ended_at",
+             status=excluded.status, tool_calls=excluded.tool_calls, ended_at=excluded.ended_at,
+             cwd=COALESCE(excluded.cwd, cwd), git_branch=COALESCE(excluded.git_branch, git_branch),
+             energy_wh=excluded.energy_wh,
+             permission_mode=COALESCE(excluded.permission_mode, permission_mode)",
             params![r.id, r.agent_id, r.project, r.model, r.total_cost, r.total_tokens,
                     r.input_tokens, r.output_tokens, r.cache_read_tokens, r.cache_creation_tokens,
-                    r.duration_ms, r.status, r.tool_calls, r.started_at, r.ended_at],
+                    r.duration_ms, r.status, r.tool_calls, r.started_at, r.ended_at,
+                    r.cwd, r.git_branch, r.energy_wh, r.permission_mode],
         ).map_err(|e| e.to_string())?;
         Ok(())
     }
 
-    pub fn insert_message(&self, m: &MessageRecord) -> Result<(), String> {
+    /// Marks running sessions with no transcript line since `idle_since` as
+    /// completed. A line arriving later sets the session running again.
+    pub fn complete_idle_sessions(&self, idle_since: &str) -> Result<usize, String> {
         let conn = self.conn.lock().map_err(|e| e.to_string())?;
         conn.execute(
-            "INSERT INTO messages (session_id, message_type, timestamp, model,
+            "UPDATE sessions SET status = 'completed'
+             WHERE status = 'running' AND COALESCE(ended_at, started_at) < ?1",
+            params![idle_since],
+        ).map_err(|e| e.to_string())
+    }
+
+    /// Inserts a message unless one with the same `message_key` is already
+    /// stored, and adds it to the hourly rollup of its session's project and
+    /// agent. The session row must exist. Returns `true` when a new row was written.
+    ///
+    /// Rows stored before messages had keys are claimed instead: a keyed
+    /// message matching one of them field for field gives it its key, so
+    /// reading the transcript again doesn't count it twice.
+    pub fn insert_message(&self, m: &MessageRecord) -> Result<bool, String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        if claim_legacy_message(&tx, m)? {
+            tx.commit().map_err(|e| e.to_string())?;
+            return Ok(false);
+        }
+        let inserted = tx.execute(
+            "INSERT INTO messages (session_id, message_key, message_type, timestamp, model,
              input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens,
-             cost, content_preview, tool_name)
-             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11)",
-            params![m.session_id, m.message_type, m.timestamp, m.model,
+             cost, content_preview, tool_name, context_tokens, energy_wh)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14)
+             ON CONFLICT(message_key) DO NOTHING",
+            params![m.session_id, m.message_key, m.message_type, m.timestamp, m.model,
                     m.input_tokens, m.output_tokens, m.cache_read_tokens,
-                    m.cache_creation_tokens, m.cost, m.content_preview, m.tool_name],
+                    m.cache_creation_tokens, m.cost, m.content_preview, m.tool_name,
+                    m.context_tokens, m.energy_wh],
         ).map_err(|e| e.to_string())?;
-        Ok(())
+
+        if inserted > 0 {
+            tx.execute(
+                "INSERT INTO usage_hourly (hour, model, project, agent_id, messages, input_tokens,
+                 output_tokens, cache_read_tokens, cache_creation_tokens, cost, energy_wh)
+                 SELECT strftime('%Y-%m-%dT%H:00:00Z', ?2), COALESCE(?3, 'unknown'),
+                        project, agent_id, 1, ?4, ?5, ?6, ?7, ?8, ?9
+                 FROM sessions WHERE id = ?1 AND strftime('%Y-%m-%dT%H:00:00Z', ?2) IS NOT NULL
+                 ON CONFLICT(hour, model, project, agent_id) DO UPDATE SET
+                 messages = messages + 1,
+                 input_tokens = input_tokens + excluded.input_tokens,
+                 output_tokens = output_tokens + excluded.output_tokens,
+                 cache_read_tokens = cache_read_tokens + excluded.cache_read_tokens,
+                 cache_creation_tokens = cache_creation_tokens + excluded.cache_creation_tokens,
+                 cost = cost + excluded.cost,
+                 energy_wh = energy_wh + excluded.energy_wh",
+                params![m.session_id, m.timestamp, m.model, m.input_tokens, m.output_tokens,
+                        m.cache_read_tokens, m.cache_creation_tokens, m.cost, m.energy_wh],
+            ).map_err(|e| e.to_string())?;
+        }
+
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(inserted > 0)
+    }
+
+    /// Gives `m`'s key to a matching keyless row without storing anything
+    /// new. Returns `true` when a row was claimed.
+    pub fn claim_message_key(&self, m: &MessageRecord) -> Result<bool, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        claim_legacy_message(&conn, m)
     }
 
     pub fn get_recent_sessions(&self, limit: u32) -> Result<Vec<SessionRecord>, String> {
         let conn = self.conn.lock().map_err(|e| e.to_string())?;
-        let mut stmt = conn.prepare(
-            "SELECT id, agent_id, project, model, total_cost, total_tokens,
-             input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens,
-             duration_ms, status, tool_calls, started_at, ended_at
-             FROM sessions ORDER BY started_at DESC LIMIT ?1"
-        ).map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM sessions ORDER BY started_at DESC LIMIT ?1",
+            SESSION_COLUMNS
+        )).map_err(|e| e.to_string())?;
 
-        let rows = stmt.query_map(params![limit], |row| {
-            Ok(SessionRecord {
-                id: row.get(0)?, agent_id: row.get(1)?, project: row.get(2)?,
-                model: row.get(3)?, total_cost: row.get(4)?, total_tokens: row.get(5)?,
-                input_tokens: row.get(6)?, output_tokens: row.get(7)?,
-                cache_read_tokens: row.get(8)?, cache_creation_tokens: row.get(9)?,
-                duration_ms: row.get(10)?, status: row.get(11)?, tool_calls: row.get(12)?,
-                started_at: row.get(13)?, ended_at: row.get(14)?,
-            })
-        }).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(params![limit], session_from_row).map_err(|e| e.to_string())?;
 
         rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
     }
@@ -174,58 +271,150 @@ impl Database {
     pub fn get_session_messages(&self, session_id: &str) -> Result<Vec<MessageRecord>, String> {
         let conn = self.conn.lock().map_err(|e| e.to_string())?;
         let mut stmt = conn.prepare(
-            "SELECT id, session_id, message_type, timestamp, model,
+            "SELECT id, session_id, message_key, message_type, timestamp, model,
              input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens,
-             cost, content_preview, tool_name
+             cost, content_preview, tool_name, context_tokens, energy_wh
              FROM messages WHERE session_id = ?1 ORDER BY timestamp ASC"
         ).map_err(|e| e.to_string())?;
 
         let rows = stmt.query_map(params![session_id], |row| {
             Ok(MessageRecord {
-                id: row.get(0)?, session_id: row.get(1)?, message_type: row.get(2)?,
-                timestamp: row.get(3)?, model: row.get(4)?, input_tokens: row.get(5)?,
-                output_tokens: row.get(6)?, cache_read_tokens: row.get(7)?,
-                cache_creation_tokens: row.get(8)?, cost: row.get(9)?,
-                content_preview: row.get(10)?, tool_name: row.get(11)?,
+                id: row.get(0)?, session_id: row.get(1)?, message_key: row.get(2)?,
+                message_type: row.get(3)?, timestamp: row.get(4)?, model: row.get(5)?,
+                input_tokens: row.get(6)?, output_tokens: row.get(7)?,
+                cache_read_tokens: row.get(8)?, cache_creation_tokens: row.get(9)?,
+                cost: row.get(10)?, content_preview: row.get(11)?, tool_name: row.get(12)?,
+                context_tokens: row.get(13)?, energy_wh: row.get(14)?,
             })
         }).map_err(|e| e.to_string())?;
 
         rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
     }
 
+    pub fn get_timezone(&self) -> Result<Tz, String> {
+        Ok(calendar::resolve_timezone(self.get_setting(TIMEZONE_KEY)?.as_deref()))
+    }
+
+    /// Sets the IANA timezone used for day/week/month boundaries. An empty
+    /// name goes back to following the system timezone.
+    pub fn set_timezone(&self, name: &str) -> Result<(), String> {
+        if !name.is_empty() {
+            name.parse::<Tz>().map_err(|_| format!("Unknown timezone: {}", name))?;
+        }
+        self.set_setting(TIMEZONE_KEY, name)
+    }
+
     pub fn get_daily_costs(&self, days: u32) -> Result<Vec<DailyCost>, String> {
+        self.get_cost_buckets(Bucket::Day, days)
+    }
+
+    /// Cost, tokens and sessions started per calendar bucket in the configured
+    /// timezone, covering the current bucket and `periods` before it. Usage is
+    /// placed by the local time of its UTC hour; an hour a bucket boundary
+    /// falls inside (zones with half-hour offsets) is split by the timestamps
+    /// of its messages, with whatever retention pruned left at the hour start.
+    pub fn get_cost_buckets(&self, bucket: Bucket, periods: u32) -> Result<Vec<DailyCost>, String> {
+        let tz = self.get_timezone()?;
+        let energy = self.get_energy_settings()?;
+        let start_date = calendar::range_start(chrono::Utc::now(), tz, bucket, periods);
+        let start = calendar::local_midnight_utc(start_date, tz);
+        let first_hour = start.format("%Y-%m-%dT%H:00:00Z").to_string();
+        let start = calendar::format_utc(start);
+
         let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut buckets: BTreeMap<NaiveDate, DailyCost> = BTreeMap::new();
+
         let mut stmt = conn.prepare(
-            "SELECT DATE(started_at) as day, SUM(total_cost), SUM(total_tokens), COUNT(*)
-             FROM sessions
-             WHERE started_at >= DATE('now', ?1)
-             GROUP BY day ORDER BY day ASC"
+            "SELECT hour, SUM(cost),
+             SUM(input_tokens + output_tokens + cache_read_tokens + cache_creation_tokens),
+             SUM(energy_wh)
+             FROM usage_hourly WHERE hour >= ?1 GROUP BY hour"
+        ).map_err(|e| e.to_string())?;
+        let hours = stmt.query_map(params![first_hour], |row| {
+            Ok((
+                row.get::<_, String>(0)?, row.get::<_, f64>(1)?,
+                row.get::<_, u64>(2)?, row.get::<_, f64>(3)?,
+            ))
+        }).map_err(|e| e.to_string())?.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+        let mut split = conn.prepare(
+            "SELECT timestamp, cost,
+             input_tokens + output_tokens + cache_read_tokens + cache_creation_tokens, energy_wh
+             FROM messages WHERE timestamp >= ?1 AND timestamp < ?2"
         ).map_err(|e| e.to_string())?;
+        for (hour, mut cost, mut tokens, mut energy_wh) in hours {
+            let Some(ts) = calendar::parse_utc(&hour) else { continue };
+            let end = ts + chrono::Duration::hours(1);
+            let date = calendar::bucket_start(ts, tz, bucket);
+            if calendar::bucket_start(end - chrono::Duration::milliseconds(1), tz, bucket) != date {
+                let messages = split.query_map(
+                    params![calendar::format_utc(ts), calendar::format_utc(end)],
+                    |row| Ok((
+                        row.get::<_, String>(0)?, row.get::<_, f64>(1)?,
+                        row.get::<_, u64>(2)?, row.get::<_, f64>(3)?,
+                    )),
+                ).map_err(|e| e.to_string())?;
+                for message in messages {
+                    let (timestamp, m_cost, m_tokens, m_energy) = message.map_err(|e| e.to_string())?;
+                    let Some(at) = calendar::parse_utc(&timestamp) else { continue };
+                    let entry = bucket_entry(&mut buckets, calendar::bucket_start(at, tz, bucket));
+                    entry.cost += m_cost;
+                    entry.tokens += m_tokens;
+                    entry.energy_wh += m_energy;
+                    entry.co2_g += energy.co2_grams(m_energy);
+                    cost -= m_cost;
+                    tokens = tokens.saturating_sub(m_tokens);
+                    energy_wh -= m_energy;
+                }
+            }
+            let entry = bucket_entry(&mut buckets, date);
+            entry.cost += cost;
+            entry.tokens += tokens;
+            entry.energy_wh += energy_wh;
+            entry.co2_g += energy.co2_grams(energy_wh);
+        }
 
-        let offset = format!("-{} days", days);
-        let rows = stmt.query_map(params![offset], |row| {
-            Ok(DailyCost {
-                date: row.get(0)?, cost: row.get(1)?, tokens: row.get(2)?, sessions: row.get(3)?,
-            })
-        }).map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT started_at FROM sessions WHERE started_at >= ?1")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![start], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?;
+        for row in rows {
+            if let Some(ts) = calendar::parse_utc(&row.map_err(|e| e.to_string())?) {
+                bucket_entry(&mut buckets, calendar::bucket_start(ts, tz, bucket)).sessions += 1;
+            }
+        }
 
-        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+        // The first hour may reach back before local midnight.
+        Ok(buckets.split_off(&start_date).into_values().collect())
     }
 
     pub fn get_model_stats(&self) -> Result<Vec<ModelStats>, String> {
+        let energy = self.get_energy_settings()?;
         let conn = self.conn.lock().map_err(|e| e.to_string())?;
         let mut stmt = conn.prepare(
-            "SELECT model, SUM(total_cost), SUM(total_tokens), COUNT(*),
-             AVG(total_cost), AVG(total_tokens)
-             FROM sessions WHERE model != 'unknown'
-             GROUP BY model ORDER BY SUM(total_cost) DESC"
+            "SELECT u.model, SUM(u.cost),
+             SUM(u.input_tokens + u.output_tokens + u.cache_read_tokens + u.cache_creation_tokens),
+             COALESCE(s.sessions, 0), SUM(u.energy_wh)
+             FROM usage_hourly u
+             LEFT JOIN (SELECT model, COUNT(*) as sessions FROM sessions GROUP BY model) s
+             ON s.model = u.model
+             WHERE u.model != 'unknown'
+             GROUP BY u.model ORDER BY SUM(u.cost) DESC"
         ).map_err(|e| e.to_string())?;
 
         let rows = stmt.query_map([], |row| {
+            let total_cost: f64 = row.get(1)?;
+            let total_tokens: u64 = row.get(2)?;
+            let session_count: u32 = row.get(3)?;
+            let energy_wh: f64 = row.get(4)?;
+            let sessions = session_count.max(1);
             Ok(ModelStats {
-                model: row.get(0)?, total_cost: row.get(1)?, total_tokens: row.get(2)?,
-                session_count: row.get(3)?, avg_cost_per_session: row.get(4)?,
-                avg_tokens_per_session: row.get::<_, f64>(5).map(|v| v as u64)?,
+                model: row.get(0)?, total_cost, total_tokens, session_count,
+                avg_cost_per_session: total_cost / sessions as f64,
+                avg_tokens_per_session: total_tokens / sessions as u64,
+                energy_wh,
+                co2_g: energy.co2_grams(energy_wh),
             })
         }).map_err(|e| e.to_string())?;
 
@@ -233,12 +422,31 @@ impl Database {
     }
 
     pub fn get_total_cost_today(&self) -> Result<f64, String> {
+        Ok(self
+            .get_cost_buckets(Bucket::Day, 0)?
+            .iter()
+            .fold(0.0, |total, d| total + d.cost))
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>, String> {
         let conn = self.conn.lock().map_err(|e| e.to_string())?;
-        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
-        conn.query_row(
-            "SELECT COALESCE(SUM(total_cost), 0.0) FROM sessions WHERE started_at >= ?1",
-            params![today], |row| row.get(0),
-        ).map_err(|e| e.to_string())
+        let mut stmt = conn
+            .prepare("SELECT value FROM settings WHERE key = ?1")
+            .map_err(|e| e.to_string())?;
+        let mut rows = stmt
+            .query_map(params![key], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        rows.next().transpose().map_err(|e| e.to_string())
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
     }
 
     pub fn session_exists(&self, id: &str) -> Result<bool, String> {
@@ -248,9 +456,228 @@ impl Database {
         ).map_err(|e| e.to_string())?;
         Ok(count > 0)
     }
+
+    pub fn get_session(&self, id: &str) -> Result<Option<SessionRecord>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            &format!("SELECT {} FROM sessions WHERE id = ?1", SESSION_COLUMNS),
+            params![id],
+            session_from_row,
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Sessions whose last activity falls in `(after, until]`, oldest first.
+    pub fn get_sessions_ended_between(&self, after: &str, until: &str) -> Result<Vec<SessionRecord>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM sessions WHERE ended_at > ?1 AND ended_at <= ?2 ORDER BY ended_at, id",
+            SESSION_COLUMNS
+        )).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(params![after, until], session_from_row).map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    /// The timestamp up to which the session's turns and tool calls were
+    /// sent to the OTLP collector, if they were.
+    pub fn get_otlp_exported_until(&self, session_id: &str) -> Result<Option<String>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT exported_until FROM otlp_exported WHERE session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn set_otlp_exported_until(&self, session_id: &str, until: &str) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO otlp_exported (session_id, exported_until) VALUES (?1, ?2)
+             ON CONFLICT(session_id) DO UPDATE SET exported_until = excluded.exported_until",
+            params![session_id, until],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+fn claim_legacy_message(conn: &Connection, m: &MessageRecord) -> Result<bool, String> {
+    if m.message_key.is_none() {
+        return Ok(false);
+    }
+    let claimed = conn.execute(
+        "UPDATE messages SET message_key = ?1 WHERE id = (
+             SELECT id FROM messages
+             WHERE session_id = ?2 AND message_key IS NULL AND message_type = ?3
+               AND timestamp = ?4 AND model IS ?5 AND input_tokens = ?6
+               AND output_tokens = ?7 AND cache_read_tokens = ?8
+               AND cache_creation_tokens = ?9
+             ORDER BY id LIMIT 1)
+         AND NOT EXISTS (SELECT 1 FROM messages WHERE message_key = ?1)",
+        params![m.message_key, m.session_id, m.message_type, m.timestamp, m.model,
+                m.input_tokens, m.output_tokens, m.cache_read_tokens,
+                m.cache_creation_tokens],
+    ).map_err(|e| e.to_string())?;
+    Ok(claimed > 0)
+}
+
+fn bucket_entry(buckets: &mut BTreeMap<NaiveDate, DailyCost>, date: NaiveDate) -> &mut DailyCost {
+    buckets.entry(date).or_insert_with(|| DailyCost {
+        date: date.format("%Y-%m-%d").to_string(),
+        cost: 0.0,
+        tokens: 0,
+        sessions: 0,
+        energy_wh: 0.0,
+        co2_g: 0.0,
+    })
 }
 
 fn get_db_path() -> Result<PathBuf, String> {
     let home = dirs::home_dir().ok_or("Could not find home directory")?;
     Ok(home.join(".open-sonar").join("sessions.db"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::calendar::Bucket;
+    use super::test_util::{message, session, temp_db};
+    use super::DailyCost;
+
+    fn day<'a>(buckets: &'a [DailyCost], date: &str) -> &'a DailyCost {
+        buckets.iter().find(|b| b.date == date).unwrap_or_else(|| panic!("no bucket for {}", date))
+    }
+
+    #[test]
+    fn quiet_sessions_are_completed_until_they_write_again() {
+        let db = temp_db("idle-sessions");
+        let running = |id: &str, last: &str| {
+            let mut record = session(id, "2026-01-05T09:00:00.000Z");
+            record.status = "running".to_string();
+            record.ended_at = Some(last.to_string());
+            record
+        };
+        db.upsert_session(&running("quiet", "2026-01-05T09:20:00.000Z")).unwrap();
+        db.upsert_session(&running("busy", "2026-01-05T09:45:00.000Z")).unwrap();
+
+        assert_eq!(db.complete_idle_sessions("2026-01-05T09:30:00.000Z").unwrap(), 1);
+        let status = |id: &str| db.get_session(id).unwrap().unwrap().status;
+        assert_eq!(status("quiet"), "completed");
+        assert_eq!(status("busy"), "running");
+
+        db.upsert_session(&running("quiet", "2026-01-05T09:50:00.000Z")).unwrap();
+        assert_eq!(status("quiet"), "running");
+    }
+
+    #[test]
+    fn keyed_message_claims_legacy_row() {
+        let db = temp_db("claim-legacy");
+        db.upsert_session(&session("s1", "2026-01-05T10:00:00.000Z")).unwrap();
+        // Stored before messages had keys.
+        assert!(db.insert_message(&message("s1", None, "2026-01-05T10:00:00.000Z", 0.5)).unwrap());
+
+        // The transcript read again: the same line, now with its key.
+        let keyed = message("s1", Some("uuid-1"), "2026-01-05T10:00:00.000Z", 0.5);
+        assert!(!db.insert_message(&keyed).unwrap());
+        assert!(!db.insert_message(&keyed).unwrap());
+        assert!(db.insert_message(&message("s1", Some("uuid-2"), "2026-01-05T10:01:00.000Z", 0.25)).unwrap());
+
+        let messages = db.get_session_messages("s1").unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].message_key.as_deref(), Some("uuid-1"));
+
+        let conn = db.conn.lock().unwrap();
+        let (count, cost): (u32, f64) = conn
+            .query_row("SELECT SUM(messages), SUM(cost) FROM usage_hourly", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(count, 2);
+        assert!((cost - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cost_buckets_follow_local_midnight_across_dst() {
+        let db = temp_db("buckets-dst");
+        db.set_timezone("Europe/Berlin").unwrap();
+        db.upsert_session(&session("s1", "2025-03-29T22:30:00.000Z")).unwrap();
+        db.upsert_session(&session("s2", "2025-03-30T22:30:00.000Z")).unwrap();
+        for (key, timestamp) in [
+            ("a", "2025-03-29T22:30:00.000Z"), // 23:30 CET, March 29
+            ("b", "2025-03-29T23:30:00.000Z"), // 00:30 CET, March 30
+            ("c", "2025-03-30T21:30:00.000Z"), // 23:30 CEST, March 30
+            ("d", "2025-03-30T22:30:00.000Z"), // 00:30 CEST, March 31
+        ] {
+            db.insert_message(&message("s1", Some(key), timestamp, 1.0)).unwrap();
+        }
+
+        let buckets = db.get_cost_buckets(Bucket::Day, 36500).unwrap();
+        assert_eq!(day(&buckets, "2025-03-29").cost, 1.0);
+        assert_eq!(day(&buckets, "2025-03-30").cost, 2.0);
+        assert_eq!(day(&buckets, "2025-03-31").cost, 1.0);
+        assert_eq!(day(&buckets, "2025-03-29").sessions, 1);
+        assert_eq!(day(&buckets, "2025-03-31").sessions, 1);
+
+        let weeks = db.get_cost_buckets(Bucket::Week, 5200).unwrap();
+        // Sunday March 30 closes the week of March 24.
+        assert_eq!(day(&weeks, "2025-03-24").cost, 3.0);
+        assert_eq!(day(&weeks, "2025-03-31").cost, 1.0);
+    }
+
+    #[test]
+    fn session_started_at_the_first_midnight_is_counted() {
+        let db = temp_db("buckets-first-midnight");
+        db.set_timezone("UTC").unwrap();
+        let first = super::calendar::range_start(chrono::Utc::now(), chrono_tz::UTC, Bucket::Day, 2);
+        let midnight = super::calendar::local_midnight_utc(first, chrono_tz::UTC);
+        db.upsert_session(&session("s1", &super::calendar::format_utc(midnight))).unwrap();
+
+        let buckets = db.get_cost_buckets(Bucket::Day, 2).unwrap();
+        assert_eq!(day(&buckets, &first.to_string()).sessions, 1);
+    }
+
+    #[test]
+    fn cost_buckets_split_hours_in_half_hour_zones() {
+        let db = temp_db("buckets-half-hour");
+        db.set_timezone("Asia/Kolkata").unwrap();
+        db.upsert_session(&session("s1", "2025-06-01T18:00:00.000Z")).unwrap();
+        // Local midnight is 18:30 UTC, halfway through the hour.
+        db.insert_message(&message("s1", Some("a"), "2025-06-01T18:15:00.000Z", 1.0)).unwrap();
+        db.insert_message(&message("s1", Some("b"), "2025-06-01T18:45:00.000Z", 2.0)).unwrap();
+        db.insert_message(&message("s1", Some("c"), "2025-06-01T18:50:00.000Z", 4.0)).unwrap();
+        // Pruned by retention: only the hourly rollup still has it.
+        db.conn.lock().unwrap().execute("DELETE FROM messages WHERE message_key = 'c'", []).unwrap();
+
+        let buckets = db.get_cost_buckets(Bucket::Day, 36500).unwrap();
+        assert_eq!(day(&buckets, "2025-06-01").cost, 5.0);
+        assert_eq!(day(&buckets, "2025-06-01").tokens, 60);
+        assert_eq!(day(&buckets, "2025-06-02").cost, 2.0);
+        assert_eq!(day(&buckets, "2025-06-02").tokens, 30);
+        assert_eq!(day(&buckets, "2025-06-02").sessions, 0);
+    }
+
+    #[test]
+    fn connections_share_the_file() {
+        let path = super::test_util::temp_path("wal");
+        let app = super::Database::open(&path).unwrap();
+        let cli = super::Database::open(&path).unwrap();
+        {
+            let conn = app.conn.lock().unwrap();
+            let mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+            assert_eq!(mode, "wal");
+            let timeout: i64 = conn.query_row("PRAGMA busy_timeout", [], |row| row.get(0)).unwrap();
+            assert_eq!(timeout, 5000);
+        }
+
+        // A read stays open on one connection while the other writes.
+        let reader = cli.conn.lock().unwrap();
+        let mut stmt = reader.prepare("SELECT id FROM sessions").unwrap();
+        let mut rows = stmt.query([]).unwrap();
+        assert!(rows.next().unwrap().is_none());
+        app.upsert_session(&session("s1", "2026-01-05T10:00:00.000Z")).unwrap();
+        drop(rows);
+        drop(stmt);
+        drop(reader);
+        assert!(cli.session_exists("s1").unwrap());
+    }
+}
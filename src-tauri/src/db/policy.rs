@@ -0,0 +1,57 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::Database;
+
+/// One tool call the policy engine looked at and what it decided.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyDecisionRecord {
+    pub id: i64,
+    pub created_at: String,
+    pub session_id: String,
+    pub agent_id: Option<String>,
+    pub tool_name: String,
+    /// The tool input as JSON, with secrets redacted and long values cut.
+    pub tool_input: String,
+    /// `allow` or `deny`.
+    pub decision: String,
+    /// The rule that denied the call.
+    pub rule: Option<String>,
+    pub reason: String,
+}
+
+impl Database {
+    pub fn insert_policy_decision(&self, d: &PolicyDecisionRecord) -> Result<i64, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO policy_decisions (created_at, session_id, agent_id, tool_name,
+             tool_input, decision, rule, reason)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8)",
+            params![d.created_at, d.session_id, d.agent_id, d.tool_name,
+                    d.tool_input, d.decision, d.rule, d.reason],
+        ).map_err(|e| e.to_string())?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Most recent decisions first, optionally for one session.
+    pub fn get_policy_decisions(&self, limit: u32, session_id: Option<&str>) -> Result<Vec<PolicyDecisionRecord>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(
+            "SELECT id, created_at, session_id, agent_id, tool_name, tool_input,
+             decision, rule, reason
+             FROM policy_decisions
+             WHERE ?1 IS NULL OR session_id = ?1
+             ORDER BY created_at DESC, id DESC LIMIT ?2"
+        ).map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map(params![session_id, limit], |row| {
+            Ok(PolicyDecisionRecord {
+                id: row.get(0)?, created_at: row.get(1)?, session_id: row.get(2)?,
+                agent_id: row.get(3)?, tool_name: row.get(4)?, tool_input: row.get(5)?,
+                decision: row.get(6)?, rule: row.get(7)?, reason: row.get(8)?,
+            })
+        }).map_err(|e| e.to_string())?;
+
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,422 @@
+use chrono_tz::Tz;
+use rusqlite::types::Value;
+use rusqlite::params_from_iter;
+use serde::{Deserialize, Serialize};
+
+use super::{calendar, session_from_row, Database, SessionRecord, SESSION_COLUMNS};
+
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSort {
+    #[default]
+    StartedAt,
+    TotalCost,
+    TotalTokens,
+    DurationMs,
+    ToolCalls,
+}
+
+impl SessionSort {
+    pub(crate) fn column(self) -> &'static str {
+        match self {
+            SessionSort::StartedAt => "started_at",
+            SessionSort::TotalCost => "total_cost",
+            SessionSort::TotalTokens => "total_tokens",
+            SessionSort::DurationMs => "duration_ms",
+            SessionSort::ToolCalls => "tool_calls",
+        }
+    }
+}
+
+/// Filters for `Database::query_sessions`. Every field is optional; unset
+/// fields don't constrain the result. Dates are RFC 3339 or `YYYY-MM-DD`
+/// (local midnight in the configured timezone) and compared against
+/// `started_at`, `to` being exclusive.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Matches either the encoded project name or the working directory.
+    pub project: Option<String>,
+    pub model: Option<String>,
+    pub agent_id: Option<String>,
+    pub status: Option<String>,
+    pub min_cost: Option<f64>,
+    pub max_cost: Option<f64>,
+    pub tool_name: Option<String>,
+    /// Matched against session id, project, working directory, branch, model
+    /// and message previews.
+    pub text: Option<String>,
+    pub sort: SessionSort,
+    pub descending: Option<bool>,
+    pub limit: Option<u32>,
+    /// `next_cursor` from the previous page.
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionPage {
+    pub sessions: Vec<SessionRecord>,
+    pub next_cursor: Option<String>,
+}
+
+impl SessionQuery {
+    /// Whether any field narrows the set of sessions; sorting and paging don't.
+    pub fn has_filters(&self) -> bool {
+        self.from.is_some() || self.to.is_some() || self.project.is_some() || self.model.is_some()
+            || self.agent_id.is_some() || self.status.is_some() || self.min_cost.is_some()
+            || self.max_cost.is_some() || self.tool_name.is_some() || self.text.is_some()
+    }
+
+    /// The `WHERE` clause (without the keyword) and its parameters, shared by
+    /// anything that needs the same filtered set of sessions. Date bounds are
+    /// bound in the stored UTC format so they compare as strings.
+    pub(crate) fn filter_sql(&self, tz: Tz) -> Result<(String, Vec<Value>), String> {
+        let mut clauses: Vec<String> = vec!["1 = 1".to_string()];
+        let mut values: Vec<Value> = Vec::new();
+
+        if let Some(ref from) = self.from {
+            let from = utc_bound(from, tz)?;
+            clauses.push(format!("started_at >= {}", bind(&mut values, Value::Text(from))));
+        }
+        if let Some(ref to) = self.to {
+            let to = utc_bound(to, tz)?;
+            clauses.push(format!("started_at < {}", bind(&mut values, Value::Text(to))));
+        }
+        if let Some(ref project) = self.project {
+            let p = bind(&mut values, Value::Text(project.clone()));
+            clauses.push(format!("(project = {p} OR cwd = {p})", p = p));
+        }
+        if let Some(ref model) = self.model {
+            clauses.push(format!("model = {}", bind(&mut values, Value::Text(model.clone()))));
+        }
+        if let Some(ref agent_id) = self.agent_id {
+            clauses.push(format!("agent_id = {}", bind(&mut values, Value::Text(agent_id.clone()))));
+        }
+        if let Some(ref status) = self.status {
+            clauses.push(format!("status = {}", bind(&mut values, Value::Text(status.clone()))));
+        }
+        if let Some(min_cost) = self.min_cost {
+            clauses.push(format!("total_cost >= {}", bind(&mut values, Value::Real(min_cost))));
+        }
+        if let Some(max_cost) = self.max_cost {
+            clauses.push(format!("total_cost <= {}", bind(&mut values, Value::Real(max_cost))));
+        }
+        if let Some(ref tool_name) = self.tool_name {
+            let p = bind(&mut values, Value::Text(tool_name.clone()));
+            clauses.push(format!(
+                "(EXISTS (SELECT 1 FROM tool_calls t WHERE t.session_id = sessions.id AND t.name = {p})
+                  OR EXISTS (SELECT 1 FROM messages m WHERE m.session_id = sessions.id AND m.tool_name = {p}))",
+                p = p
+            ));
+        }
+        if let Some(ref text) = self.text {
+            let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            let p = bind(&mut values, Value::Text(format!("%{}%", escaped)));
+            clauses.push(format!(
+                "(id LIKE {p} ESCAPE '\\' OR project LIKE {p} ESCAPE '\\' OR model LIKE {p} ESCAPE '\\'
+                  OR cwd LIKE {p} ESCAPE '\\' OR git_branch LIKE {p} ESCAPE '\\'
+                  OR EXISTS (SELECT 1 FROM messages m WHERE m.session_id = sessions.id
+                             AND m.content_preview LIKE {p} ESCAPE '\\'))",
+                p = p
+            ));
+        }
+
+        Ok((clauses.join(" AND "), values))
+    }
+}
+
+/// A range bound normalized to the format timestamps are stored in.
+pub(crate) fn utc_bound(value: &str, tz: Tz) -> Result<String, String> {
+    calendar::parse_bound(value, tz)
+        .map(calendar::format_utc)
+        .ok_or_else(|| format!("Invalid date: {}", value))
+}
+
+/// Adds a parameter and returns its numbered placeholder.
+fn bind(values: &mut Vec<Value>, value: Value) -> String {
+    values.push(value);
+    format!("?{}", values.len())
+}
+
+impl Database {
+    /// Filtered, sorted session listing with keyset pagination: the cursor
+    /// carries the last row's sort value and id, so pages stay stable while
+    /// new sessions arrive.
+    pub fn query_sessions(&self, q: &SessionQuery) -> Result<SessionPage, String> {
+        let (filter, mut values) = q.filter_sql(self.get_timezone()?)?;
+        let column = q.sort.column();
+        let descending = q.descending.unwrap_or(true);
+        let (cmp, order) = if descending { ("<", "DESC") } else { (">", "ASC") };
+        let limit = q.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+        let mut sql = format!("SELECT {} FROM sessions WHERE {}", SESSION_COLUMNS, filter);
+
+        if let Some(ref cursor) = q.cursor {
+            let (sort_value, id) = decode_cursor(cursor)?;
+            let v = bind(&mut values, sort_value);
+            let i = bind(&mut values, Value::Text(id));
+            sql.push_str(&format!(
+                " AND ({col} {cmp} {v} OR ({col} = {v} AND id {cmp} {i}))",
+                col = column, cmp = cmp, v = v, i = i
+            ));
+        }
+
+        let n = bind(&mut values, Value::Integer(limit as i64 + 1));
+        sql.push_str(&format!(" ORDER BY {col} {order}, id {order} LIMIT {n}", col = column, order = order, n = n));
+
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params_from_iter(values.iter()), session_from_row)
+            .map_err(|e| e.to_string())?;
+        let mut sessions = rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+
+        let next_cursor = if sessions.len() > limit as usize {
+            sessions.truncate(limit as usize);
+            sessions.last().map(|last| encode_cursor(q.sort, last))
+        } else {
+            None
+        };
+
+        Ok(SessionPage { sessions, next_cursor })
+    }
+}
+
+fn encode_cursor(sort: SessionSort, last: &SessionRecord) -> String {
+    let value = match sort {
+        SessionSort::StartedAt => serde_json::json!(last.started_at),
+        SessionSort::TotalCost => serde_json::json!(last.total_cost),
+        SessionSort::TotalTokens => serde_json::json!(last.total_tokens),
+        SessionSort::DurationMs => serde_json::json!(last.duration_ms),
+        SessionSort::ToolCalls => serde_json::json!(last.tool_calls),
+    };
+    serde_json::json!([value, last.id]).to_string()
+}
+
+fn decode_cursor(cursor: &str) -> Result<(Value, String), String> {
+    let invalid = || "Invalid cursor".to_string();
+    let parsed: serde_json::Value = serde_json::from_str(cursor).map_err(|_| invalid())?;
+    let id = parsed.get(1).and_then(|v| v.as_str()).ok_or_else(invalid)?.to_string();
+    let value = match parsed.get(0).ok_or_else(invalid)? {
+        serde_json::Value::String(s) => Value::Text(s.clone()),
+        serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => {
+            Value::Integer(n.as_i64().ok_or_else(invalid)?)
+        }
+        serde_json::Value::Number(n) => Value::Real(n.as_f64().ok_or_else(invalid)?),
+        _ => return Err(invalid()),
+    };
+    Ok((value, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SessionQuery, SessionSort};
+    use crate::db::test_util::{message, session, temp_db};
+    use crate::db::tools::ToolCallRecord;
+    use crate::db::{Database, SessionRecord};
+
+    const SORTS: [SessionSort; 5] = [
+        SessionSort::StartedAt,
+        SessionSort::TotalCost,
+        SessionSort::TotalTokens,
+        SessionSort::DurationMs,
+        SessionSort::ToolCalls,
+    ];
+
+    /// Sessions with ties on every sort column.
+    fn fixture(name: &str) -> (Database, Vec<SessionRecord>) {
+        let db = temp_db(name);
+        let records: Vec<SessionRecord> = [
+            ("s1", "2026-01-01T10:00:00.000Z", 1.0, 100, 5000, 2),
+            ("s2", "2026-01-02T10:00:00.000Z", 3.0, 50, 1000, 0),
+            ("s3", "2026-01-03T10:00:00.000Z", 1.0, 300, 5000, 2),
+            ("s4", "2026-01-04T10:00:00.000Z", 0.5, 100, 2000, 1),
+            ("s5", "2026-01-04T10:00:00.000Z", 2.0, 200, 3000, 5),
+        ]
+        .into_iter()
+        .map(|(id, started_at, total_cost, total_tokens, duration_ms, tool_calls)| SessionRecord {
+            total_cost,
+            total_tokens,
+            duration_ms,
+            tool_calls,
+            ..session(id, started_at)
+        })
+        .collect();
+        for record in &records {
+            db.upsert_session(record).unwrap();
+        }
+        (db, records)
+    }
+
+    /// Every page of `query`, `limit` rows at a time.
+    fn walk(db: &Database, query: &SessionQuery, limit: u32) -> Vec<String> {
+        let mut ids = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = db.query_sessions(&SessionQuery { limit: Some(limit), cursor, ..query.clone() }).unwrap();
+            assert!(page.sessions.len() <= limit as usize);
+            ids.extend(page.sessions.into_iter().map(|s| s.id));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return ids,
+            }
+        }
+    }
+
+    fn sort_key(sort: SessionSort, r: &SessionRecord) -> (String, f64) {
+        match sort {
+            SessionSort::StartedAt => (r.started_at.clone(), 0.0),
+            SessionSort::TotalCost => (String::new(), r.total_cost),
+            SessionSort::TotalTokens => (String::new(), r.total_tokens as f64),
+            SessionSort::DurationMs => (String::new(), r.duration_ms as f64),
+            SessionSort::ToolCalls => (String::new(), r.tool_calls as f64),
+        }
+    }
+
+    fn ids(db: &crate::db::Database, from: Option<&str>, to: Option<&str>) -> Vec<String> {
+        let query = SessionQuery {
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+            descending: Some(false),
+            ..Default::default()
+        };
+        db.query_sessions(&query).unwrap().sessions.into_iter().map(|s| s.id).collect()
+    }
+
+    #[test]
+    fn date_bounds_are_local_midnight() {
+        let db = temp_db("query-bounds");
+        db.set_timezone("Europe/Berlin").unwrap();
+        db.upsert_session(&session("before", "2026-01-04T22:30:00.000Z")).unwrap();
+        db.upsert_session(&session("local-jan-5", "2026-01-04T23:30:00.000Z")).unwrap();
+        db.upsert_session(&session("jan-5", "2026-01-05T12:00:00.000Z")).unwrap();
+        db.upsert_session(&session("local-jan-6", "2026-01-05T23:30:00.000Z")).unwrap();
+
+        assert_eq!(ids(&db, Some("2026-01-05"), Some("2026-01-06")), ["local-jan-5", "jan-5"]);
+        // Offsets are normalized to UTC before comparing.
+        assert_eq!(ids(&db, Some("2026-01-05T13:30:00+01:00"), None), ["local-jan-6"]);
+        assert_eq!(ids(&db, None, Some("2026-01-05T00:31:00.000+01:00")), ["before", "local-jan-5"]);
+    }
+
+    #[test]
+    fn invalid_bounds_are_rejected() {
+        let db = temp_db("query-invalid");
+        let query = SessionQuery { from: Some("last week".to_string()), ..Default::default() };
+        assert_eq!(db.query_sessions(&query).unwrap_err(), "Invalid date: last week");
+    }
+
+    #[test]
+    fn every_sort_pages_through_each_session_once_in_order() {
+        let (db, records) = fixture("query-sorts");
+        for sort in SORTS {
+            for descending in [false, true] {
+                // Equal sort values fall back to the id, in the same direction.
+                let mut expected = records.clone();
+                expected.sort_by(|a, b| {
+                    let (a_text, a_number) = sort_key(sort, a);
+                    let (b_text, b_number) = sort_key(sort, b);
+                    a_text.cmp(&b_text).then(a_number.total_cmp(&b_number)).then(a.id.cmp(&b.id))
+                });
+                if descending {
+                    expected.reverse();
+                }
+                let expected: Vec<String> = expected.into_iter().map(|r| r.id).collect();
+
+                let query = SessionQuery { sort, descending: Some(descending), ..Default::default() };
+                for limit in [1, 2, 3, 100] {
+                    assert_eq!(walk(&db, &query, limit), expected, "{:?} descending={} limit={}", sort, descending, limit);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ties_are_broken_by_id() {
+        let (db, _) = fixture("query-ties");
+        let by_cost = SessionQuery { sort: SessionSort::TotalCost, descending: Some(false), ..Default::default() };
+        assert_eq!(walk(&db, &by_cost, 2), ["s4", "s1", "s3", "s5", "s2"]);
+        let newest = SessionQuery::default();
+        assert_eq!(walk(&db, &newest, 1), ["s5", "s4", "s3", "s2", "s1"]);
+    }
+
+    #[test]
+    fn cursors_hold_the_last_sort_value_and_id() {
+        let (db, _) = fixture("query-cursor");
+        let page = |sort, cursor: Option<&str>| {
+            let query = SessionQuery {
+                sort,
+                descending: Some(false),
+                limit: Some(2),
+                cursor: cursor.map(str::to_string),
+                ..Default::default()
+            };
+            db.query_sessions(&query)
+        };
+
+        let cursor = page(SessionSort::TotalCost, None).unwrap().next_cursor.unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&cursor).unwrap(), serde_json::json!([1.0, "s1"]));
+        let cursor = page(SessionSort::StartedAt, None).unwrap().next_cursor.unwrap();
+        assert_eq!(cursor, r#"["2026-01-02T10:00:00.000Z","s2"]"#);
+        let cursor = page(SessionSort::ToolCalls, None).unwrap().next_cursor.unwrap();
+        assert_eq!(cursor, r#"[1,"s4"]"#);
+
+        // A hand-written cursor picks up after that row.
+        let next = page(SessionSort::TotalTokens, Some(r#"[100,"s1"]"#)).unwrap();
+        let ids: Vec<&str> = next.sessions.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["s4", "s5"]);
+
+        for invalid in ["nope", "[]", r#"[1]"#, r#"[{}, "s1"]"#, r#"[1, 2]"#] {
+            assert_eq!(page(SessionSort::TotalCost, Some(invalid)).unwrap_err(), "Invalid cursor", "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn tool_text_and_status_filters() {
+        let (db, mut records) = fixture("query-filters");
+        records[1].status = "running".to_string();
+        records[2].git_branch = Some("fix/flaky_parser".to_string());
+        for record in &records {
+            db.upsert_session(record).unwrap();
+        }
+        db.insert_tool_call(&ToolCallRecord {
+            tool_use_id: "toolu_1".to_string(),
+            session_id: "s1".to_string(),
+            message_key: None,
+            name: "mcp__github__search".to_string(),
+            server: Some("github".to_string()),
+            input_bytes: 10,
+            started_at: "2026-01-01T10:00:01.000Z".to_string(),
+            cost: 0.1,
+        })
+        .unwrap();
+        // Sessions stored before tool calls were, with only the message's tool.
+        let mut legacy = message("s4", Some("m4"), "2026-01-04T10:00:01.000Z", 0.5);
+        legacy.tool_name = Some("Bash".to_string());
+        legacy.content_preview = Some("Cost went up 50% after the upgrade".to_string());
+        db.insert_message(&legacy).unwrap();
+
+        let ids = |query: SessionQuery| walk(&db, &SessionQuery { descending: Some(false), ..query }, 100);
+        let text = |text: &str| ids(SessionQuery { text: Some(text.to_string()), ..Default::default() });
+
+        assert_eq!(ids(SessionQuery { tool_name: Some("mcp__github__search".to_string()), ..Default::default() }), ["s1"]);
+        assert_eq!(ids(SessionQuery { tool_name: Some("Bash".to_string()), ..Default::default() }), ["s4"]);
+        assert!(ids(SessionQuery { tool_name: Some("mcp__github".to_string()), ..Default::default() }).is_empty());
+
+        assert_eq!(ids(SessionQuery { status: Some("running".to_string()), ..Default::default() }), ["s2"]);
+        assert_eq!(ids(SessionQuery { status: Some("completed".to_string()), ..Default::default() }).len(), 4);
+
+        assert_eq!(text("s3"), ["s3"]);
+        assert_eq!(text("went up"), ["s4"]);
+        assert_eq!(text("FLAKY"), ["s3"]);
+        // `%` and `_` match themselves rather than anything.
+        assert_eq!(text("50%"), ["s4"]);
+        assert_eq!(text("y_p"), ["s3"]);
+        assert!(text("5_%").is_empty());
+        assert_eq!(text("/work/app").len(), 5);
+    }
+}
@@ -0,0 +1,25 @@
+use rusqlite::params;
+
+use super::{session_from_row, Database, SessionRecord, SESSION_COLUMNS};
+
+impl Database {
+    /// Sessions whose transcripts still need reading for search, tool calls
+    /// and message keys.
+    pub fn get_pending_reindex(&self) -> Result<Vec<SessionRecord>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM sessions WHERE id IN (SELECT session_id FROM pending_reindex)
+             ORDER BY started_at",
+            SESSION_COLUMNS
+        )).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], session_from_row).map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    pub fn finish_reindex(&self, session_id: &str) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM pending_reindex WHERE session_id = ?1", params![session_id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{calendar, Database};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportGroup {
+    Project,
+    Model,
+    Agent,
+    /// Local calendar day in the configured timezone.
+    Day,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageReportRow {
+    pub key: String,
+    pub messages: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+    pub energy_wh: f64,
+}
+
+impl Database {
+    /// Usage in `[from, to)` from the hourly rollups, grouped by `by`. Bounds
+    /// are RFC 3339 or `YYYY-MM-DD`; `from` is widened to its whole hour.
+    /// Days come out in order, everything else by cost, highest first.
+    pub fn get_usage_report(
+        &self,
+        from: &str,
+        to: Option<&str>,
+        by: ReportGroup,
+    ) -> Result<Vec<UsageReportRow>, String> {
+        let tz = self.get_timezone()?;
+        let start = calendar::parse_bound(from, tz).ok_or_else(|| format!("Invalid date: {}", from))?;
+        let end = match to {
+            Some(to) => calendar::parse_bound(to, tz).ok_or_else(|| format!("Invalid date: {}", to))?,
+            None => chrono::Utc::now(),
+        };
+
+        let key = match by {
+            ReportGroup::Project => "project",
+            ReportGroup::Model => "model",
+            ReportGroup::Agent => "agent_id",
+            ReportGroup::Day => "hour",
+        };
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {key}, SUM(messages), SUM(input_tokens), SUM(output_tokens),
+             SUM(cache_read_tokens), SUM(cache_creation_tokens), SUM(cost), SUM(energy_wh)
+             FROM usage_hourly WHERE hour >= ?1 AND hour < ?2 GROUP BY {key}",
+            key = key
+        )).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(
+            params![start.format("%Y-%m-%dT%H:00:00Z").to_string(), calendar::format_utc(end)],
+            |row| {
+                let mut r = UsageReportRow {
+                    key: row.get(0)?,
+                    messages: row.get(1)?,
+                    input_tokens: row.get(2)?,
+                    output_tokens: row.get(3)?,
+                    cache_read_tokens: row.get(4)?,
+                    cache_creation_tokens: row.get(5)?,
+                    total_tokens: 0,
+                    cost: row.get(6)?,
+                    energy_wh: row.get(7)?,
+                };
+                r.total_tokens = r.input_tokens + r.output_tokens + r.cache_read_tokens + r.cache_creation_tokens;
+                Ok(r)
+            },
+        ).map_err(|e| e.to_string())?;
+        let mut rows = rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+
+        if by != ReportGroup::Day {
+            rows.sort_by(|a, b| b.cost.total_cmp(&a.cost).then_with(|| a.key.cmp(&b.key)));
+            return Ok(rows);
+        }
+
+        let mut days: BTreeMap<chrono::NaiveDate, UsageReportRow> = BTreeMap::new();
+        for r in rows {
+            let Some(hour) = calendar::parse_utc(&r.key) else { continue };
+            let date = calendar::bucket_start(hour, tz, calendar::Bucket::Day);
+            let day = days.entry(date).or_insert_with(|| UsageReportRow {
+                key: date.format("%Y-%m-%d").to_string(),
+                ..Default::default()
+            });
+            day.messages += r.messages;
+            day.input_tokens += r.input_tokens;
+            day.output_tokens += r.output_tokens;
+            day.cache_read_tokens += r.cache_read_tokens;
+            day.cache_creation_tokens += r.cache_creation_tokens;
+            day.total_tokens += r.total_tokens;
+            day.cost += r.cost;
+            day.energy_wh += r.energy_wh;
+        }
+        Ok(days.into_values().collect())
+    }
+}
@@ -0,0 +1,293 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::{calendar, Database};
+
+const RETENTION_DAYS_KEY: &str = "retention_days";
+const PREVIEW_RETENTION_DAYS_KEY: &str = "preview_retention_days";
+/// Nothing is deleted until the user picks a retention period.
+const DEFAULT_RETENTION_DAYS: u32 = 0;
+
+/// How long per-message detail is kept: messages, their search entries, tool
+/// calls, compaction events and tool policy decisions. Session totals and hourly rollups are never
+/// pruned. A `retention_days` of 0, the default, disables pruning entirely.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub retention_days: u32,
+    /// Content previews, and the text search indexed, can be cleared earlier
+    /// than the messages themselves.
+    pub preview_retention_days: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionReport {
+    pub messages_deleted: u64,
+    /// Tool calls, compaction events and tool policy decisions.
+    #[serde(default)]
+    pub events_deleted: u64,
+    pub previews_cleared: u64,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub bytes_reclaimed: u64,
+}
+
+impl RetentionPolicy {
+    /// Detail older than this is pruned, if anything is.
+    pub fn cutoff(&self) -> Option<String> {
+        (self.retention_days > 0).then(|| cutoff_timestamp(self.retention_days))
+    }
+}
+
+impl Database {
+    pub fn get_retention_policy(&self) -> Result<RetentionPolicy, String> {
+        let retention_days = self
+            .get_setting(RETENTION_DAYS_KEY)?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_RETENTION_DAYS);
+        let preview_retention_days = self
+            .get_setting(PREVIEW_RETENTION_DAYS_KEY)?
+            .and_then(|v| v.parse().ok());
+        Ok(RetentionPolicy { retention_days, preview_retention_days })
+    }
+
+    pub fn set_retention_policy(&self, policy: &RetentionPolicy) -> Result<(), String> {
+        self.set_setting(RETENTION_DAYS_KEY, &policy.retention_days.to_string())?;
+        match policy.preview_retention_days {
+            Some(days) => self.set_setting(PREVIEW_RETENTION_DAYS_KEY, &days.to_string()),
+            None => self.set_setting(PREVIEW_RETENTION_DAYS_KEY, ""),
+        }
+    }
+
+    /// Deletes messages (and their search index entries), tool calls,
+    /// compaction events and policy decisions older than the policy, clears stale content previews
+    /// and search text, and vacuums the file. Totals survive in `sessions` and `usage_hourly`.
+    pub fn apply_retention(&self, policy: &RetentionPolicy) -> Result<RetentionReport, String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let bytes_before = database_size(&conn)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+        let mut messages_deleted = 0u64;
+        let mut events_deleted = 0u64;
+        if let Some(cutoff) = policy.cutoff() {
+            messages_deleted = tx
+                .execute("DELETE FROM messages WHERE timestamp < ?1", params![cutoff])
+                .map_err(|e| e.to_string())? as u64;
+            tx.execute(
+                "DELETE FROM transcript_fts WHERE rowid IN
+                 (SELECT id FROM search_documents WHERE timestamp < ?1)",
+                params![cutoff],
+            ).map_err(|e| e.to_string())?;
+            tx.execute("DELETE FROM search_documents WHERE timestamp < ?1", params![cutoff])
+                .map_err(|e| e.to_string())?;
+            for sql in [
+                "DELETE FROM tool_calls WHERE started_at < ?1",
+                "DELETE FROM compaction_events WHERE timestamp < ?1",
+                "DELETE FROM policy_decisions WHERE created_at < ?1",
+            ] {
+                events_deleted += tx.execute(sql, params![cutoff]).map_err(|e| e.to_string())? as u64;
+            }
+        }
+
+        let mut previews_cleared = 0u64;
+        if let Some(days) = policy.preview_retention_days {
+            let cutoff = cutoff_timestamp(days);
+            previews_cleared = tx
+                .execute(
+                    "UPDATE messages SET content_preview = NULL
+                     WHERE timestamp < ?1 AND content_preview IS NOT NULL",
+                    params![cutoff],
+                )
+                .map_err(|e| e.to_string())? as u64;
+            // The index rows stay, so reading the transcript again doesn't
+            // bring the text back.
+            tx.execute(
+                "UPDATE transcript_fts SET text = '', tool_input = '', tool_result = ''
+                 WHERE rowid IN (SELECT id FROM search_documents WHERE timestamp < ?1)
+                 AND (text != '' OR tool_input != '' OR tool_result != '')",
+                params![cutoff],
+            ).map_err(|e| e.to_string())?;
+        }
+
+        tx.commit().map_err(|e| e.to_string())?;
+
+        if messages_deleted > 0 || events_deleted > 0 || previews_cleared > 0 {
+            vacuum(&conn)?;
+        }
+
+        let bytes_after = database_size(&conn)?;
+        Ok(RetentionReport {
+            messages_deleted,
+            events_deleted,
+            previews_cleared,
+            bytes_before,
+            bytes_after,
+            bytes_reclaimed: bytes_before.saturating_sub(bytes_after),
+        })
+    }
+}
+
+fn cutoff_timestamp(days: u32) -> String {
+    calendar::format_utc(chrono::Utc::now() - chrono::Duration::days(days as i64))
+}
+
+fn database_size(conn: &rusqlite::Connection) -> Result<u64, String> {
+    let page_count: u64 = conn
+        .query_row("PRAGMA page_count", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let page_size: u64 = conn
+        .query_row("PRAGMA page_size", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    Ok(page_count * page_size)
+}
+
+/// The first run switches the file to incremental auto-vacuum (which needs a
+/// full `VACUUM` to take effect); later runs only release free pages.
+fn vacuum(conn: &rusqlite::Connection) -> Result<(), String> {
+    let mode: u32 = conn
+        .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if mode == 2 {
+        conn.execute_batch("PRAGMA incremental_vacuum;")
+    } else {
+        conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")
+    }
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::context::CompactionEvent;
+    use crate::db::policy::PolicyDecisionRecord;
+    use crate::db::search::SearchDocument;
+    use crate::db::test_util::{message, session, temp_db};
+    use crate::db::tools::ToolCallRecord;
+
+    fn days_ago(days: i64) -> String {
+        calendar::format_utc(chrono::Utc::now() - chrono::Duration::days(days))
+    }
+
+    fn fill(db: &Database) {
+        for (id, age) in [("old", 200), ("new", 1)] {
+            let at = days_ago(age);
+            db.upsert_session(&session(id, &at)).unwrap();
+            db.insert_message(&message(id, Some(&format!("{}-m", id)), &at, 1.0)).unwrap();
+            db.insert_tool_call(&ToolCallRecord {
+                tool_use_id: format!("{}-t", id),
+                session_id: id.to_string(),
+                message_key: Some(format!("{}-m", id)),
+                name: "Bash".to_string(),
+                server: None,
+                input_bytes: 10,
+                started_at: at.clone(),
+                cost: 0.5,
+            })
+            .unwrap();
+            db.insert_compaction(&CompactionEvent {
+                session_id: id.to_string(),
+                event_key: format!("{}-c", id),
+                kind: "compact_boundary".to_string(),
+                timestamp: at.clone(),
+                trigger: Some("auto".to_string()),
+                pre_tokens: Some(1000),
+                summary: None,
+            })
+            .unwrap();
+            db.insert_policy_decision(&PolicyDecisionRecord {
+                id: 0,
+                created_at: at,
+                session_id: id.to_string(),
+                agent_id: None,
+                tool_name: "Bash".to_string(),
+                tool_input: "{}".to_string(),
+                decision: "allow".to_string(),
+                rule: None,
+                reason: "No rule matched".to_string(),
+            })
+            .unwrap();
+        }
+    }
+
+    fn rows(db: &Database, table: &str) -> i64 {
+        let conn = db.conn.lock().unwrap();
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn keeps_everything_by_default() {
+        let db = temp_db("retention-default");
+        fill(&db);
+
+        let policy = db.get_retention_policy().unwrap();
+        assert_eq!(policy.retention_days, 0);
+        let report = db.apply_retention(&policy).unwrap();
+
+        assert_eq!(report.messages_deleted, 0);
+        assert_eq!(report.events_deleted, 0);
+        assert_eq!(rows(&db, "messages"), 2);
+    }
+
+    #[test]
+    fn prunes_detail_older_than_the_policy() {
+        let db = temp_db("retention-prune");
+        fill(&db);
+        let policy = RetentionPolicy { retention_days: 90, preview_retention_days: None };
+        db.set_retention_policy(&policy).unwrap();
+
+        let report = db.apply_retention(&db.get_retention_policy().unwrap()).unwrap();
+
+        assert_eq!(report.messages_deleted, 1);
+        assert_eq!(report.events_deleted, 3);
+        for table in ["messages", "tool_calls", "compaction_events", "policy_decisions"] {
+            assert_eq!(rows(&db, table), 1, "{}", table);
+        }
+        // Totals stay.
+        assert_eq!(rows(&db, "sessions"), 2);
+        assert_eq!(rows(&db, "usage_hourly"), 2);
+    }
+
+    #[test]
+    fn preview_retention_clears_indexed_text_too() {
+        let db = temp_db("retention-preview-search");
+        let privacy = db.get_search_privacy().unwrap();
+        let document = |id: &str, timestamp: &str| SearchDocument {
+            session_id: id.to_string(),
+            message_key: format!("{}-m", id),
+            message_type: "assistant".to_string(),
+            timestamp: timestamp.to_string(),
+            text: "the flaky parser test".to_string(),
+            tool_input: r#"{"command":"cargo test parser"}"#.to_string(),
+            tool_result: "parser ok".to_string(),
+        };
+        for (id, age) in [("old", 60), ("new", 1)] {
+            let at = days_ago(age);
+            db.upsert_session(&session(id, &at)).unwrap();
+            let mut m = message(id, Some(&format!("{}-m", id)), &at, 1.0);
+            m.content_preview = Some("the flaky parser test".to_string());
+            db.insert_message(&m).unwrap();
+            db.index_document(&document(id, &at), &privacy).unwrap();
+        }
+
+        let policy = RetentionPolicy { retention_days: 0, preview_retention_days: Some(30) };
+        assert_eq!(db.apply_retention(&policy).unwrap().previews_cleared, 1);
+
+        let hits = db.search_transcripts("parser", None).unwrap();
+        assert_eq!(hits.iter().map(|h| h.session_id.as_str()).collect::<Vec<_>>(), ["new"]);
+        // Reading the transcript again leaves it cleared.
+        assert!(!db.index_document(&document("old", &days_ago(60)), &privacy).unwrap());
+        assert_eq!(db.search_transcripts("cargo", None).unwrap().len(), 1);
+        assert_eq!(db.get_session_messages("old").unwrap()[0].content_preview, None);
+        assert_eq!(rows(&db, "messages"), 2);
+    }
+
+    #[test]
+    fn saving_days_keeps_preview_retention() {
+        let db = temp_db("retention-preview");
+        db.set_retention_policy(&RetentionPolicy { retention_days: 0, preview_retention_days: Some(7) }).unwrap();
+        let policy = RetentionPolicy { retention_days: 30, ..db.get_retention_policy().unwrap() };
+        db.set_retention_policy(&policy).unwrap();
+
+        assert_eq!(db.get_retention_policy().unwrap().preview_retention_days, Some(7));
+    }
+}
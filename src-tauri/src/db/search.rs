@@ -0,0 +1,147 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::Database;
+
+const INDEX_TEXT_KEY: &str = "search_index_text";
+const INDEX_TOOL_INPUTS_KEY: &str = "search_index_tool_inputs";
+const INDEX_TOOL_RESULTS_KEY: &str = "search_index_tool_results";
+
+const DEFAULT_SEARCH_LIMIT: u32 = 50;
+
+/// Which parts of a transcript are written to the search index. Turning a
+/// part off also blanks it from everything already indexed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchPrivacy {
+    pub index_text: bool,
+    pub index_tool_inputs: bool,
+    pub index_tool_results: bool,
+}
+
+/// One transcript line as it is indexed: prompt/response text, tool inputs
+/// (JSON) and tool result output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchDocument {
+    pub session_id: String,
+    pub message_key: String,
+    pub message_type: String,
+    pub timestamp: String,
+    pub text: String,
+    pub tool_input: String,
+    pub tool_result: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub session_id: String,
+    pub message_key: String,
+    pub message_type: String,
+    pub timestamp: String,
+    pub project: Option<String>,
+    /// Matching excerpt with matched terms wrapped in `**`.
+    pub snippet: String,
+    /// bm25 score; lower is a better match.
+    pub rank: f64,
+}
+
+impl Database {
+    pub fn get_search_privacy(&self) -> Result<SearchPrivacy, String> {
+        let flag = |key: &str| -> Result<bool, String> {
+            Ok(self.get_setting(key)?.map(|v| v != "false").unwrap_or(true))
+        };
+        Ok(SearchPrivacy {
+            index_text: flag(INDEX_TEXT_KEY)?,
+            index_tool_inputs: flag(INDEX_TOOL_INPUTS_KEY)?,
+            index_tool_results: flag(INDEX_TOOL_RESULTS_KEY)?,
+        })
+    }
+
+    pub fn set_search_privacy(&self, privacy: &SearchPrivacy) -> Result<(), String> {
+        self.set_setting(INDEX_TEXT_KEY, &privacy.index_text.to_string())?;
+        self.set_setting(INDEX_TOOL_INPUTS_KEY, &privacy.index_tool_inputs.to_string())?;
+        self.set_setting(INDEX_TOOL_RESULTS_KEY, &privacy.index_tool_results.to_string())?;
+
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        for (enabled, column) in [
+            (privacy.index_text, "text"),
+            (privacy.index_tool_inputs, "tool_input"),
+            (privacy.index_tool_results, "tool_result"),
+        ] {
+            if !enabled {
+                conn.execute(
+                    &format!("UPDATE transcript_fts SET {0} = '' WHERE {0} != ''", column),
+                    [],
+                ).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a transcript line to the full-text index, leaving out the parts
+    /// `privacy` excludes. Lines already indexed are skipped.
+    pub fn index_document(&self, doc: &SearchDocument, privacy: &SearchPrivacy) -> Result<bool, String> {
+        let text = if privacy.index_text { doc.text.as_str() } else { "" };
+        let tool_input = if privacy.index_tool_inputs { doc.tool_input.as_str() } else { "" };
+        let tool_result = if privacy.index_tool_results { doc.tool_result.as_str() } else { "" };
+        if text.is_empty() && tool_input.is_empty() && tool_result.is_empty() {
+            return Ok(false);
+        }
+
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let inserted = tx.execute(
+            "INSERT INTO search_documents (session_id, message_key, message_type, timestamp)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(message_key) DO NOTHING",
+            params![doc.session_id, doc.message_key, doc.message_type, doc.timestamp],
+        ).map_err(|e| e.to_string())?;
+
+        if inserted > 0 {
+            tx.execute(
+                "INSERT INTO transcript_fts (rowid, text, tool_input, tool_result)
+                 VALUES (last_insert_rowid(), ?1, ?2, ?3)",
+                params![text, tool_input, tool_result],
+            ).map_err(|e| e.to_string())?;
+        }
+
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(inserted > 0)
+    }
+
+    /// Ranked full-text search over indexed transcripts. Every whitespace
+    /// separated term must match; terms are taken literally, not as FTS syntax.
+    pub fn search_transcripts(&self, query: &str, limit: Option<u32>) -> Result<Vec<SearchHit>, String> {
+        let match_expr = query
+            .split_whitespace()
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if match_expr.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(
+            "SELECT d.session_id, d.message_key, d.message_type, d.timestamp, s.project,
+             snippet(transcript_fts, -1, '**', '**', '…', 16), bm25(transcript_fts)
+             FROM transcript_fts
+             JOIN search_documents d ON d.id = transcript_fts.rowid
+             LEFT JOIN sessions s ON s.id = d.session_id
+             WHERE transcript_fts MATCH ?1
+             ORDER BY bm25(transcript_fts) LIMIT ?2"
+        ).map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map(
+            params![match_expr, limit.unwrap_or(DEFAULT_SEARCH_LIMIT)],
+            |row| {
+                Ok(SearchHit {
+                    session_id: row.get(0)?, message_key: row.get(1)?, message_type: row.get(2)?,
+                    timestamp: row.get(3)?, project: row.get(4)?, snippet: row.get(5)?,
+                    rank: row.get(6)?,
+                })
+            },
+        ).map_err(|e| e.to_string())?;
+
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,61 @@
+//! Fixtures shared by the database tests.
+
+use std::path::PathBuf;
+
+use super::{Database, MessageRecord, SessionRecord};
+
+/// A fresh file under the temp directory, removed with its backups first.
+pub fn temp_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sonar-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join("sessions.db")
+}
+
+pub fn temp_db(name: &str) -> Database {
+    Database::open(&temp_path(name)).unwrap()
+}
+
+pub fn session(id: &str, started_at: &str) -> SessionRecord {
+    SessionRecord {
+        id: id.to_string(),
+        agent_id: "agent".to_string(),
+        project: "/work/app".to_string(),
+        model: "claude-sonnet-4-5".to_string(),
+        total_cost: 0.0,
+        total_tokens: 0,
+        input_tokens: 0,
+        output_tokens: 0,
+        cache_read_tokens: 0,
+        cache_creation_tokens: 0,
+        duration_ms: 0,
+        status: "completed".to_string(),
+        tool_calls: 0,
+        started_at: started_at.to_string(),
+        ended_at: Some(started_at.to_string()),
+        cwd: None,
+        git_branch: None,
+        energy_wh: 0.0,
+        permission_mode: None,
+    }
+}
+
+pub fn message(session_id: &str, key: Option<&str>, timestamp: &str, cost: f64) -> MessageRecord {
+    MessageRecord {
+        id: 0,
+        session_id: session_id.to_string(),
+        message_key: key.map(str::to_string),
+        message_type: "assistant".to_string(),
+        timestamp: timestamp.to_string(),
+        model: Some("claude-sonnet-4-5".to_string()),
+        input_tokens: 10,
+        output_tokens: 20,
+        cache_read_tokens: 0,
+        cache_creation_tokens: 0,
+        cost,
+        content_preview: None,
+        tool_name: None,
+        context_tokens: 10,
+        energy_wh: 0.0,
+    }
+}
@@ -0,0 +1,219 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::Database;
+
+/// One `tool_use` block. `cost` is the share of its message's cost, split
+/// evenly across the tool calls the message made.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub tool_use_id: String,
+    pub session_id: String,
+    pub message_key: Option<String>,
+    pub name: String,
+    /// MCP server for `mcp__<server>__<tool>` names.
+    pub server: Option<String>,
+    pub input_bytes: u64,
+    pub started_at: String,
+    pub cost: f64,
+}
+
+/// The `tool_result` that answers a `tool_use`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallResult {
+    pub tool_use_id: String,
+    pub finished_at: String,
+    pub is_error: bool,
+}
+
+/// A stored tool call with its outcome; `finished_at` and `is_error` stay
+/// unset until the result arrives.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionToolCall {
+    pub tool_use_id: String,
+    pub message_key: Option<String>,
+    pub name: String,
+    pub server: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub is_error: Option<bool>,
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolStats {
+    /// Tool name, or `mcp__<server>` for server rollups.
+    pub name: String,
+    pub server: Option<String>,
+    pub calls: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    /// Calls still waiting for a result are left out.
+    pub avg_duration_ms: Option<f64>,
+    pub avg_input_bytes: f64,
+    pub total_cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolStatsReport {
+    pub tools: Vec<ToolStats>,
+    /// MCP tools grouped by the server that provides them.
+    pub mcp_servers: Vec<ToolStats>,
+}
+
+pub fn mcp_server(tool_name: &str) -> Option<String> {
+    let rest = tool_name.strip_prefix("mcp__")?;
+    rest.split("__").next().filter(|s| !s.is_empty()).map(|s| s.to_string())
+}
+
+impl Database {
+    pub fn insert_tool_call(&self, t: &ToolCallRecord) -> Result<bool, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let inserted = conn.execute(
+            "INSERT INTO tool_calls (tool_use_id, session_id, message_key, name, server,
+             input_bytes, started_at, cost)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8)
+             ON CONFLICT(tool_use_id) DO NOTHING",
+            params![t.tool_use_id, t.session_id, t.message_key, t.name, t.server,
+                    t.input_bytes, t.started_at, t.cost],
+        ).map_err(|e| e.to_string())?;
+        Ok(inserted > 0)
+    }
+
+    pub fn complete_tool_call(&self, r: &ToolCallResult) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE tool_calls SET finished_at = ?2, is_error = ?3,
+             duration_ms = MAX(0, CAST(ROUND((julianday(?2) - julianday(started_at)) * 86400000) AS INTEGER))
+             WHERE tool_use_id = ?1 AND finished_at IS NULL",
+            params![r.tool_use_id, r.finished_at, r.is_error],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn get_session_tool_calls(&self, session_id: &str) -> Result<Vec<SessionToolCall>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(
+            "SELECT tool_use_id, message_key, name, server, started_at, finished_at, is_error, cost
+             FROM tool_calls WHERE session_id = ?1 ORDER BY started_at, id"
+        ).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(params![session_id], |row| {
+            Ok(SessionToolCall {
+                tool_use_id: row.get(0)?, message_key: row.get(1)?, name: row.get(2)?,
+                server: row.get(3)?, started_at: row.get(4)?, finished_at: row.get(5)?,
+                is_error: row.get(6)?, cost: row.get(7)?,
+            })
+        }).map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Per-tool call counts, error rate, latency and attributed cost for calls
+    /// started in `[from, to)`; either bound may be omitted.
+    pub fn get_tool_stats(&self, from: Option<&str>, to: Option<&str>) -> Result<ToolStatsReport, String> {
+        let tools = self.tool_stats_grouped_by("name", from, to)?;
+        let mcp_servers = self
+            .tool_stats_grouped_by("'mcp__' || server", from, to)?
+            .into_iter()
+            .filter(|s| s.server.is_some())
+            .collect();
+        Ok(ToolStatsReport { tools, mcp_servers })
+    }
+
+    fn tool_stats_grouped_by(
+        &self,
+        key: &str,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<Vec<ToolStats>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {key}, MAX(server), COUNT(*), COALESCE(SUM(is_error), 0),
+             AVG(duration_ms), AVG(input_bytes), SUM(cost)
+             FROM tool_calls
+             WHERE (?1 IS NULL OR started_at >= ?1) AND (?2 IS NULL OR started_at < ?2)
+             GROUP BY {key} HAVING {key} IS NOT NULL
+             ORDER BY COUNT(*) DESC",
+            key = key
+        )).map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map(params![from, to], |row| {
+            let calls: u64 = row.get(2)?;
+            let errors: u64 = row.get(3)?;
+            Ok(ToolStats {
+                name: row.get(0)?, server: row.get(1)?, calls, errors,
+                error_rate: if calls > 0 { errors as f64 / calls as f64 } else { 0.0 },
+                avg_duration_ms: row.get(4)?, avg_input_bytes: row.get(5)?, total_cost: row.get(6)?,
+            })
+        }).map_err(|e| e.to_string())?;
+
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mcp_server, ToolCallRecord, ToolCallResult};
+    use crate::db::test_util::{session, temp_db};
+
+    fn call(id: &str, name: &str, started_at: &str, input_bytes: u64, cost: f64) -> ToolCallRecord {
+        ToolCallRecord {
+            tool_use_id: id.to_string(),
+            session_id: "s1".to_string(),
+            message_key: None,
+            name: name.to_string(),
+            server: mcp_server(name),
+            input_bytes,
+            started_at: started_at.to_string(),
+            cost,
+        }
+    }
+
+    fn finish(id: &str, finished_at: &str, is_error: bool) -> ToolCallResult {
+        ToolCallResult { tool_use_id: id.to_string(), finished_at: finished_at.to_string(), is_error }
+    }
+
+    #[test]
+    fn stats_count_errors_durations_and_servers() {
+        let db = temp_db("tool-stats");
+        db.upsert_session(&session("s1", "2026-03-01T10:00:00.000Z")).unwrap();
+        for record in [
+            call("t1", "Read", "2026-03-01T10:00:00.000Z", 100, 0.01),
+            call("t2", "Read", "2026-03-01T10:01:00.000Z", 300, 0.03),
+            call("t3", "Read", "2026-03-01T10:02:00.000Z", 200, 0.02),
+            call("t4", "mcp__github__search_code", "2026-03-01T10:03:00.000Z", 50, 0.05),
+            call("t5", "mcp__github__get_issue", "2026-03-01T10:04:00.000Z", 70, 0.07),
+            call("t6", "Bash", "2026-03-02T10:00:00.000Z", 10, 0.5),
+        ] {
+            assert!(db.insert_tool_call(&record).unwrap());
+        }
+        assert!(!db.insert_tool_call(&call("t1", "Read", "2026-03-01T10:00:00.000Z", 100, 0.01)).unwrap());
+        db.complete_tool_call(&finish("t1", "2026-03-01T10:00:01.000Z", false)).unwrap();
+        db.complete_tool_call(&finish("t2", "2026-03-01T10:01:03.000Z", true)).unwrap();
+        db.complete_tool_call(&finish("t4", "2026-03-01T10:03:00.500Z", true)).unwrap();
+        // A second result for the same call does not move it.
+        db.complete_tool_call(&finish("t1", "2026-03-01T10:09:00.000Z", true)).unwrap();
+
+        let report = db
+            .get_tool_stats(Some("2026-03-01T00:00:00.000Z"), Some("2026-03-02T00:00:00.000Z"))
+            .unwrap();
+
+        assert_eq!(report.tools.len(), 3);
+        let read = &report.tools[0];
+        assert_eq!(read.name, "Read");
+        assert_eq!((read.calls, read.errors), (3, 1));
+        assert!((read.error_rate - 1.0 / 3.0).abs() < 1e-9);
+        // t3 is still waiting for its result.
+        assert_eq!(read.avg_duration_ms, Some(2000.0));
+        assert_eq!(read.avg_input_bytes, 200.0);
+        assert!((read.total_cost - 0.06).abs() < 1e-9);
+        assert!(report.tools.iter().all(|t| t.name != "Bash"));
+
+        assert_eq!(report.mcp_servers.len(), 1);
+        let github = &report.mcp_servers[0];
+        assert_eq!(github.name, "mcp__github");
+        assert_eq!(github.server.as_deref(), Some("github"));
+        assert_eq!((github.calls, github.errors), (2, 1));
+        assert_eq!(github.avg_duration_ms, Some(500.0));
+        assert!((github.total_cost - 0.12).abs() < 1e-9);
+    }
+}
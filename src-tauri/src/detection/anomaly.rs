@@ -0,0 +1,276 @@
+use chrono::{Duration, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::db::anomaly::AnomalyAlert;
+use crate::db::calendar::{self, Bucket};
+use crate::db::{Database, MessageRecord, SessionRecord};
+
+/// Modified z-score above which a value is flagged (Iglewicz and Hoaglin).
+const SCORE_THRESHOLD: f64 = 3.5;
+/// Fewer samples than this and the project has no baseline yet.
+const MIN_SAMPLES: usize = 8;
+const SESSION_BASELINE_DAYS: i64 = 90;
+const DAY_BASELINE_DAYS: i64 = 28;
+/// How far back completed days are checked after the app was closed.
+const MAX_UNCHECKED_DAYS: i64 = 28;
+const MESSAGE_SAMPLE_LIMIT: u32 = 5000;
+const BASELINE_REFRESH_SECS: u64 = 10 * 60;
+
+/// Median and median absolute deviation of a metric.
+#[derive(Debug, Clone, Copy)]
+pub struct Baseline {
+    pub median: f64,
+    pub mad: f64,
+}
+
+impl Baseline {
+    pub fn from_samples(mut values: Vec<f64>) -> Option<Self> {
+        if values.len() < MIN_SAMPLES {
+            return None;
+        }
+        let mid = median(&mut values);
+        let mut deviations: Vec<f64> = values.iter().map(|v| (v - mid).abs()).collect();
+        Some(Self { median: mid, mad: median(&mut deviations) })
+    }
+
+    /// Modified z-score of `value`. When most samples are identical the MAD is
+    /// zero, so it is floored at a tenth of the median instead.
+    pub fn score(&self, value: f64) -> f64 {
+        let mad = self.mad.max(self.median.abs() * 0.1).max(f64::EPSILON);
+        0.6745 * (value - self.median) / mad
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+struct ProjectSamples {
+    loaded_at: Instant,
+    session_costs: Vec<(String, f64)>,
+    message_tokens: Option<Baseline>,
+}
+
+/// Flags sessions and days whose cost or token use is far above what is
+/// usual for their project. Only the high side is flagged.
+pub struct AnomalyDetector {
+    projects: Mutex<HashMap<String, ProjectSamples>>,
+}
+
+impl AnomalyDetector {
+    pub fn new() -> Self {
+        Self {
+            projects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks a session the watcher just updated, with the messages that were
+    /// added. Returns the alerts not raised for this session before.
+    pub fn check_session(
+        &self,
+        db: &Database,
+        session: &SessionRecord,
+        messages: &[&MessageRecord],
+    ) -> Result<Vec<AnomalyAlert>, String> {
+        let mut projects = self.projects.lock().map_err(|e| e.to_string())?;
+        let fresh = projects
+            .get(&session.project)
+            .is_some_and(|p| p.loaded_at.elapsed().as_secs() <= BASELINE_REFRESH_SECS);
+        if !fresh {
+            let since = calendar::format_utc(Utc::now() - Duration::days(SESSION_BASELINE_DAYS));
+            let samples = ProjectSamples {
+                loaded_at: Instant::now(),
+                session_costs: db.project_session_costs(&session.project, &since)?,
+                message_tokens: Baseline::from_samples(
+                    db.project_message_tokens(&session.project, &since, MESSAGE_SAMPLE_LIMIT)?,
+                ),
+            };
+            projects.insert(session.project.clone(), samples);
+        }
+        let samples = match projects.get(&session.project) {
+            Some(samples) => samples,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut alerts = Vec::new();
+
+        let other_sessions = samples
+            .session_costs
+            .iter()
+            .filter(|(id, _)| *id != session.id)
+            .map(|(_, cost)| *cost)
+            .collect();
+        if let Some(baseline) = Baseline::from_samples(other_sessions) {
+            let score = baseline.score(session.total_cost);
+            if score >= SCORE_THRESHOLD {
+                alerts.push(alert("session", &session.project, &session.id, "session_cost",
+                    session.total_cost, baseline,
+                    format!(
+                        "Session has cost ${:.2} so far, against a typical ${:.2} per session in this project",
+                        session.total_cost, baseline.median
+                    )));
+            }
+        }
+
+        if let Some(baseline) = samples.message_tokens {
+            let largest = messages
+                .iter()
+                .map(|m| (m.input_tokens + m.output_tokens + m.cache_read_tokens + m.cache_creation_tokens) as f64)
+                .fold(0.0, f64::max);
+            let score = baseline.score(largest);
+            if score >= SCORE_THRESHOLD {
+                alerts.push(alert("session", &session.project, &session.id, "message_tokens",
+                    largest, baseline,
+                    format!(
+                        "A message used {:.0} tokens, against a typical {:.0} per message in this project",
+                        largest, baseline.median
+                    )));
+            }
+        }
+
+        drop(projects);
+        store(db, alerts)
+    }
+
+    /// Checks the cost per project of every day completed since the last
+    /// check (yesterday on the first run, four weeks at most) against the
+    /// active days of the four weeks before each.
+    pub fn check_completed_days(&self, db: &Database) -> Result<Vec<AnomalyAlert>, String> {
+        let tz = db.get_timezone()?;
+        let today = calendar::bucket_start(Utc::now(), tz, Bucket::Day);
+        let yesterday = today - Duration::days(1);
+        let first = match db.get_last_checked_day()? {
+            Some(last) if last >= yesterday => return Ok(Vec::new()),
+            Some(last) => (last + Duration::days(1)).max(today - Duration::days(MAX_UNCHECKED_DAYS)),
+            None => yesterday,
+        };
+        let since = calendar::local_midnight_utc(first - Duration::days(DAY_BASELINE_DAYS), tz);
+        let until = calendar::local_midnight_utc(today, tz);
+
+        let mut alerts = Vec::new();
+        for (project, days) in db.project_daily_costs(&calendar::format_utc(since), &calendar::format_utc(until))? {
+            for (&date, &cost) in days.range(first..) {
+                let history = days
+                    .range(date - Duration::days(DAY_BASELINE_DAYS)..date)
+                    .map(|(_, c)| *c)
+                    .collect();
+                let baseline = match Baseline::from_samples(history) {
+                    Some(baseline) => baseline,
+                    None => continue,
+                };
+                let score = baseline.score(cost);
+                if score >= SCORE_THRESHOLD {
+                    let day = date.format("%Y-%m-%d").to_string();
+                    let reason = format!(
+                        "{} cost ${:.2}, against a typical ${:.2} per active day in this project",
+                        day, cost, baseline.median
+                    );
+                    alerts.push(alert("day", &project, &day, "daily_cost", cost, baseline, reason));
+                }
+            }
+        }
+        let alerts = store(db, alerts)?;
+        db.set_last_checked_day(yesterday)?;
+        Ok(alerts)
+    }
+}
+
+fn alert(
+    kind: &str,
+    project: &str,
+    subject: &str,
+    metric: &str,
+    value: f64,
+    baseline: Baseline,
+    reason: String,
+) -> AnomalyAlert {
+    AnomalyAlert {
+        id: 0,
+        kind: kind.to_string(),
+        project: project.to_string(),
+        subject: subject.to_string(),
+        metric: metric.to_string(),
+        value,
+        baseline_median: baseline.median,
+        baseline_mad: baseline.mad,
+        score: baseline.score(value),
+        reason,
+        created_at: calendar::format_utc(Utc::now()),
+    }
+}
+
+/// Persists the alerts and keeps the ones that weren't already stored.
+fn store(db: &Database, alerts: Vec<AnomalyAlert>) -> Result<Vec<AnomalyAlert>, String> {
+    let mut new_alerts = Vec::new();
+    for mut alert in alerts {
+        if let Some(id) = db.insert_anomaly(&alert)? {
+            alert.id = id;
+            new_alerts.push(alert);
+        }
+    }
+    Ok(new_alerts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::{message, session, temp_db};
+
+    fn noon(date: chrono::NaiveDate) -> String {
+        calendar::format_utc(calendar::local_midnight_utc(date, chrono_tz::Tz::UTC) + Duration::hours(12))
+    }
+
+    #[test]
+    fn checks_every_day_since_the_last_check() {
+        let db = temp_db("anomaly-days");
+        db.set_timezone("UTC").unwrap();
+        let today = calendar::bucket_start(Utc::now(), chrono_tz::Tz::UTC, Bucket::Day);
+        let day = |n: i64| today - Duration::days(n);
+        db.upsert_session(&session("s1", &noon(day(20)))).unwrap();
+        let spend = |n: i64, cost: f64| {
+            let key = format!("day-{}", n);
+            db.insert_message(&message("s1", Some(&key), &noon(day(n)), cost)).unwrap();
+        };
+        for n in 11..=20 {
+            spend(n, 1.0 + n as f64 / 100.0);
+        }
+        spend(5, 50.0);
+        spend(3, 60.0);
+        spend(1, 1.1);
+
+        // The app was last running a week ago.
+        db.set_last_checked_day(day(7)).unwrap();
+        let detector = AnomalyDetector::new();
+        let alerts = detector.check_completed_days(&db).unwrap();
+        let mut flagged: Vec<String> = alerts.iter().map(|a| a.subject.clone()).collect();
+        flagged.sort();
+        assert_eq!(flagged, [day(5).to_string(), day(3).to_string()]);
+        assert_eq!(db.get_last_checked_day().unwrap(), Some(day(1)));
+
+        assert!(detector.check_completed_days(&db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn first_check_only_looks_at_yesterday() {
+        let db = temp_db("anomaly-first");
+        db.set_timezone("UTC").unwrap();
+        let today = calendar::bucket_start(Utc::now(), chrono_tz::Tz::UTC, Bucket::Day);
+        let day = |n: i64| today - Duration::days(n);
+        db.upsert_session(&session("s1", &noon(day(20)))).unwrap();
+        for n in 3..=20 {
+            let cost = if n == 3 { 80.0 } else { 1.0 };
+            db.insert_message(&message("s1", Some(&format!("day-{}", n)), &noon(day(n)), cost)).unwrap();
+        }
+
+        assert!(AnomalyDetector::new().check_completed_days(&db).unwrap().is_empty());
+        assert_eq!(db.get_last_checked_day().unwrap(), Some(day(1)));
+    }
+}
@@ -59,8 +59,8 @@ impl LoopDetector {
                 .take_while(|chunk| similarity(chunk, last) > 0.8)
                 .count();
 
-            if repeat_count >= REPEAT_THRESHOLD {
-                window.loop_detected = true;
+            window.loop_detected = repeat_count >= REPEAT_THRESHOLD;
+            if window.loop_detected {
                 window.loop_count += 1;
 
                 let estimated_waste = burn_rate_per_sec * 30.0 * repeat_count as f64;
@@ -77,12 +77,6 @@ impl LoopDetector {
         None
     }
 
-    pub fn reset(&self, agent_id: &str) {
-        if let Ok(mut windows) = self.windows.lock() {
-            windows.remove(agent_id);
-        }
-    }
-
     pub fn is_looping(&self, agent_id: &str) -> bool {
         self.windows
             .lock()
@@ -121,9 +115,45 @@ fn similarity(a: &str, b: &str) -> f64 {
 }
 
 fn truncate(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
-    } else {
-        format!("{}...", &s[..max_len])
+    match s.char_indices().nth(max_len) {
+        Some((end, _)) => format!("{}...", &s[..end]),
+        None => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: &str = "Bash {\"command\":\"cargo test -p parser -- --nocapture flaky_case\"}";
+
+    #[test]
+    fn clears_once_the_output_moves_on() {
+        let detector = LoopDetector::new();
+        assert!(detector.feed("a1", STEP, 0.0).is_none());
+        assert!(detector.feed("a1", STEP, 0.0).is_none());
+        assert_eq!(detector.feed("a1", STEP, 0.0).unwrap().repeat_count, 3);
+        assert!(detector.is_looping("a1"));
+
+        detector.feed("a1", "Read {\"file_path\":\"/work/app/src/parser.rs\",\"offset\":120}", 0.0);
+        assert!(!detector.is_looping("a1"));
+
+        // A second loop is a new one rather than the first still going.
+        for _ in 0..2 {
+            assert!(detector.feed("a1", STEP, 0.0).is_none());
+        }
+        assert!(detector.feed("a1", STEP, 0.0).is_some());
+        assert!(detector.is_looping("a1"));
+    }
+
+    #[test]
+    fn long_multi_byte_patterns_are_cut_between_characters() {
+        let detector = LoopDetector::new();
+        let step = "Écris le résumé → «étape» ".repeat(10);
+        detector.feed("a1", &step, 0.0);
+        detector.feed("a1", &step, 0.0);
+        let alert = detector.feed("a1", &step, 0.0).unwrap();
+        assert!(alert.pattern.ends_with("..."));
+        assert!(detector.is_looping("a1"));
     }
 }
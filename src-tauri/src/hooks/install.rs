@@ -0,0 +1,218 @@
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/// Hook events forwarded to the app. Tool events take a matcher; `*`
+/// matches every tool.
+const HOOK_EVENTS: &[(&str, bool)] = &[
+    ("SessionStart", false),
+    ("PreToolUse", true),
+    ("PostToolUse", true),
+    ("Notification", false),
+    ("Stop", false),
+    ("SubagentStop", false),
+];
+
+const HELPER_NAME: &str = "sonar-hook";
+
+/// The settings file the hooks go in. It is the per-user one, since the
+/// helper's path is specific to this machine.
+pub fn settings_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(".claude").join("settings.local.json")
+}
+
+/// The helper installed next to the running executable, where Tauri puts
+/// the `sonar-hook` sidecar in the bundle and in `tauri dev`.
+pub fn default_helper_path() -> Result<PathBuf, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let helper = exe.with_file_name(format!("{}{}", HELPER_NAME, std::env::consts::EXE_SUFFIX));
+    if !helper.exists() {
+        return Err(format!("Hook helper not found at {}", helper.display()));
+    }
+    Ok(helper)
+}
+
+/// Adds a hook running `helper` for every forwarded event to the project's
+/// settings, replacing any earlier Sonar hooks and keeping everything else.
+/// Returns the settings file written.
+pub fn install_hooks(project_dir: &Path, helper: &Path) -> Result<PathBuf, String> {
+    let path = settings_path(project_dir);
+    let mut settings = read_settings(&path)?;
+    remove_sonar_hooks(&mut settings);
+
+    // Claude Code runs hook commands through the shell.
+    let command = format!("\"{}\"", helper.to_string_lossy());
+    let hooks = settings
+        .as_object_mut()
+        .ok_or_else(|| format!("{} is not a JSON object", path.display()))?
+        .entry("hooks")
+        .or_insert_with(|| json!({}));
+    let hooks = hooks
+        .as_object_mut()
+        .ok_or_else(|| format!("\"hooks\" in {} is not a JSON object", path.display()))?;
+    for (event, takes_matcher) in HOOK_EVENTS {
+        let mut entry = json!({ "hooks": [{ "type": "command", "command": command }] });
+        if *takes_matcher {
+            entry["matcher"] = json!("*");
+        }
+        let entries = hooks.entry(event.to_string()).or_insert_with(|| json!([]));
+        match entries.as_array_mut() {
+            Some(entries) => entries.push(entry),
+            None => return Err(format!("\"hooks.{}\" in {} is not a list", event, path.display())),
+        }
+    }
+
+    write_settings(&path, &settings)?;
+    Ok(path)
+}
+
+/// Removes Sonar's hooks from the project's settings. Returns `false` when
+/// there were none.
+pub fn uninstall_hooks(project_dir: &Path) -> Result<bool, String> {
+    let path = settings_path(project_dir);
+    if !path.exists() {
+        return Ok(false);
+    }
+    let mut settings = read_settings(&path)?;
+    if !remove_sonar_hooks(&mut settings) {
+        return Ok(false);
+    }
+    write_settings(&path, &settings)?;
+    Ok(true)
+}
+
+pub fn hooks_installed(project_dir: &Path) -> Result<bool, String> {
+    let path = settings_path(project_dir);
+    if !path.exists() {
+        return Ok(false);
+    }
+    // Removing from a copy tells whether there is anything to remove.
+    let mut settings = read_settings(&path)?;
+    Ok(remove_sonar_hooks(&mut settings))
+}
+
+fn read_settings(path: &Path) -> Result<Value, String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) if text.trim().is_empty() => Ok(json!({})),
+        Ok(text) => serde_json::from_str(&text).map_err(|e| format!("Can't parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(json!({})),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Writes through a temporary file so Claude Code never reads half a file.
+fn write_settings(path: &Path, settings: &Value) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let mut text = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    text.push('\n');
+    let partial = path.with_extension("json.partial");
+    std::fs::write(&partial, text).map_err(|e| e.to_string())?;
+    std::fs::rename(&partial, path).map_err(|e| e.to_string())
+}
+
+/// Drops every hook that runs the helper, then any matcher or event left
+/// without hooks. Returns whether anything was removed.
+fn remove_sonar_hooks(settings: &mut Value) -> bool {
+    let Some(hooks) = settings.get_mut("hooks").and_then(Value::as_object_mut) else {
+        return false;
+    };
+    let mut removed = false;
+    for entries in hooks.values_mut() {
+        let Some(entries) = entries.as_array_mut() else { continue };
+        for entry in entries.iter_mut() {
+            let Some(commands) = entry.get_mut("hooks").and_then(Value::as_array_mut) else { continue };
+            let before = commands.len();
+            commands.retain(|hook| !is_sonar_hook(hook));
+            removed |= commands.len() != before;
+        }
+        entries.retain(|entry| !entry.get("hooks").and_then(Value::as_array).is_some_and(|h| h.is_empty()));
+    }
+    hooks.retain(|_, entries| !entries.as_array().is_some_and(|e| e.is_empty()));
+    if hooks.is_empty() {
+        if let Some(settings) = settings.as_object_mut() {
+            settings.remove("hooks");
+        }
+    }
+    removed
+}
+
+fn is_sonar_hook(hook: &Value) -> bool {
+    hook.get("command")
+        .and_then(Value::as_str)
+        .and_then(|command| Path::new(command.trim_matches('"')).file_stem())
+        .is_some_and(|stem| stem == HELPER_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::temp_path;
+
+    const HELPER: &str = "/Applications/Sonar.app/Contents/MacOS/sonar-hook";
+
+    fn project(name: &str) -> PathBuf {
+        temp_path(name).parent().unwrap().to_path_buf()
+    }
+
+    fn settings(project_dir: &Path) -> Value {
+        serde_json::from_str(&std::fs::read_to_string(settings_path(project_dir)).unwrap()).unwrap()
+    }
+
+    fn sonar_hooks(settings: &Value, event: &str) -> usize {
+        settings["hooks"][event]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|entry| entry["hooks"].as_array().into_iter().flatten())
+            .filter(|hook| is_sonar_hook(hook))
+            .count()
+    }
+
+    #[test]
+    fn installing_keeps_other_hooks_and_does_not_duplicate() {
+        let dir = project("hooks-install");
+        std::fs::create_dir_all(dir.join(".claude")).unwrap();
+        let theirs = json!({ "matcher": "Bash", "hooks": [{ "type": "command", "command": "./lint.sh" }] });
+        let existing = json!({ "permissions": { "allow": ["Read"] }, "hooks": { "PreToolUse": [theirs.clone()] } });
+        std::fs::write(settings_path(&dir), existing.to_string()).unwrap();
+
+        install_hooks(&dir, Path::new(HELPER)).unwrap();
+        install_hooks(&dir, Path::new(HELPER)).unwrap();
+
+        let installed = settings(&dir);
+        for (event, _) in HOOK_EVENTS {
+            assert_eq!(sonar_hooks(&installed, event), 1, "{}", event);
+        }
+        assert_eq!(installed["hooks"]["PreToolUse"][0], theirs);
+        assert_eq!(installed["hooks"]["PreToolUse"][1]["matcher"], "*");
+        assert_eq!(installed["hooks"]["Stop"][0]["hooks"][0]["command"], format!("\"{}\"", HELPER));
+        assert_eq!(installed["permissions"], existing["permissions"]);
+        assert!(hooks_installed(&dir).unwrap());
+    }
+
+    #[test]
+    fn uninstalling_removes_only_sonar_hooks_and_empty_events() {
+        let dir = project("hooks-uninstall");
+        let theirs = json!({ "matcher": "Bash", "hooks": [{ "type": "command", "command": "./lint.sh" }] });
+        let existing = json!({ "hooks": { "PreToolUse": [theirs.clone()] } });
+        std::fs::create_dir_all(dir.join(".claude")).unwrap();
+        std::fs::write(settings_path(&dir), existing.to_string()).unwrap();
+        install_hooks(&dir, Path::new(HELPER)).unwrap();
+
+        assert!(uninstall_hooks(&dir).unwrap());
+        assert_eq!(settings(&dir), existing);
+        assert!(!hooks_installed(&dir).unwrap());
+        assert!(!uninstall_hooks(&dir).unwrap());
+    }
+
+    #[test]
+    fn uninstalling_the_only_hooks_drops_the_hooks_key() {
+        let dir = project("hooks-only-sonar");
+        assert!(!hooks_installed(&dir).unwrap());
+        install_hooks(&dir, Path::new(HELPER)).unwrap();
+
+        assert!(uninstall_hooks(&dir).unwrap());
+        assert_eq!(settings(&dir), json!({}));
+    }
+}
@@ -0,0 +1,696 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::HookPayload;
+use crate::db::Database;
+
+const POLICY_SETTINGS_KEY: &str = "tool_policy";
+
+/// Programs that only run the command they are given.
+const WRAPPERS: &[&str] = &["sudo", "doas", "env", "nohup", "nice", "time", "command", "exec", "xargs"];
+
+const SHELLS: &[&str] = &["sh", "bash", "zsh", "dash"];
+
+/// Rules checked before every tool call once hooks are installed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicySettings {
+    pub enabled: bool,
+    /// Recursive `rm` of anything but paths inside the working directory.
+    pub deny_rm_rf_outside_cwd: bool,
+    /// Writes to `.env` and `.env.*` files, except examples and templates.
+    pub protect_env_files: bool,
+    /// Most Bash commands one session may run in a minute.
+    pub bash_per_minute: Option<u32>,
+    pub deny_network_tools: bool,
+    /// Tool names denied with `deny_network_tools`. A trailing `*` matches
+    /// any tool with that prefix, e.g. `mcp__fetch__*`.
+    pub network_tools: Vec<String>,
+    /// Programs Bash may not run with `deny_network_tools`.
+    pub network_commands: Vec<String>,
+}
+
+impl Default for PolicySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            deny_rm_rf_outside_cwd: true,
+            protect_env_files: true,
+            bash_per_minute: None,
+            deny_network_tools: false,
+            network_tools: vec!["WebFetch".to_string(), "WebSearch".to_string()],
+            network_commands: ["curl", "wget", "nc", "ncat", "ssh", "scp", "sftp", "telnet", "ftp", "rsync"]
+                .iter()
+                .map(|c| c.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl PolicySettings {
+    pub fn load(db: &Database) -> Result<Self, String> {
+        Ok(db
+            .get_setting(POLICY_SETTINGS_KEY)?
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn save(&self, db: &Database) -> Result<(), String> {
+        if self.bash_per_minute == Some(0) {
+            return Err("Bash commands per minute must be at least 1".to_string());
+        }
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        db.set_setting(POLICY_SETTINGS_KEY, &json)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyDecision {
+    pub allow: bool,
+    /// The rule that denied the call.
+    pub rule: Option<&'static str>,
+    pub reason: String,
+}
+
+impl PolicyDecision {
+    fn allow() -> Self {
+        Self { allow: true, rule: None, reason: "No rule matched".to_string() }
+    }
+
+    fn deny(rule: &'static str, reason: String) -> Self {
+        Self { allow: false, rule: Some(rule), reason }
+    }
+}
+
+/// Decides on PreToolUse calls. Shell commands are read the way a shell
+/// splits them, without running anything, so paths are judged lexically:
+/// a symlink inside the working directory pointing out of it isn't caught.
+#[derive(Default)]
+pub struct PolicyEngine {
+    /// When each session's allowed Bash commands ran, over the last minute.
+    bash_calls: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl PolicyEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn evaluate(&self, settings: &PolicySettings, payload: &HookPayload) -> PolicyDecision {
+        let tool = payload.tool_name.as_deref().unwrap_or_default();
+        let input = payload.tool_input.as_ref().unwrap_or(&Value::Null);
+        let cwd = Path::new(&payload.cwd);
+        let commands = match input.get("command").and_then(Value::as_str) {
+            Some(command) if tool == "Bash" => parse_commands(command, 0),
+            _ => Vec::new(),
+        };
+
+        if settings.deny_network_tools {
+            if settings.network_tools.iter().any(|pattern| tool_matches(pattern, tool)) {
+                return PolicyDecision::deny("network_tool", format!("{} is blocked by the network policy", tool));
+            }
+            if let Some(program) = commands
+                .iter()
+                .map(|c| c.program.as_str())
+                .find(|program| settings.network_commands.iter().any(|c| c == program))
+            {
+                return PolicyDecision::deny("network_tool", format!("`{}` is blocked by the network policy", program));
+            }
+        }
+
+        if settings.deny_rm_rf_outside_cwd {
+            if let Some(reason) = rm_outside_any(&commands, cwd) {
+                return PolicyDecision::deny("rm_rf_outside_cwd", reason);
+            }
+        }
+
+        if settings.protect_env_files {
+            let target = match tool {
+                "Write" | "Edit" | "MultiEdit" => input
+                    .get("file_path")
+                    .and_then(Value::as_str)
+                    .filter(|path| is_env_file(path))
+                    .map(str::to_string),
+                _ => commands.iter().find_map(env_write),
+            };
+            if let Some(target) = target {
+                return PolicyDecision::deny("env_file_write", format!("Writing to {} is blocked by policy", target));
+            }
+        }
+
+        if let (Some(limit), true) = (settings.bash_per_minute, tool == "Bash") {
+            if !self.take_bash_slot(&payload.session_id, limit) {
+                return PolicyDecision::deny(
+                    "bash_rate_limit",
+                    format!("More than {} Bash commands in a minute; wait before running another", limit),
+                );
+            }
+        }
+
+        PolicyDecision::allow()
+    }
+
+    /// Counts a Bash command against the session's limit, unless it is
+    /// already reached.
+    fn take_bash_slot(&self, session_id: &str, limit: u32) -> bool {
+        let Ok(mut calls) = self.bash_calls.lock() else { return true };
+        let now = Instant::now();
+        let window = Duration::from_secs(60);
+        calls.retain(|_, times| {
+            while times.front().is_some_and(|t| now.duration_since(*t) >= window) {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        let times = calls.entry(session_id.to_string()).or_default();
+        if times.len() >= limit as usize {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+}
+
+fn tool_matches(pattern: &str, tool: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => tool.starts_with(prefix),
+        None => pattern == tool,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Word {
+    text: String,
+    /// Holds an unquoted or double-quoted `$` or backtick, so its value is
+    /// only known when the shell runs.
+    expands: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Word(Word),
+    /// `;`, `&`, `|`, `&&`, `||` or a newline.
+    Separator,
+    /// `>` or `>>`.
+    Redirect,
+    /// `<`.
+    Input,
+}
+
+/// One simple command with its wrappers peeled off.
+#[derive(Debug, Default)]
+struct Command {
+    /// File name of the program run.
+    program: String,
+    args: Vec<Word>,
+    /// Files written through `>` and `>>`.
+    redirects: Vec<Word>,
+    /// Run through `xargs`, so some arguments come from its input.
+    via_xargs: bool,
+}
+
+/// Splits a command line into simple commands, following `sh -c` and `eval`
+/// scripts a couple of levels down.
+fn parse_commands(line: &str, depth: u32) -> Vec<Command> {
+    let mut commands = Vec::new();
+    let mut current: Vec<Token> = Vec::new();
+    for token in tokenize(line).into_iter().chain([Token::Separator]) {
+        if let Token::Separator = token {
+            if let Some(command) = simple_command(std::mem::take(&mut current)) {
+                let script = if depth < 2 { nested_script(&command) } else { None };
+                commands.push(command);
+                if let Some(script) = script {
+                    commands.extend(parse_commands(&script, depth + 1));
+                }
+            }
+        } else {
+            current.push(token);
+        }
+    }
+    commands
+}
+
+/// The script a command runs in a new shell: the argument after `-c` (alone
+/// or in an option group such as `-lc`) for a shell, or the arguments of
+/// `eval` run together.
+fn nested_script(command: &Command) -> Option<String> {
+    if command.program == "eval" {
+        let words: Vec<&str> = command.args.iter().map(|w| w.text.as_str()).collect();
+        return Some(words.join(" "));
+    }
+    if !SHELLS.contains(&command.program.as_str()) {
+        return None;
+    }
+    let mut args = command.args.iter();
+    while let Some(arg) = args.next() {
+        let text = arg.text.as_str();
+        if text == "-" || text == "--" || !(text.starts_with('-') || text.starts_with('+')) {
+            // Past the options: a script file, or nothing to follow.
+            return None;
+        }
+        if text.starts_with("--") {
+            continue;
+        }
+        if text.starts_with('-') && text.contains('c') {
+            return args.next().map(|script| script.text.clone());
+        }
+        // `-o pipefail` and `+O extglob` name their option in the next word.
+        if text.ends_with('o') || text.ends_with('O') {
+            args.next();
+        }
+    }
+    None
+}
+
+fn tokenize(line: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word: Option<Word> = None;
+    let mut chars = line.chars().peekable();
+
+    fn finish(tokens: &mut Vec<Token>, word: &mut Option<Word>) {
+        if let Some(word) = word.take() {
+            tokens.push(Token::Word(word));
+        }
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => finish(&mut tokens, &mut word),
+            '\n' | ';' | '&' | '|' | '(' | ')' => {
+                finish(&mut tokens, &mut word);
+                if chars.peek() == Some(&c) {
+                    chars.next();
+                }
+                // `&>` and `|&` redirect or pipe, the `&` alone separates.
+                if c == '&' && chars.peek() == Some(&'>') {
+                    continue;
+                }
+                tokens.push(Token::Separator);
+            }
+            '>' => {
+                // `2>` and `2>&1` are about file descriptors, not words.
+                if word.as_ref().is_some_and(|w| w.text.chars().all(|c| c.is_ascii_digit())) {
+                    word = None;
+                }
+                finish(&mut tokens, &mut word);
+                if chars.peek() == Some(&'>') {
+                    chars.next();
+                }
+                if chars.peek() == Some(&'&') {
+                    chars.next();
+                    while chars.peek().is_some_and(|c| c.is_ascii_digit() || *c == '-') {
+                        chars.next();
+                    }
+                    continue;
+                }
+                tokens.push(Token::Redirect);
+            }
+            '<' => {
+                finish(&mut tokens, &mut word);
+                tokens.push(Token::Input);
+            }
+            '\'' => {
+                let w = word.get_or_insert_with(|| Word { text: String::new(), expands: false });
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    w.text.push(c);
+                }
+            }
+            '"' => {
+                let w = word.get_or_insert_with(|| Word { text: String::new(), expands: false });
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            if let Some(next) = chars.next() {
+                                w.text.push(next);
+                            }
+                        }
+                        '$' | '`' => {
+                            w.expands = true;
+                            w.text.push(c);
+                        }
+                        _ => w.text.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                let w = word.get_or_insert_with(|| Word { text: String::new(), expands: false });
+                if let Some(next) = chars.next() {
+                    if next != '\n' {
+                        w.text.push(next);
+                    }
+                }
+            }
+            _ => {
+                let w = word.get_or_insert_with(|| Word { text: String::new(), expands: false });
+                w.expands |= c == '$' || c == '`';
+                w.text.push(c);
+            }
+        }
+    }
+    finish(&mut tokens, &mut word);
+    tokens
+}
+
+fn simple_command(tokens: Vec<Token>) -> Option<Command> {
+    let mut command = Command::default();
+    let mut words = Vec::new();
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => words.push(word),
+            Token::Redirect => {
+                if let Some(Token::Word(target)) = tokens.next() {
+                    command.redirects.push(target);
+                }
+            }
+            Token::Input => {
+                tokens.next();
+            }
+            Token::Separator => {}
+        }
+    }
+
+    let mut words = words.into_iter().peekable();
+    loop {
+        let word = words.next()?;
+        // Variable assignments before the program.
+        if !word.text.starts_with('-') && word.text.contains('=') {
+            continue;
+        }
+        let program = Path::new(&word.text)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if WRAPPERS.contains(&program.as_str()) {
+            command.via_xargs |= program == "xargs";
+            // The wrapper's own options.
+            while let Some(option) = words.next_if(|w| w.text.starts_with('-') || (program == "env" && w.text.contains('='))) {
+                if takes_value(&program, &option.text) {
+                    words.next();
+                }
+            }
+            continue;
+        }
+        command.program = program;
+        break;
+    }
+    command.args = words.collect();
+    Some(command)
+}
+
+/// Wrapper options whose value is the next word.
+fn takes_value(wrapper: &str, option: &str) -> bool {
+    let values: &[&str] = match wrapper {
+        "sudo" | "doas" => &["-u", "-g", "-h", "-p", "-C", "-D", "-r", "-t", "-U"],
+        "env" => &["-u", "-C", "-S"],
+        "nice" => &["-n"],
+        "xargs" => &["-n", "-I", "-L", "-P", "-d", "-s", "-E", "-a"],
+        _ => &[],
+    };
+    values.contains(&option)
+}
+
+/// Why a recursive `rm` in the command line reaches outside `cwd`, if one
+/// does. A `cd` before it moves where its relative paths start from.
+fn rm_outside_any(commands: &[Command], cwd: &Path) -> Option<String> {
+    let mut dir = Some(normalize(cwd));
+    for command in commands {
+        match command.program.as_str() {
+            "cd" | "pushd" => dir = dir.and_then(|dir| cd_target(command, &dir)),
+            "popd" => dir = None,
+            _ => {
+                if let Some(reason) = rm_outside(command, dir.as_deref(), cwd) {
+                    return Some(reason);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Where a `cd` from `dir` ends up, if that is known before it runs.
+fn cd_target(command: &Command, dir: &Path) -> Option<PathBuf> {
+    match command.args.iter().find(|a| !a.text.starts_with('-') || a.text == "-") {
+        None => dirs::home_dir(),
+        Some(target) if target.expands || target.text == "-" => None,
+        Some(target) => Some(normalize(&dir.join(expand_home(&target.text)))),
+    }
+}
+
+/// Why a recursive `rm` run from `dir` reaches outside `cwd`, if it does.
+/// `dir` is `None` once a `cd` went somewhere only known at run time.
+fn rm_outside(command: &Command, dir: Option<&Path>, cwd: &Path) -> Option<String> {
+    if command.program != "rm" {
+        return None;
+    }
+    let mut recursive = false;
+    let mut paths = Vec::new();
+    let mut options_done = false;
+    for arg in &command.args {
+        if options_done || !arg.text.starts_with('-') || arg.text == "-" {
+            paths.push(arg);
+        } else if arg.text == "--" {
+            options_done = true;
+        } else if let Some(long) = arg.text.strip_prefix("--") {
+            recursive |= long == "recursive";
+        } else {
+            recursive |= arg.text.contains(['r', 'R']);
+        }
+    }
+    if !recursive {
+        return None;
+    }
+    if command.via_xargs {
+        return Some("Recursive rm of paths read by xargs is blocked by policy".to_string());
+    }
+
+    let Some(dir) = dir else {
+        return Some("Recursive rm after a cd to a directory only known when it runs is blocked by policy".to_string());
+    };
+    let cwd = normalize(cwd);
+    for path in paths {
+        if path.expands {
+            return Some(format!("Recursive rm of {} is blocked by policy: its value is only known when it runs", path.text));
+        }
+        let resolved = normalize(&dir.join(expand_home(&path.text)));
+        if resolved == cwd {
+            return Some(format!("Recursive rm of {} would remove the working directory", path.text));
+        }
+        if cwd.as_os_str().is_empty() || !resolved.starts_with(&cwd) {
+            return Some(format!("Recursive rm of {} is outside the working directory {}", path.text, cwd.display()));
+        }
+    }
+    None
+}
+
+/// The `.env` file a shell command writes to, if any.
+fn env_write(command: &Command) -> Option<String> {
+    let paths: Vec<&Word> = command.args.iter().filter(|a| !a.text.starts_with('-')).collect();
+    let written: Vec<&Word> = match command.program.as_str() {
+        "tee" | "touch" | "truncate" => paths,
+        "cp" | "mv" | "install" | "ln" => paths.last().copied().into_iter().collect(),
+        "sed" | "perl" if command.args.iter().any(|a| a.text.starts_with("-i")) => paths,
+        _ => Vec::new(),
+    };
+    written
+        .into_iter()
+        .chain(&command.redirects)
+        .find(|path| is_env_file(&path.text))
+        .map(|path| path.text.clone())
+}
+
+fn is_env_file(path: &str) -> bool {
+    let Some(name) = Path::new(path).file_name().and_then(|n| n.to_str()) else { return false };
+    match name.strip_prefix(".env") {
+        Some("") => true,
+        Some(suffix) => {
+            suffix.starts_with('.') && !matches!(&suffix[1..], "example" | "sample" | "template" | "dist")
+        }
+        None => false,
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), dirs::home_dir()) {
+        (Some(""), Some(home)) => home,
+        (Some(rest), Some(home)) if rest.starts_with('/') => home.join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Resolves `.` and `..` without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(engine: &PolicyEngine, settings: &PolicySettings, session_id: &str, tool: &str, input: Value) -> PolicyDecision {
+        let payload: HookPayload = serde_json::from_value(json!({
+            "hook_event_name": "PreToolUse",
+            "session_id": session_id,
+            "cwd": "/work/app",
+            "tool_name": tool,
+            "tool_input": input,
+        }))
+        .unwrap();
+        engine.evaluate(settings, &payload)
+    }
+
+    fn bash(command: &str) -> PolicyDecision {
+        let settings = PolicySettings { enabled: true, ..PolicySettings::default() };
+        call(&PolicyEngine::new(), &settings, "s1", "Bash", json!({ "command": command }))
+    }
+
+    fn denied(command: &str) -> bool {
+        bash(command).rule == Some("rm_rf_outside_cwd")
+    }
+
+    #[test]
+    fn recursive_rm_follows_cd() {
+        assert!(!denied("rm -rf build"));
+        assert!(!denied("cd src && rm -rf generated"));
+        assert!(!denied("cd src; cd .. && rm -rf target"));
+
+        assert!(denied("cd / && rm -rf home"));
+        assert!(denied("cd .. && rm -rf app"));
+        assert!(denied("cd src && rm -rf ../../other"));
+        assert!(denied("cd && rm -rf projects"));
+        assert!(denied("cd ~ && rm -rf work"));
+        assert!(denied("sh -c 'cd /tmp && rm -r cache'"));
+    }
+
+    #[test]
+    fn recursive_rm_after_unknown_cd_is_denied() {
+        assert!(denied("cd \"$TARGET\" && rm -rf dist"));
+        assert!(denied("cd - && rm -rf dist"));
+        assert!(denied("pushd src && popd && rm -rf dist"));
+        // Only recursive rm is affected.
+        assert!(!denied("cd \"$TARGET\" && rm notes.txt"));
+    }
+
+    #[test]
+    fn scripts_run_by_a_shell_or_eval_are_checked() {
+        assert!(denied("bash -lc 'rm -rf /etc'"));
+        assert!(denied("sh -e -c 'rm -rf /etc'"));
+        assert!(denied("bash -o pipefail -c 'rm -rf /etc'"));
+        assert!(denied("bash --norc -c 'rm -rf /etc'"));
+        assert!(denied("eval 'rm -rf /etc'"));
+        assert!(denied("eval rm -rf /etc"));
+
+        assert!(!denied("bash -lc 'rm -rf build'"));
+        // Arguments to a script file are not a script.
+        assert!(!denied("bash deploy.sh -c 'rm -rf /etc'"));
+    }
+
+    #[test]
+    fn env_files_exclude_examples_and_lookalikes() {
+        assert!(is_env_file(".env"));
+        assert!(is_env_file("/work/app/.env.local"));
+        assert!(is_env_file("config/.env.production"));
+
+        assert!(!is_env_file(".env.example"));
+        assert!(!is_env_file("config/.env.template"));
+        assert!(!is_env_file(".envrc"));
+        assert!(!is_env_file(".environment"));
+        assert!(!is_env_file("env"));
+    }
+
+    #[test]
+    fn env_file_writes_are_denied() {
+        let settings = PolicySettings { enabled: true, ..PolicySettings::default() };
+        let engine = PolicyEngine::new();
+        let rule = |tool: &str, input: Value| call(&engine, &settings, "s1", tool, input).rule;
+        let command = |command: &str| rule("Bash", json!({ "command": command }));
+
+        assert_eq!(rule("Write", json!({ "file_path": "/work/app/.env" })), Some("env_file_write"));
+        assert_eq!(rule("Edit", json!({ "file_path": "/work/app/.env.local" })), Some("env_file_write"));
+        assert_eq!(rule("Write", json!({ "file_path": "/work/app/.env.example" })), None);
+
+        for written in [
+            "echo API_KEY=1 > .env",
+            "echo API_KEY=1 >> config/.env.local",
+            "printf x &> .env",
+            "sed -i 's/old/new/' .env",
+            "sed -i.bak 's/old/new/' .env",
+            "cp .env.example .env",
+            "mv generated .env.production",
+            "echo x | tee -a .env",
+        ] {
+            assert_eq!(command(written), Some("env_file_write"), "{}", written);
+        }
+        for read in ["cat .env", "cp .env backup.txt", "sed 's/old/new/' .env", "echo x > .env.example"] {
+            assert_eq!(command(read), None, "{}", read);
+        }
+
+        let off = PolicySettings { protect_env_files: false, ..settings.clone() };
+        assert_eq!(call(&engine, &off, "s1", "Bash", json!({ "command": "echo x > .env" })).rule, None);
+    }
+
+    #[test]
+    fn bash_commands_are_limited_per_session_and_minute() {
+        let settings = PolicySettings { enabled: true, bash_per_minute: Some(2), ..PolicySettings::default() };
+        let engine = PolicyEngine::new();
+        let run = |session_id: &str| call(&engine, &settings, session_id, "Bash", json!({ "command": "cargo check" }));
+
+        assert!(run("s1").allow);
+        assert!(run("s1").allow);
+        let denied = run("s1");
+        assert_eq!(denied.rule, Some("bash_rate_limit"));
+        assert!(denied.reason.contains("More than 2"));
+        // Denied commands don't take a slot, and other sessions have their own.
+        assert!(!run("s1").allow);
+        assert!(run("s2").allow);
+        // Only Bash counts.
+        assert!(call(&engine, &settings, "s1", "Read", json!({ "file_path": "src/lib.rs" })).allow);
+    }
+
+    #[test]
+    fn network_tools_and_commands_are_denied_when_asked() {
+        let settings = PolicySettings {
+            enabled: true,
+            deny_network_tools: true,
+            network_tools: vec!["WebFetch".to_string(), "mcp__fetch__*".to_string()],
+            ..PolicySettings::default()
+        };
+        let engine = PolicyEngine::new();
+        let rule = |tool: &str, input: Value| call(&engine, &settings, "s1", tool, input).rule;
+        let command = |command: &str| rule("Bash", json!({ "command": command }));
+
+        assert_eq!(rule("WebFetch", json!({ "url": "https://example.com" })), Some("network_tool"));
+        assert_eq!(rule("mcp__fetch__get", json!({})), Some("network_tool"));
+        assert_eq!(rule("WebSearch", json!({ "query": "rust" })), None);
+        assert_eq!(rule("mcp__github__search", json!({})), None);
+
+        assert_eq!(command("curl -s https://example.com"), Some("network_tool"));
+        assert_eq!(command("sudo wget https://example.com"), Some("network_tool"));
+        assert_eq!(command("cargo build && /usr/bin/ssh host"), Some("network_tool"));
+        assert_eq!(command("bash -c 'nc -l 8080'"), Some("network_tool"));
+        assert_eq!(command("git status"), None);
+
+        let off = PolicySettings { deny_network_tools: false, ..settings.clone() };
+        assert!(call(&engine, &off, "s1", "Bash", json!({ "command": "curl https://example.com" })).allow);
+        assert!(call(&engine, &off, "s1", "WebFetch", json!({})).allow);
+    }
+}
@@ -1,25 +1,47 @@
-mod collector;
+// Without the app, the terminal manager and the daily checks have no caller.
+#![cfg_attr(not(feature = "app"), allow(dead_code))]
+
+#[cfg(feature = "app")]
+mod api;
+pub mod collector;
+#[cfg(feature = "app")]
 mod commands;
-mod db;
+pub mod db;
 mod detection;
+#[cfg(feature = "app")]
+mod hooks;
 mod pty;
 
+#[cfg(feature = "app")]
 use std::sync::Arc;
+#[cfg(feature = "app")]
+use tauri::{Emitter, Manager};
 
+/// The desktop app. The `sonar` CLI builds without it (`--no-default-features`),
+/// leaving out Tauri and the webview.
+#[cfg(feature = "app")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let pty_manager = Arc::new(pty::manager::PtyManager::new());
-    let loop_detector = Arc::new(detection::loop_detector::LoopDetector::new());
     let database = Arc::new(db::Database::new().expect("Failed to initialize database"));
-    let session_watcher = Arc::new(collector::watcher::SessionWatcher::new());
+    let session_watcher = Arc::new(collector::watcher::SessionWatcher::new(database.clone()));
 
     let watcher_for_setup = session_watcher.clone();
+    let db_for_reindex = database.clone();
+    let db_for_retention = database.clone();
+    let db_for_anomalies = database.clone();
+    let db_for_otlp = database.clone();
+    let db_for_idle = database.clone();
+    let db_for_api = database.clone();
+    let pty_for_api = pty_manager.clone();
+    let db_for_hooks = database.clone();
+    let pty_for_hooks = pty_manager.clone();
+    let watcher_for_api = session_watcher.clone();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
         .manage(pty_manager)
-        .manage(loop_detector)
         .manage(database)
         .manage(session_watcher)
         .setup(move |app| {
@@ -40,14 +62,101 @@ pub fn run() {
                 // since start() is only called once at startup.
                 let watcher_ptr = Arc::as_ptr(&watcher) as *mut collector::watcher::SessionWatcher;
                 unsafe {
-                    if let Err(e) = (*watcher_ptr).start(handle) {
+                    if let Err(e) = (*watcher_ptr).start(Arc::new(handle)) {
                         log::error!("Failed to start session watcher: {}", e);
                     } else {
                         log::info!("Session watcher started");
                     }
                 }
+
+                // Sessions stored before search and tool calls were indexed
+                match collector::import::reindex_pending(&db_for_reindex) {
+                    Ok(0) => {}
+                    Ok(count) => log::info!("Reindexed {} sessions", count),
+                    Err(e) => log::error!("Reindex failed: {}", e),
+                }
             });
 
+            // Prune old message detail at startup and once a day after that
+            std::thread::spawn(move || loop {
+                match db_for_retention
+                    .get_retention_policy()
+                    .and_then(|policy| db_for_retention.apply_retention(&policy))
+                {
+                    Ok(report) => log::info!(
+                        "Retention: deleted {} messages and {} tool calls or events, cleared {} previews, reclaimed {} bytes",
+                        report.messages_deleted, report.events_deleted, report.previews_cleared, report.bytes_reclaimed
+                    ),
+                    Err(e) => log::error!("Retention job failed: {}", e),
+                }
+                std::thread::sleep(std::time::Duration::from_secs(24 * 60 * 60));
+            });
+
+            // Check each finished day against its project's baseline. Checked
+            // days are remembered, so checking hourly only matters after midnight.
+            let handle = app.handle().clone();
+            std::thread::spawn(move || {
+                let detector = detection::anomaly::AnomalyDetector::new();
+                loop {
+                    match detector.check_completed_days(&db_for_anomalies) {
+                        Ok(alerts) => {
+                            for alert in alerts {
+                                let _ = handle.emit("anomaly-detected", alert);
+                            }
+                        }
+                        Err(e) => log::error!("Anomaly check failed: {}", e),
+                    }
+                    std::thread::sleep(std::time::Duration::from_secs(60 * 60));
+                }
+            });
+
+            // Sessions left running by an agent that has gone quiet, or by a
+            // previous run of the app
+            std::thread::spawn(move || loop {
+                match collector::watcher::complete_idle_sessions(&db_for_idle) {
+                    Ok(0) => {}
+                    Ok(count) => log::info!("Marked {} idle sessions completed", count),
+                    Err(e) => log::error!("Failed to complete idle sessions: {}", e),
+                }
+                std::thread::sleep(std::time::Duration::from_secs(5 * 60));
+            });
+
+            // Send sessions that have gone quiet to the OTLP collector, if set up
+            std::thread::spawn(move || loop {
+                match collector::otlp::OtlpSettings::load(&db_for_otlp) {
+                    Ok(settings) if settings.enabled => {
+                        match collector::otlp::export_settled(&db_for_otlp, &settings) {
+                            Ok(report) if report.sessions > 0 => log::info!(
+                                "OTLP: exported {} sessions, {} spans", report.sessions, report.spans
+                            ),
+                            Ok(_) => {}
+                            Err(e) => log::error!("OTLP export failed: {}", e),
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::error!("Failed to load OTLP settings: {}", e),
+                }
+                std::thread::sleep(std::time::Duration::from_secs(60));
+            });
+
+            // Lifecycle events from Claude Code hooks, via the sonar-hook helper
+            let receiver = Arc::new(hooks::HookReceiver::new(db_for_hooks, pty_for_hooks));
+            if let Err(e) = receiver.start(Arc::new(app.handle().clone())) {
+                log::error!("Failed to start hook receiver: {}", e);
+            }
+
+            // The local HTTP API, when the user has turned it on
+            let api_server = api::ApiServer::new(app.handle(), watcher_for_api);
+            match api::ApiSettings::load(&db_for_api) {
+                Ok(settings) => {
+                    if let Err(e) = api_server.apply(&settings, app.handle().clone(), db_for_api, pty_for_api) {
+                        log::error!("Failed to start API server: {}", e);
+                    }
+                }
+                Err(e) => log::error!("Failed to load API settings: {}", e),
+            }
+            app.manage(api_server);
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -59,13 +168,52 @@ pub fn run() {
             commands::agent::is_agent_alive,
             commands::agent::register_agent_dir,
             commands::agent::get_home_dir,
+            commands::hooks::install_hooks,
+            commands::hooks::uninstall_hooks,
+            commands::hooks::hooks_installed,
+            commands::hooks::get_tool_policy,
+            commands::hooks::set_tool_policy,
+            commands::hooks::get_policy_decisions,
+            commands::api::get_api_settings,
+            commands::api::set_api_settings,
+            commands::api::rotate_api_token,
             commands::stats::get_recent_sessions,
+            commands::stats::query_sessions,
+            commands::stats::export_sessions,
+            commands::stats::export_messages,
+            commands::stats::export_dataset,
+            commands::stats::export_transcript,
+            commands::stats::get_otlp_settings,
+            commands::stats::set_otlp_settings,
+            commands::stats::export_otlp,
             commands::stats::get_session_messages,
+            commands::stats::get_session_context,
             commands::stats::get_daily_costs,
+            commands::stats::get_cost_buckets,
             commands::stats::get_model_stats,
+            commands::stats::get_project_stats,
+            commands::stats::get_branch_stats,
+            commands::stats::get_tool_stats,
+            commands::stats::get_cache_stats,
+            commands::stats::get_usage_blocks,
+            commands::stats::get_current_block,
+            commands::stats::get_block_token_limit,
+            commands::stats::set_block_token_limit,
             commands::stats::get_total_cost_today,
+            commands::stats::get_spend_forecast,
+            commands::stats::get_timezone,
+            commands::stats::set_timezone,
             commands::stats::save_session,
             commands::stats::import_history,
+            commands::stats::search_transcripts,
+            commands::stats::get_search_privacy,
+            commands::stats::set_search_privacy,
+            commands::stats::get_retention_policy,
+            commands::stats::set_retention_policy,
+            commands::stats::run_retention,
+            commands::stats::get_anomalies,
+            commands::stats::get_energy_settings,
+            commands::stats::set_energy_settings,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -3,7 +3,12 @@ use std::collections::HashMap;
 use std::io::{BufReader, Read, Write};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use tauri::{AppHandle, Emitter};
+use std::time::{Duration, Instant};
+
+use crate::collector::watcher::{emit, EventSink};
+
+/// Silence after which a running terminal is taken to be waiting for input.
+const WAITING_AFTER: Duration = Duration::from_secs(10);
 
 #[derive(Clone, serde::Serialize)]
 pub struct PtyOutput {
@@ -17,10 +22,104 @@ pub struct PtyExit {
     pub exit_code: Option<i32>,
 }
 
+/// How much an agent may do without asking, as Claude Code's
+/// `--permission-mode`. `Bypass` skips every prompt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionMode {
+    #[default]
+    Default,
+    AcceptEdits,
+    Plan,
+    #[serde(alias = "bypassPermissions")]
+    Bypass,
+}
+
+impl PermissionMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PermissionMode::Default => "default",
+            PermissionMode::AcceptEdits => "acceptEdits",
+            PermissionMode::Plan => "plan",
+            PermissionMode::Bypass => "bypass",
+        }
+    }
+}
+
+/// Claude Code options for a spawned agent.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AgentOptions {
+    pub permission_mode: PermissionMode,
+    /// Tools, or rules like `Bash(git diff:*)`, allowed without a prompt.
+    pub allowed_tools: Vec<String>,
+    pub disallowed_tools: Vec<String>,
+    /// Directories the agent may use besides its working directory.
+    pub add_dirs: Vec<String>,
+}
+
+impl AgentOptions {
+    /// The `claude` arguments for these options.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        match self.permission_mode {
+            PermissionMode::Default => {}
+            PermissionMode::Bypass => args.push("--dangerously-skip-permissions".to_string()),
+            mode => {
+                args.push("--permission-mode".to_string());
+                args.push(mode.as_str().to_string());
+            }
+        }
+        for (flag, values) in [("--allowedTools", &self.allowed_tools), ("--disallowedTools", &self.disallowed_tools)] {
+            let values: Vec<&str> = values.iter().map(|v| v.trim()).filter(|v| !v.is_empty()).collect();
+            if !values.is_empty() {
+                args.push(flag.to_string());
+                args.extend(values.iter().map(|v| v.to_string()));
+            }
+        }
+        if !self.add_dirs.is_empty() {
+            args.push("--add-dir".to_string());
+            args.extend(self.add_dirs.iter().cloned());
+        }
+        args
+    }
+}
+
+/// The `claude` command line for an agent. The task comes last, after its
+/// own flag, so it can't be read as one of the options' values.
+fn agent_args(options: &AgentOptions, task: &str) -> Vec<String> {
+    let mut args = options.args();
+    if !task.is_empty() {
+        args.push("-p".to_string());
+        args.push(task.to_string());
+    }
+    args
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct PtyInfo {
+    pub agent_id: String,
+    /// `shell` or `agent`.
+    pub kind: String,
+    pub working_dir: String,
+    pub started_at: String,
+    /// `false` once the process has exited but before the PTY is closed.
+    pub running: bool,
+    /// Running, but no output for a while; usually sitting at a prompt.
+    pub waiting: bool,
+    /// Set for agents.
+    pub permission_mode: Option<PermissionMode>,
+}
+
 struct PtySession {
     writer: Box<dyn Write + Send>,
     master: Box<dyn MasterPty + Send>,
     child: Box<dyn portable_pty::Child + Send>,
+    kind: &'static str,
+    working_dir: String,
+    started_at: String,
+    last_output: Arc<Mutex<Instant>>,
+    permission_mode: Option<PermissionMode>,
 }
 
 pub struct PtyManager {
@@ -40,51 +139,57 @@ impl PtyManager {
         working_dir: &str,
         rows: u16,
         cols: u16,
-        app_handle: AppHandle,
+        sink: Arc<dyn EventSink>,
     ) -> Result<(), String> {
-        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".to_string());
+        // The user's login shell, from `$SHELL`.
         let cmd = CommandBuilder::new_default_prog();
-        self.spawn_process(agent_id, cmd, working_dir, rows, cols, app_handle)
+        self.spawn_process(agent_id, "shell", cmd, working_dir, pty_size(rows, cols), sink)
     }
 
+    /// Starts `claude` in `working_dir`, running `task` non-interactively
+    /// when one is given.
     pub fn spawn_agent(
         &self,
         agent_id: &str,
         task: &str,
         working_dir: &str,
-        app_handle: AppHandle,
+        options: &AgentOptions,
+        sink: Arc<dyn EventSink>,
     ) -> Result<(), String> {
-        let mut cmd = CommandBuilder::new("claude");
-        if !task.is_empty() {
-            cmd.arg("--dangerously-skip-permissions");
-            cmd.arg("-p");
-            cmd.arg(task);
+        // Claude Code resolves them against the working directory as well.
+        if let Some(dir) = options.add_dirs.iter().find(|dir| !std::path::Path::new(working_dir).join(dir).is_dir()) {
+            return Err(format!("Not a directory: {}", dir));
         }
+        let mut cmd = CommandBuilder::new("claude");
+        cmd.args(agent_args(options, task));
         cmd.cwd(working_dir);
-        self.spawn_process(agent_id, cmd, working_dir, 40, 120, app_handle)
+        self.spawn_process(agent_id, "agent", cmd, working_dir, pty_size(40, 120), sink)?;
+
+        let mut sessions = self.sessions.lock().map_err(|e| e.to_string())?;
+        if let Some(session) = sessions.get_mut(agent_id) {
+            session.permission_mode = Some(options.permission_mode);
+        }
+        Ok(())
     }
 
     fn spawn_process(
         &self,
         agent_id: &str,
+        kind: &'static str,
         mut cmd: CommandBuilder,
         working_dir: &str,
-        rows: u16,
-        cols: u16,
-        app_handle: AppHandle,
+        size: PtySize,
+        sink: Arc<dyn EventSink>,
     ) -> Result<(), String> {
         let pty_system = native_pty_system();
 
         let pair = pty_system
-            .openpty(PtySize {
-                rows,
-                cols,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
+            .openpty(size)
             .map_err(|e| format!("Failed to open PTY: {}", e))?;
 
         cmd.cwd(working_dir);
+        // Lets the hook helper say which terminal a hook came from.
+        cmd.env("SONAR_AGENT_ID", agent_id);
 
         let child = pair
             .slave
@@ -102,7 +207,8 @@ impl PtyManager {
             .map_err(|e| format!("Failed to take writer: {}", e))?;
 
         let id = agent_id.to_string();
-        let handle = app_handle.clone();
+        let last_output = Arc::new(Mutex::new(Instant::now()));
+        let output_seen = last_output.clone();
 
         thread::spawn(move || {
             let mut buf_reader = BufReader::new(reader);
@@ -111,8 +217,12 @@ impl PtyManager {
                 match buf_reader.read(&mut buf) {
                     Ok(0) => break,
                     Ok(n) => {
+                        if let Ok(mut at) = output_seen.lock() {
+                            *at = Instant::now();
+                        }
                         let data = String::from_utf8_lossy(&buf[..n]).to_string();
-                        let _ = handle.emit(
+                        emit(
+                            sink.as_ref(),
                             "pty-output",
                             PtyOutput {
                                 agent_id: id.clone(),
@@ -124,7 +234,8 @@ impl PtyManager {
                 }
             }
 
-            let _ = handle.emit(
+            emit(
+                sink.as_ref(),
                 "pty-exit",
                 PtyExit {
                     agent_id: id.clone(),
@@ -141,6 +252,11 @@ impl PtyManager {
                     writer,
                     master: pair.master,
                     child,
+                    kind,
+                    working_dir: working_dir.to_string(),
+                    started_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+                    last_output,
+                    permission_mode: None,
                 },
             );
         }
@@ -183,12 +299,7 @@ impl PtyManager {
         if let Some(session) = sessions.get(agent_id) {
             session
                 .master
-                .resize(PtySize {
-                    rows,
-                    cols,
-                    pixel_width: 0,
-                    pixel_height: 0,
-                })
+                .resize(pty_size(rows, cols))
                 .map_err(|e| format!("Failed to resize: {}", e))?;
             Ok(())
         } else {
@@ -196,8 +307,76 @@ impl PtyManager {
         }
     }
 
+    pub fn list(&self) -> Result<Vec<PtyInfo>, String> {
+        let mut sessions = self.sessions.lock().map_err(|e| e.to_string())?;
+        let mut list: Vec<PtyInfo> = sessions
+            .iter_mut()
+            .map(|(agent_id, session)| {
+                let running = matches!(session.child.try_wait(), Ok(None));
+                let quiet = session.last_output.lock().map(|at| at.elapsed() >= WAITING_AFTER).unwrap_or(false);
+                PtyInfo {
+                    agent_id: agent_id.clone(),
+                    kind: session.kind.to_string(),
+                    working_dir: session.working_dir.clone(),
+                    started_at: session.started_at.clone(),
+                    running,
+                    waiting: running && quiet,
+                    permission_mode: session.permission_mode,
+                }
+            })
+            .collect();
+        list.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        Ok(list)
+    }
+
     pub fn is_alive(&self, agent_id: &str) -> bool {
         let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
         sessions.contains_key(agent_id)
     }
 }
+
+fn pty_size(rows: u16, cols: u16) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(options: &AgentOptions, task: &str) -> String {
+        agent_args(options, task).join(" ")
+    }
+
+    #[test]
+    fn permission_modes_map_to_their_flags() {
+        let with_mode = |permission_mode| AgentOptions { permission_mode, ..AgentOptions::default() };
+        assert_eq!(args(&with_mode(PermissionMode::Default), ""), "");
+        assert_eq!(args(&with_mode(PermissionMode::AcceptEdits), ""), "--permission-mode acceptEdits");
+        assert_eq!(args(&with_mode(PermissionMode::Plan), ""), "--permission-mode plan");
+        assert_eq!(args(&with_mode(PermissionMode::Bypass), ""), "--dangerously-skip-permissions");
+    }
+
+    #[test]
+    fn tool_lists_are_trimmed_and_the_task_comes_last() {
+        let options = AgentOptions {
+            permission_mode: PermissionMode::Plan,
+            allowed_tools: vec![" Read ".to_string(), "".to_string(), "Bash(git diff:*)".to_string()],
+            disallowed_tools: vec!["  ".to_string()],
+            add_dirs: vec!["../shared".to_string(), "/opt/docs".to_string()],
+        };
+        assert_eq!(
+            agent_args(&options, "fix the build"),
+            [
+                "--permission-mode", "plan",
+                "--allowedTools", "Read", "Bash(git diff:*)",
+                "--add-dir", "../shared", "/opt/docs",
+                "-p", "fix the build",
+            ]
+        );
+    }
+}
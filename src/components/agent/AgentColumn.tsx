@@ -1,5 +1,5 @@
 import { useCallback } from "react";
-import { useAgentStore } from "../../stores/agentStore";
+import { useAgentStore, type AgentOptions } from "../../stores/agentStore";
 import { useAgent } from "../../hooks/useAgent";
 import { AgentHeader } from "./AgentHeader";
 import { AgentKPI } from "./AgentKPI";
@@ -14,14 +14,22 @@ export function AgentColumn({ agentId }: { agentId: string }) {
   const updateAgent = useAgentStore((s) => s.updateAgent);
   const { spawn, kill, write, resize } = useAgent(agentId);
 
-  const handleSubmit = useCallback(async (task: string, workingDir: string) => {
+  const handleSubmit = useCallback(async (task: string, workingDir: string, options: AgentOptions) => {
     let resolved = workingDir;
     if (resolved === "~" || resolved === "") {
       try { resolved = await invoke<string>("get_home_dir"); } catch { resolved = "/tmp"; }
     } else if (resolved.startsWith("~/")) {
       try { const h = await invoke<string>("get_home_dir"); resolved = resolved.replace("~", h); } catch {}
     }
-    spawn(task, resolved);
+    // Claude Code gets the extra directories as they are, without a shell.
+    let addDirs = options.add_dirs;
+    if (addDirs.some((d) => d === "~" || d.startsWith("~/"))) {
+      try {
+        const h = await invoke<string>("get_home_dir");
+        addDirs = addDirs.map((d) => (d === "~" || d.startsWith("~/") ? d.replace("~", h) : d));
+      } catch {}
+    }
+    spawn(task, resolved, { ...options, add_dirs: addDirs });
   }, [spawn]);
 
   const handleKill = useCallback(() => {
@@ -26,6 +26,11 @@ export function AgentHeader({
             <StatusDot status={agent.status} />
             {model && <span className="text-xs text-sonar-text-muted">· {model}</span>}
             {dir && agent.status !== "idle" && <span className="text-xs text-sonar-text-muted truncate">· {dir}</span>}
+            {agent.status !== "idle" && agent.permissionMode !== "default" && (
+              <span className={`text-xs ${agent.permissionMode === "bypass" ? "text-sonar-danger" : "text-sonar-text-muted"}`} title="Permission mode">
+                · {agent.permissionMode}
+              </span>
+            )}
           </div>
         </div>
         <div className="flex items-center gap-1 shrink-0">
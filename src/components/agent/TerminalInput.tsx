@@ -1,47 +1,108 @@
 import { useState, useRef, useCallback } from "react";
+import type { AgentOptions, PermissionMode } from "../../stores/agentStore";
+
+const PERMISSION_MODES: { value: PermissionMode; label: string }[] = [
+  { value: "default", label: "ask before edits and commands" },
+  { value: "acceptEdits", label: "accept edits, ask before commands" },
+  { value: "plan", label: "plan only, no changes" },
+  { value: "bypass", label: "bypass every prompt" },
+];
+
+// The last mode picked, so someone who runs tasks unattended picks it once.
+const PERMISSION_MODE_KEY = "sonar-permission-mode";
+
+function savedMode(): PermissionMode {
+  const saved = localStorage.getItem(PERMISSION_MODE_KEY);
+  return PERMISSION_MODES.some((m) => m.value === saved) ? (saved as PermissionMode) : "default";
+}
+
+const list = (text: string) => text.split(",").map((t) => t.trim()).filter(Boolean);
 
 export function TerminalInput({
   status,
   onSubmit,
 }: {
   status: "idle" | "running" | "warning" | "error" | "completed";
-  onSubmit: (task: string, workingDir: string) => void;
+  onSubmit: (task: string, workingDir: string, options: AgentOptions) => void;
 }) {
   const [task, setTask] = useState("");
   const [dir, setDir] = useState("~");
+  const [mode, setMode] = useState<PermissionMode>(savedMode);
+  const [allowedTools, setAllowedTools] = useState("");
+  const [disallowedTools, setDisallowedTools] = useState("");
+  const [addDirs, setAddDirs] = useState("");
   const [showDir, setShowDir] = useState(false);
   const inputRef = useRef<HTMLInputElement>(null);
 
+  const changeMode = (next: PermissionMode) => {
+    setMode(next);
+    localStorage.setItem(PERMISSION_MODE_KEY, next);
+  };
+
   const submit = useCallback(() => {
     if (!task.trim()) return;
-    onSubmit(task.trim(), dir || "~");
+    onSubmit(task.trim(), dir || "~", {
+      permission_mode: mode,
+      allowed_tools: list(allowedTools),
+      disallowed_tools: list(disallowedTools),
+      add_dirs: list(addDirs),
+    });
     setTask("");
-  }, [task, dir, onSubmit]);
+  }, [task, dir, mode, allowedTools, disallowedTools, addDirs, onSubmit]);
+
+  const optionRow = (label: string, value: string, onChange: (v: string) => void, placeholder: string) => (
+    <div className="flex items-center gap-2 px-4 py-1.5 border-b border-sonar-border/30" style={{ fontFamily: "SF Mono, Menlo, Monaco, monospace" }}>
+      <span className="text-xs text-sonar-text-muted select-none w-16 shrink-0">{label}</span>
+      <input
+        value={value}
+        onChange={(e) => onChange(e.target.value)}
+        className="flex-1 bg-transparent text-xs text-sonar-text-secondary outline-none"
+        style={{ fontFamily: "inherit" }}
+        placeholder={placeholder}
+      />
+    </div>
+  );
 
   return (
     <div className="shrink-0 border-t border-sonar-border/50">
       {showDir && (
-        <div className="flex items-center gap-2 px-4 py-1.5 border-b border-sonar-border/30" style={{ fontFamily: "SF Mono, Menlo, Monaco, monospace" }}>
-          <span className="text-xs text-sonar-text-muted select-none">dir</span>
-          <input
-            value={dir}
-            onChange={(e) => setDir(e.target.value)}
-            className="flex-1 bg-transparent text-xs text-sonar-text-secondary outline-none"
-            style={{ fontFamily: "inherit" }}
-            placeholder="~/projects/my-project"
-          />
-        </div>
+        <>
+          {optionRow("dir", dir, setDir, "~/projects/my-project")}
+          <div className="flex items-center gap-2 px-4 py-1.5 border-b border-sonar-border/30" style={{ fontFamily: "SF Mono, Menlo, Monaco, monospace" }}>
+            <span className="text-xs text-sonar-text-muted select-none w-16 shrink-0">mode</span>
+            <select
+              value={mode}
+              onChange={(e) => changeMode(e.target.value as PermissionMode)}
+              className="flex-1 bg-transparent text-xs text-sonar-text-secondary outline-none"
+              style={{ fontFamily: "inherit" }}
+            >
+              {PERMISSION_MODES.map((m) => (
+                <option key={m.value} value={m.value}>{m.value} — {m.label}</option>
+              ))}
+            </select>
+          </div>
+          {optionRow("allow", allowedTools, setAllowedTools, "Read, Bash(git diff:*)")}
+          {optionRow("deny", disallowedTools, setDisallowedTools, "WebFetch")}
+          {optionRow("add dirs", addDirs, setAddDirs, "~/projects/shared-lib")}
+        </>
       )}
       <div className="flex items-center gap-2 px-4 py-2.5" style={{ fontFamily: "SF Mono, Menlo, Monaco, monospace" }}>
         <button
           onClick={() => setShowDir(!showDir)}
           className="text-sonar-text-muted hover:text-sonar-text-secondary transition-colors select-none"
-          title="Set working directory"
+          title="Working directory and permissions"
         >
           <svg className="w-3.5 h-3.5" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth={1.5}>
             <path strokeLinecap="round" strokeLinejoin="round" d="M2.25 12.75V12A2.25 2.25 0 014.5 9.75h15A2.25 2.25 0 0121.75 12v.75m-8.69-6.44l-2.12-2.12a1.5 1.5 0 00-1.061-.44H4.5A2.25 2.25 0 002.25 6v12a2.25 2.25 0 002.25 2.25h15A2.25 2.25 0 0021.75 18V9a2.25 2.25 0 00-2.25-2.25h-5.379a1.5 1.5 0 01-1.06-.44z" />
           </svg>
         </button>
+        <span
+          onClick={() => setShowDir(true)}
+          className={`text-xs select-none cursor-pointer ${mode === "bypass" ? "text-sonar-danger" : "text-sonar-text-muted"}`}
+          title="Permission mode the task runs in"
+        >
+          {mode}
+        </span>
         <span className="text-sonar-accent text-sm select-none">›</span>
         <input
           ref={inputRef}
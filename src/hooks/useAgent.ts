@@ -1,16 +1,17 @@
 import { invoke } from "@tauri-apps/api/core";
-import { useAgentStore } from "../stores/agentStore";
+import { useAgentStore, type AgentOptions } from "../stores/agentStore";
 
 export function useAgent(agentId: string) {
   const { updateAgent, startAgent, stopAgent } = useAgentStore();
 
-  const spawn = async (task: string, workingDir: string) => {
-    startAgent(agentId, task, workingDir);
+  const spawn = async (task: string, workingDir: string, options: AgentOptions) => {
+    startAgent(agentId, task, workingDir, options.permission_mode);
     try {
       await invoke("spawn_agent", {
         agentId,
         task,
         workingDir,
+        options,
       });
     } catch (err) {
       updateAgent(agentId, { status: "error" });
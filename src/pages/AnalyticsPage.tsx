@@ -4,19 +4,12 @@ import { AreaChart, Area, XAxis, YAxis, Tooltip, ResponsiveContainer, BarChart,
 const isTauri = !!(window as any).__TAURI_INTERNALS__;
 const font = "Menlo, Monaco, 'SF Mono', monospace";
 
-interface DailyCost { date: string; cost: number; tokens: number; sessions: number; }
-interface ModelStats { model: string; total_cost: number; total_tokens: number; session_count: number; avg_cost_per_session: number; avg_tokens_per_session: number; }
+interface DailyCost { date: string; cost: number; tokens: number; sessions: number; energy_wh: number; co2_g: number; }
+interface ModelStats { model: string; total_cost: number; total_tokens: number; session_count: number; avg_cost_per_session: number; avg_tokens_per_session: number; energy_wh: number; co2_g: number; }
 
 const fmtCost = (c: number) => c < 0.01 ? `$${c.toFixed(4)}` : c < 1 ? `$${c.toFixed(3)}` : `$${c.toFixed(2)}`;
 const fmtTokens = (t: number) => t >= 1_000_000 ? `${(t / 1_000_000).toFixed(1)}M` : t >= 1_000 ? `${(t / 1_000).toFixed(1)}k` : `${t}`;
 
-const ENERGY_PER_TOKEN: Record<string, number> = {
-  opus: 0.004,
-  sonnet: 0.001,
-  haiku: 0.0005,
-};
-const CO2_PER_WH = 0.000385; // kg CO2 per Wh (US average grid)
-
 const MODEL_COLORS: Record<string, string> = {
   opus: "#bf5af2",
   sonnet: "#0a84ff",
@@ -52,11 +45,8 @@ export function AnalyticsPage() {
   const totalTokens = daily.reduce((s, d) => s + d.tokens, 0);
   const totalSessions = daily.reduce((s, d) => s + d.sessions, 0);
 
-  const totalEnergy = models.reduce((s, m) => {
-    const key = getModelKey(m.model);
-    return s + (m.total_tokens * (ENERGY_PER_TOKEN[key] || 0.001)) / 1000;
-  }, 0);
-  const totalCO2 = totalEnergy * CO2_PER_WH * 1000;
+  const totalEnergy = models.reduce((s, m) => s + m.energy_wh, 0);
+  const totalCO2 = models.reduce((s, m) => s + m.co2_g, 0);
 
   return (
     <div style={{ padding: 20, fontFamily: font, fontSize: 13, color: "#d1d1d6", overflow: "auto", height: "100%" }}>
@@ -146,8 +136,8 @@ export function AnalyticsPage() {
         <div style={{ color: "#636366", fontSize: 11, textTransform: "uppercase", letterSpacing: 1, marginBottom: 8 }}>Energy Estimation</div>
         <div style={{ background: "#1e1e1e", borderRadius: 8, padding: 16, color: "#9898a0", fontSize: 12, lineHeight: 1.6 }}>
           Based on Luccioni et al. (2023) estimates for LLM inference energy consumption.
-          <br />Opus ≈ 0.004 Wh/1K tokens · Sonnet ≈ 0.001 Wh/1K tokens · Haiku ≈ 0.0005 Wh/1K tokens
-          <br />CO₂ calculated using US average grid intensity (385g CO₂/kWh).
+          <br />Defaults: Opus ≈ 0.004 Wh/1K tokens · Sonnet ≈ 0.001 Wh/1K tokens · Haiku ≈ 0.0005 Wh/1K tokens
+          <br />CO₂ defaults to US average grid intensity (385g CO₂/kWh).
           <div style={{ display: "flex", gap: 24, marginTop: 12, color: "#d1d1d6" }}>
             <span>Total energy: <strong>{totalEnergy.toFixed(3)} Wh</strong></span>
             <span>CO₂: <strong>{totalCO2.toFixed(2)}g</strong></span>
@@ -1,4 +1,5 @@
-import { useState } from "react";
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
 
 const font = "Menlo, Monaco, 'SF Mono', monospace";
 
@@ -7,7 +8,35 @@ interface Settings {
   opusInput: number; opusOutput: number;
   haikuInput: number; haikuOutput: number;
   defaultDir: string;
-  retentionDays: number;
+}
+
+interface RetentionPolicy {
+  retention_days: number;
+  preview_retention_days: number | null;
+}
+
+interface ApiSettings {
+  enabled: boolean;
+  port: number;
+  token: string;
+  metrics: boolean;
+}
+
+interface OtlpSettings {
+  enabled: boolean;
+  endpoint: string;
+  headers: Record<string, string>;
+  service_name: string;
+}
+
+interface PolicySettings {
+  enabled: boolean;
+  deny_rm_rf_outside_cwd: boolean;
+  protect_env_files: boolean;
+  bash_per_minute: number | null;
+  deny_network_tools: boolean;
+  network_tools: string[];
+  network_commands: string[];
 }
 
 const DEFAULT_SETTINGS: Settings = {
@@ -15,9 +44,28 @@ const DEFAULT_SETTINGS: Settings = {
   opusInput: 15, opusOutput: 75,
   haikuInput: 0.25, haikuOutput: 1.25,
   defaultDir: "~",
-  retentionDays: 90,
 };
 
+// Retention used to be saved only in the browser, where it did nothing.
+const RETENTION_MIGRATED_KEY = "sonar-retention-migrated";
+const OLD_DEFAULT_RETENTION_DAYS = 90;
+
+const inputStyle = { background: "#1e1e1e", border: "1px solid #2a2a2e", borderRadius: 6, color: "#d1d1d6", fontSize: 13, fontFamily: font, padding: "4px 8px", outline: "none" };
+
+// OTLP headers are edited as one `name: value` per line.
+function formatHeaders(headers: Record<string, string>): string {
+  return Object.entries(headers).map(([name, value]) => `${name}: ${value}`).join("\n");
+}
+
+function parseHeaders(text: string): Record<string, string> {
+  const headers: Record<string, string> = {};
+  for (const line of text.split("\n")) {
+    const at = line.indexOf(":");
+    if (at > 0) headers[line.slice(0, at).trim()] = line.slice(at + 1).trim();
+  }
+  return headers;
+}
+
 export function SettingsPage() {
   const [settings, setSettings] = useState<Settings>(() => {
     try {
@@ -26,12 +74,113 @@ export function SettingsPage() {
     } catch { return DEFAULT_SETTINGS; }
   });
 
+  const [retention, setRetention] = useState<RetentionPolicy | null>(null);
+  const [api, setApi] = useState<ApiSettings | null>(null);
+  const [apiError, setApiError] = useState<string | null>(null);
+  const [otlp, setOtlp] = useState<OtlpSettings | null>(null);
+  const [otlpHeaders, setOtlpHeaders] = useState("");
+  const [policy, setPolicy] = useState<PolicySettings | null>(null);
+  const [hooksDir, setHooksDir] = useState(settings.defaultDir);
+  const [hooksInstalled, setHooksInstalled] = useState<boolean | null>(null);
+  const [hooksMessage, setHooksMessage] = useState<string | null>(null);
+
+  useEffect(() => {
+    invoke<RetentionPolicy>("get_retention_policy").then((policy) => {
+      if (!localStorage.getItem(RETENTION_MIGRATED_KEY)) {
+        localStorage.setItem(RETENTION_MIGRATED_KEY, "1");
+        // The page saved 90 for everyone, so only a value the user changed
+        // is carried over.
+        let days: unknown;
+        try { days = JSON.parse(localStorage.getItem("sonar-settings") || "{}").retentionDays; } catch { days = undefined; }
+        if (typeof days === "number" && days > 0 && days !== OLD_DEFAULT_RETENTION_DAYS && policy.retention_days === 0) {
+          policy = { ...policy, retention_days: days };
+          invoke("set_retention_policy", { policy }).catch(() => {});
+        }
+      }
+      setRetention(policy);
+    }).catch(() => {});
+    invoke<ApiSettings>("get_api_settings").then(setApi).catch(() => {});
+    invoke<OtlpSettings>("get_otlp_settings").then((settings) => {
+      setOtlp(settings);
+      setOtlpHeaders(formatHeaders(settings.headers));
+    }).catch(() => {});
+    invoke<PolicySettings>("get_tool_policy").then(setPolicy).catch(() => {});
+  }, []);
+
   const update = (key: keyof Settings, value: number | string) => {
     const next = { ...settings, [key]: value };
     setSettings(next);
     localStorage.setItem("sonar-settings", JSON.stringify(next));
   };
 
+  const updateRetentionDays = (days: number) => {
+    if (!retention || !Number.isInteger(days) || days < 0) return;
+    const policy = { ...retention, retention_days: days };
+    setRetention(policy);
+    invoke("set_retention_policy", { policy }).catch(() => {});
+  };
+
+  // Hooks go in a project directory, which may be written with `~`.
+  const resolveDir = async (dir: string) => {
+    const trimmed = dir.trim();
+    if (!trimmed.startsWith("~")) return trimmed;
+    const home = await invoke<string>("get_home_dir");
+    return home + trimmed.slice(1);
+  };
+
+  useEffect(() => {
+    setHooksInstalled(null);
+    setHooksMessage(null);
+    if (!hooksDir.trim()) return;
+    let current = true;
+    resolveDir(hooksDir)
+      .then((projectDir) => invoke<boolean>("hooks_installed", { projectDir }))
+      .then((installed) => { if (current) setHooksInstalled(installed); })
+      .catch(() => {});
+    return () => { current = false; };
+  }, [hooksDir]);
+
+  const toggleHooks = async () => {
+    try {
+      const projectDir = await resolveDir(hooksDir);
+      if (hooksInstalled) {
+        await invoke("uninstall_hooks", { projectDir });
+        setHooksInstalled(false);
+        setHooksMessage(null);
+      } else {
+        const written = await invoke<string>("install_hooks", { projectDir, helperPath: null });
+        setHooksInstalled(true);
+        setHooksMessage(`Added to ${written}`);
+      }
+    } catch (e) {
+      setHooksMessage(String(e));
+    }
+  };
+
+  // Saving restarts the server, so text fields save on blur.
+  const saveApi = (next: ApiSettings) => {
+    setApi(next);
+    invoke("set_api_settings", { settings: next })
+      .then(() => setApiError(null))
+      .catch((e) => setApiError(String(e)));
+  };
+
+  const rotateToken = () => {
+    invoke<ApiSettings>("rotate_api_token")
+      .then((settings) => { setApi(settings); setApiError(null); })
+      .catch((e) => setApiError(String(e)));
+  };
+
+  const saveOtlp = (next: OtlpSettings) => {
+    setOtlp(next);
+    invoke("set_otlp_settings", { settings: next }).catch(() => {});
+  };
+
+  const savePolicy = (next: PolicySettings) => {
+    setPolicy(next);
+    invoke("set_tool_policy", { settings: next }).catch(() => {});
+  };
+
   return (
     <div style={{ padding: 20, fontFamily: font, fontSize: 13, color: "#d1d1d6", overflow: "auto", height: "100%", maxWidth: 600 }}>
       <div style={{ fontSize: 15, fontWeight: 600, marginBottom: 20 }}>Settings</div>
@@ -50,12 +199,98 @@ export function SettingsPage() {
           <input value={settings.defaultDir} onChange={(e) => update("defaultDir", e.target.value)}
             style={{ background: "#1e1e1e", border: "1px solid #2a2a2e", borderRadius: 6, color: "#d1d1d6", fontSize: 13, fontFamily: font, padding: "4px 8px", width: 200, outline: "none" }} />
         </Row>
-        <Row label="Retention (days)">
-          <input type="number" value={settings.retentionDays} onChange={(e) => update("retentionDays", parseInt(e.target.value) || 90)}
+        <Row label="Keep message detail (days, 0 = forever)">
+          <input type="number" min={0} value={retention?.retention_days ?? 0} disabled={!retention} onChange={(e) => updateRetentionDays(parseInt(e.target.value, 10))}
             style={{ background: "#1e1e1e", border: "1px solid #2a2a2e", borderRadius: 6, color: "#d1d1d6", fontSize: 13, fontFamily: font, padding: "4px 8px", width: 80, outline: "none" }} />
         </Row>
       </Section>
 
+      {api && (
+        <Section title="Local API">
+          <Row label="Serve the API on 127.0.0.1">
+            <Toggle checked={api.enabled} onChange={(enabled) => saveApi({ ...api, enabled })} />
+          </Row>
+          <Row label="Port">
+            <input type="number" min={1} max={65535} value={api.port}
+              onChange={(e) => setApi({ ...api, port: parseInt(e.target.value, 10) || 0 })}
+              onBlur={() => saveApi(api)}
+              style={{ ...inputStyle, width: 80 }} />
+          </Row>
+          <Row label="Token">
+            <div style={{ display: "flex", alignItems: "center", gap: 6 }}>
+              <input readOnly value={api.token} onFocus={(e) => e.target.select()} style={{ ...inputStyle, width: 200 }} />
+              <button onClick={rotateToken} style={{ ...inputStyle, cursor: "pointer" }}>Rotate</button>
+            </div>
+          </Row>
+          <Row label="Serve /metrics for Prometheus">
+            <Toggle checked={api.metrics} onChange={(metrics) => saveApi({ ...api, metrics })} />
+          </Row>
+          {apiError && <div style={{ color: "#ff6b6b", paddingTop: 6 }}>{apiError}</div>}
+        </Section>
+      )}
+
+      {otlp && (
+        <Section title="OpenTelemetry export">
+          <Row label="Send finished sessions over OTLP/HTTP">
+            <Toggle checked={otlp.enabled} onChange={(enabled) => saveOtlp({ ...otlp, enabled })} />
+          </Row>
+          <Row label="Endpoint">
+            <input value={otlp.endpoint} onChange={(e) => setOtlp({ ...otlp, endpoint: e.target.value })}
+              onBlur={() => saveOtlp(otlp)} style={{ ...inputStyle, width: 240 }} />
+          </Row>
+          <Row label="Service name">
+            <input value={otlp.service_name} onChange={(e) => setOtlp({ ...otlp, service_name: e.target.value })}
+              onBlur={() => saveOtlp(otlp)} style={{ ...inputStyle, width: 240 }} />
+          </Row>
+          <Row label="Headers (name: value per line)">
+            <textarea value={otlpHeaders} rows={2} onChange={(e) => setOtlpHeaders(e.target.value)}
+              onBlur={() => saveOtlp({ ...otlp, headers: parseHeaders(otlpHeaders) })}
+              style={{ ...inputStyle, width: 240, resize: "vertical" }} />
+          </Row>
+        </Section>
+      )}
+
+      <Section title="Claude Code hooks">
+        <Row label="Project directory">
+          <input value={hooksDir} onChange={(e) => setHooksDir(e.target.value)} style={{ ...inputStyle, width: 240 }} />
+        </Row>
+        <Row label={hooksInstalled ? "Hooks installed" : "Hooks not installed"}>
+          <button onClick={toggleHooks} disabled={hooksInstalled === null} style={{ ...inputStyle, cursor: "pointer" }}>
+            {hooksInstalled ? "Remove" : "Install"}
+          </button>
+        </Row>
+        {hooksMessage && <div style={{ color: "#636366", paddingTop: 6 }}>{hooksMessage}</div>}
+      </Section>
+
+      {policy && (
+        <Section title="Tool policy (needs hooks)">
+          <Row label="Check tool calls before they run">
+            <Toggle checked={policy.enabled} onChange={(enabled) => savePolicy({ ...policy, enabled })} />
+          </Row>
+          <Row label="Deny recursive rm outside the working directory">
+            <Toggle checked={policy.deny_rm_rf_outside_cwd} disabled={!policy.enabled}
+              onChange={(deny_rm_rf_outside_cwd) => savePolicy({ ...policy, deny_rm_rf_outside_cwd })} />
+          </Row>
+          <Row label="Protect .env files">
+            <Toggle checked={policy.protect_env_files} disabled={!policy.enabled}
+              onChange={(protect_env_files) => savePolicy({ ...policy, protect_env_files })} />
+          </Row>
+          <Row label="Deny network tools">
+            <Toggle checked={policy.deny_network_tools} disabled={!policy.enabled}
+              onChange={(deny_network_tools) => savePolicy({ ...policy, deny_network_tools })} />
+          </Row>
+          <Row label="Bash commands per minute (empty = no cap)">
+            <input type="number" min={1} value={policy.bash_per_minute ?? ""} disabled={!policy.enabled}
+              onChange={(e) => {
+                const cap = parseInt(e.target.value, 10);
+                setPolicy({ ...policy, bash_per_minute: cap > 0 ? cap : null });
+              }}
+              onBlur={() => savePolicy(policy)}
+              style={{ ...inputStyle, width: 80 }} />
+          </Row>
+        </Section>
+      )}
+
       <Section title="About">
         <div style={{ color: "#636366", lineHeight: 1.6 }}>
           Sonar v0.2.0<br />
@@ -85,6 +320,13 @@ function Row({ label, children }: { label: string; children: React.ReactNode })
   );
 }
 
+function Toggle({ checked, disabled, onChange }: { checked: boolean; disabled?: boolean; onChange: (v: boolean) => void }) {
+  return (
+    <input type="checkbox" checked={checked} disabled={disabled} onChange={(e) => onChange(e.target.checked)}
+      style={{ accentColor: "#0a84ff", cursor: disabled ? "default" : "pointer" }} />
+  );
+}
+
 function PriceRow({ label, value, onChange }: { label: string; value: number; onChange: (v: number) => void }) {
   return (
     <Row label={label}>
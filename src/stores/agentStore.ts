@@ -7,10 +7,29 @@ export type AgentStatus =
   | "error"
   | "completed";
 
+/** Claude Code's permission mode; `bypass` skips every prompt. */
+export type PermissionMode = "default" | "acceptEdits" | "plan" | "bypass";
+
+/** Options passed to `spawn_agent`, matching the Rust `AgentOptions`. */
+export interface AgentOptions {
+  permission_mode: PermissionMode;
+  allowed_tools: string[];
+  disallowed_tools: string[];
+  add_dirs: string[];
+}
+
+export const DEFAULT_AGENT_OPTIONS: AgentOptions = {
+  permission_mode: "default",
+  allowed_tools: [],
+  disallowed_tools: [],
+  add_dirs: [],
+};
+
 export interface AgentSession {
   id: string;
   task: string;
   workingDir: string;
+  permissionMode: PermissionMode;
   status: AgentStatus;
   model: string;
   totalCost: number;
@@ -33,6 +52,7 @@ function createAgent(id: string): AgentSession {
     id,
     task: "",
     workingDir: "",
+    permissionMode: "default",
     status: "idle",
     model: "unknown",
     totalCost: 0,
@@ -58,7 +78,7 @@ interface AgentStore {
   addAgent: () => string;
   removeAgent: (id: string) => void;
   updateAgent: (id: string, updates: Partial<AgentSession>) => void;
-  startAgent: (id: string, task: string, workingDir: string) => void;
+  startAgent: (id: string, task: string, workingDir: string, permissionMode: PermissionMode) => void;
   stopAgent: (id: string) => void;
   setGlobalCostToday: (cost: number) => void;
   getRunningCount: () => number;
@@ -92,7 +112,7 @@ export const useAgentStore = create<AgentStore>((set, get) => ({
     }));
   },
 
-  startAgent: (id, task, workingDir) => {
+  startAgent: (id, task, workingDir, permissionMode) => {
     set((state) => ({
       agents: state.agents.map((a) =>
         a.id === id
@@ -100,6 +120,7 @@ export const useAgentStore = create<AgentStore>((set, get) => ({
               ...a,
               task,
               workingDir,
+              permissionMode,
               status: "running" as AgentStatus,
               startedAt: Date.now(),
               totalCost: 0,